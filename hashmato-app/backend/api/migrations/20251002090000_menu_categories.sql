@@ -0,0 +1,25 @@
+-- Menu categories table
+CREATE TABLE IF NOT EXISTS menu_categories (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    parent_id INTEGER REFERENCES menu_categories(id) ON DELETE SET NULL,
+    display_order INTEGER NOT NULL DEFAULT 0,
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+ALTER TABLE menu_items
+    ADD COLUMN IF NOT EXISTS category_id INTEGER REFERENCES menu_categories(id) ON DELETE SET NULL;
+
+-- Indexes for performance
+CREATE INDEX IF NOT EXISTS idx_menu_categories_parent_id ON menu_categories (parent_id);
+CREATE INDEX IF NOT EXISTS idx_menu_categories_updated_at ON menu_categories (updated_at);
+CREATE INDEX IF NOT EXISTS idx_menu_items_category_id ON menu_items (category_id);
+
+-- Triggers for updated_at columns
+DROP TRIGGER IF EXISTS trg_menu_categories_updated ON menu_categories;
+CREATE TRIGGER trg_menu_categories_updated
+    BEFORE UPDATE ON menu_categories
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,53 @@
+-- Modifier groups (e.g. "Size", "Add-ons", "Remove") attached to a menu item
+CREATE TABLE IF NOT EXISTS modifier_groups (
+    id SERIAL PRIMARY KEY,
+    menu_item_id INTEGER NOT NULL REFERENCES menu_items(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    min_select INTEGER NOT NULL DEFAULT 0,
+    max_select INTEGER NOT NULL DEFAULT 1,
+    display_order INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    CONSTRAINT chk_modifier_groups_selection CHECK (min_select >= 0 AND max_select >= min_select AND max_select > 0)
+);
+
+-- Options within a modifier group, each with its own price delta
+CREATE TABLE IF NOT EXISTS modifier_options (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES modifier_groups(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    price_delta NUMERIC(12, 2) NOT NULL DEFAULT 0,
+    available BOOLEAN NOT NULL DEFAULT true,
+    display_order INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+-- Modifiers chosen for an order line; names and prices are copied so later menu edits
+-- don't rewrite what was ordered
+CREATE TABLE IF NOT EXISTS order_item_modifiers (
+    id SERIAL PRIMARY KEY,
+    order_item_id INTEGER NOT NULL REFERENCES order_items(id) ON DELETE CASCADE,
+    modifier_option_id INTEGER REFERENCES modifier_options(id) ON DELETE SET NULL,
+    group_name TEXT NOT NULL,
+    option_name TEXT NOT NULL,
+    price_delta NUMERIC(12, 2) NOT NULL DEFAULT 0
+);
+
+-- Indexes for performance
+CREATE INDEX IF NOT EXISTS idx_modifier_groups_menu_item_id ON modifier_groups (menu_item_id);
+CREATE INDEX IF NOT EXISTS idx_modifier_options_group_id ON modifier_options (group_id);
+CREATE INDEX IF NOT EXISTS idx_order_item_modifiers_order_item_id ON order_item_modifiers (order_item_id);
+
+-- Triggers for updated_at columns
+DROP TRIGGER IF EXISTS trg_modifier_groups_updated ON modifier_groups;
+CREATE TRIGGER trg_modifier_groups_updated
+    BEFORE UPDATE ON modifier_groups
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+DROP TRIGGER IF EXISTS trg_modifier_options_updated ON modifier_options;
+CREATE TRIGGER trg_modifier_options_updated
+    BEFORE UPDATE ON modifier_options
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,60 @@
+-- Tax rates; `rate` is a percentage (e.g. 8.250). Inclusive rates are already part of
+-- the menu price, exclusive rates are added on top of it.
+CREATE TABLE IF NOT EXISTS tax_rates (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    rate NUMERIC(6, 3) NOT NULL CHECK (rate >= 0),
+    inclusive BOOLEAN NOT NULL DEFAULT false,
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+-- At most one rate applies to items without their own tax_rate_id
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tax_rates_default ON tax_rates (is_default) WHERE is_default;
+
+ALTER TABLE menu_items
+    ADD COLUMN IF NOT EXISTS tax_rate_id INTEGER REFERENCES tax_rates(id) ON DELETE SET NULL;
+
+-- Prices and tax captured when the order was placed
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS base_price NUMERIC(12, 2),
+    ADD COLUMN IF NOT EXISTS unit_price NUMERIC(12, 2),
+    ADD COLUMN IF NOT EXISTS line_total NUMERIC(12, 2),
+    ADD COLUMN IF NOT EXISTS tax_rate NUMERIC(6, 3) NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS tax_inclusive BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS tax_amount NUMERIC(12, 2) NOT NULL DEFAULT 0;
+
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS subtotal NUMERIC(12, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS tax_total NUMERIC(12, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS total NUMERIC(12, 2) NOT NULL DEFAULT 0;
+
+-- Backfill existing orders from current menu prices, the best information we have
+UPDATE order_items oi
+SET base_price = mi.price::numeric,
+    unit_price = mi.price::numeric + COALESCE(
+        (SELECT SUM(m.price_delta) FROM order_item_modifiers m WHERE m.order_item_id = oi.id), 0
+    )
+FROM menu_items mi
+WHERE mi.id = oi.menu_item_id AND oi.unit_price IS NULL;
+
+UPDATE order_items SET line_total = unit_price * quantity WHERE line_total IS NULL;
+
+UPDATE orders o
+SET subtotal = t.subtotal,
+    total = t.subtotal
+FROM (SELECT order_id, SUM(line_total) AS subtotal FROM order_items GROUP BY order_id) t
+WHERE t.order_id = o.id;
+
+ALTER TABLE order_items
+    ALTER COLUMN base_price SET NOT NULL,
+    ALTER COLUMN unit_price SET NOT NULL,
+    ALTER COLUMN line_total SET NOT NULL;
+
+DROP TRIGGER IF EXISTS trg_tax_rates_updated ON tax_rates;
+CREATE TRIGGER trg_tax_rates_updated
+    BEFORE UPDATE ON tax_rates
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
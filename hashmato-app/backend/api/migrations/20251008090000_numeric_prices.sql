@@ -0,0 +1,8 @@
+-- menu_items.price was created as DOUBLE PRECISION while the API treats it as an exact
+-- decimal. Convert it to NUMERIC(12, 2), which every other money column already uses.
+ALTER TABLE menu_items
+    ALTER COLUMN price TYPE NUMERIC(12, 2) USING round(price::numeric, 2);
+
+-- Orders record the currency they were priced in
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS currency TEXT NOT NULL DEFAULT 'USD';
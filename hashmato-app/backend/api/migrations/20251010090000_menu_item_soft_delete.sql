@@ -0,0 +1,8 @@
+-- Soft delete for menu items so order_items history keeps its menu_item_id
+ALTER TABLE menu_items
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP WITH TIME ZONE;
+
+-- Names only need to be unique among live items, so a deleted item's name can be reused
+ALTER TABLE menu_items DROP CONSTRAINT IF EXISTS menu_items_name_key;
+DROP INDEX IF EXISTS idx_menu_items_name;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_menu_items_name_live ON menu_items (name) WHERE deleted_at IS NULL;
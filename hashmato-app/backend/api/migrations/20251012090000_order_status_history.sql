@@ -0,0 +1,21 @@
+-- Every order status transition, for auditing how long each stage took
+CREATE TABLE IF NOT EXISTS order_status_history (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    from_status TEXT,
+    to_status TEXT NOT NULL,
+    actor TEXT,
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_status_history_order_id ON order_status_history (order_id, changed_at);
+
+-- Seed history for existing orders: creation, plus their current status if it moved on
+INSERT INTO order_status_history (order_id, from_status, to_status, changed_at)
+SELECT id, NULL, 'pending', COALESCE(created_at, now()) FROM orders
+WHERE NOT EXISTS (SELECT 1 FROM order_status_history h WHERE h.order_id = orders.id);
+
+INSERT INTO order_status_history (order_id, from_status, to_status, changed_at)
+SELECT o.id, 'pending', o.status, COALESCE(o.updated_at, now()) FROM orders o
+WHERE o.status <> 'pending'
+  AND NOT EXISTS (SELECT 1 FROM order_status_history h WHERE h.order_id = o.id AND h.from_status IS NOT NULL);
@@ -0,0 +1,23 @@
+-- Per business day, per prefix token counters. Rows are locked by the upsert that bumps
+-- them, so concurrent orders never draw the same number.
+CREATE TABLE IF NOT EXISTS token_sequences (
+    business_date DATE NOT NULL,
+    prefix TEXT NOT NULL,
+    last_number INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (business_date, prefix)
+);
+
+ALTER TABLE queue_tokens
+    ADD COLUMN IF NOT EXISTS token_label TEXT,
+    ADD COLUMN IF NOT EXISTS business_date DATE;
+
+UPDATE queue_tokens
+SET token_label = token_number::text,
+    business_date = COALESCE(created_at, now())::date
+WHERE token_label IS NULL;
+
+ALTER TABLE queue_tokens
+    ALTER COLUMN token_label SET NOT NULL,
+    ALTER COLUMN business_date SET NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_queue_tokens_day_label ON queue_tokens (business_date, token_label);
@@ -0,0 +1,27 @@
+-- Staff accounts. PINs are for quick switching at a till, passwords for back office;
+-- both are stored as argon2 hashes.
+CREATE TABLE IF NOT EXISTS staff (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    username TEXT NOT NULL,
+    pin_hash TEXT,
+    password_hash TEXT,
+    active BOOLEAN NOT NULL DEFAULT true,
+    failed_logins INTEGER NOT NULL DEFAULT 0,
+    locked_until TIMESTAMP WITH TIME ZONE,
+    last_login_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_staff_username ON staff (lower(username));
+
+-- Status changes are now made by a signed-in staff member
+ALTER TABLE order_status_history
+    ADD COLUMN IF NOT EXISTS staff_id INTEGER REFERENCES staff(id) ON DELETE SET NULL;
+
+DROP TRIGGER IF EXISTS trg_staff_updated ON staff;
+CREATE TRIGGER trg_staff_updated
+    BEFORE UPDATE ON staff
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
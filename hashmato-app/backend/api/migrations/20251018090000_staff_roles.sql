@@ -0,0 +1,14 @@
+-- Role assignments. What each role may do is defined in the API (src/permissions.rs);
+-- the database only records who holds which role.
+CREATE TABLE IF NOT EXISTS staff_roles (
+    staff_id INTEGER NOT NULL REFERENCES staff(id) ON DELETE CASCADE,
+    role TEXT NOT NULL CHECK (role IN ('cashier', 'kitchen', 'manager', 'admin')),
+    PRIMARY KEY (staff_id, role)
+);
+
+-- Accounts created before roles existed: keep the first one able to manage staff so the
+-- install isn't locked out, and give everyone else the cashier role they effectively had.
+INSERT INTO staff_roles (staff_id, role)
+SELECT id, CASE WHEN id = (SELECT min(id) FROM staff) THEN 'admin' ELSE 'cashier' END
+FROM staff
+WHERE NOT EXISTS (SELECT 1 FROM staff_roles WHERE staff_roles.staff_id = staff.id);
@@ -0,0 +1,22 @@
+-- Tenders taken against an order. An order may be settled by several payments, e.g.
+-- part cash, part card. `amount` is what counts towards the order; for cash the
+-- customer may hand over more (`tendered`) and receive `change_given` back.
+CREATE TABLE IF NOT EXISTS payments (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    tender TEXT NOT NULL CHECK (tender IN ('cash', 'card', 'other')),
+    amount NUMERIC(12, 2) NOT NULL CHECK (amount > 0),
+    tendered NUMERIC(12, 2) NOT NULL,
+    change_given NUMERIC(12, 2) NOT NULL DEFAULT 0 CHECK (change_given >= 0),
+    reference TEXT,
+    staff_id INTEGER REFERENCES staff(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    CHECK (tendered = amount + change_given)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payments_order ON payments (order_id);
+
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS amount_paid NUMERIC(12, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS payment_status TEXT NOT NULL DEFAULT 'unpaid'
+        CHECK (payment_status IN ('unpaid', 'partially_paid', 'paid'));
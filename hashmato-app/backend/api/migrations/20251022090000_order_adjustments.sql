@@ -0,0 +1,57 @@
+-- Voids (before payment) and refunds (after payment). Order lines are never edited or
+-- deleted; each reversal is recorded here with the quantities it takes back. Amounts
+-- are stored negative so sales reporting can simply sum them with the original sale.
+CREATE TABLE IF NOT EXISTS order_adjustments (
+    id SERIAL PRIMARY KEY,
+    order_id INTEGER NOT NULL REFERENCES orders(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('void', 'refund')),
+    reason_code TEXT NOT NULL,
+    note TEXT,
+    subtotal NUMERIC(12, 2) NOT NULL CHECK (subtotal <= 0),
+    tax_total NUMERIC(12, 2) NOT NULL CHECK (tax_total <= 0),
+    total NUMERIC(12, 2) NOT NULL CHECK (total <= 0),
+    staff_id INTEGER REFERENCES staff(id) ON DELETE SET NULL,
+    -- Manager who approved it with their PIN, when the staff member couldn't on their own
+    approved_by INTEGER REFERENCES staff(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_adjustments_order ON order_adjustments (order_id);
+CREATE INDEX IF NOT EXISTS idx_order_adjustments_created ON order_adjustments (created_at);
+
+CREATE TABLE IF NOT EXISTS order_adjustment_items (
+    id SERIAL PRIMARY KEY,
+    adjustment_id INTEGER NOT NULL REFERENCES order_adjustments(id) ON DELETE CASCADE,
+    order_item_id INTEGER NOT NULL REFERENCES order_items(id) ON DELETE CASCADE,
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    line_total NUMERIC(12, 2) NOT NULL CHECK (line_total <= 0),
+    tax_amount NUMERIC(12, 2) NOT NULL CHECK (tax_amount <= 0)
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_adjustment_items_item ON order_adjustment_items (order_item_id);
+
+-- Refunds go back out through the tenders they came in on, as negative payments that
+-- point at the original payment.
+ALTER TABLE payments
+    ADD COLUMN IF NOT EXISTS refund_of INTEGER REFERENCES payments(id) ON DELETE CASCADE,
+    ADD COLUMN IF NOT EXISTS adjustment_id INTEGER REFERENCES order_adjustments(id) ON DELETE CASCADE;
+
+ALTER TABLE payments DROP CONSTRAINT IF EXISTS payments_amount_check;
+ALTER TABLE payments ADD CONSTRAINT payments_amount_check
+    CHECK ((refund_of IS NULL AND amount > 0) OR (refund_of IS NOT NULL AND amount < 0));
+
+CREATE INDEX IF NOT EXISTS idx_payments_refund_of ON payments (refund_of);
+
+-- Running sum of the order's adjustments; what is owed is `total + adjustment_total`.
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS adjustment_total NUMERIC(12, 2) NOT NULL DEFAULT 0;
+
+-- One row per sale and per reversal, for reporting.
+CREATE OR REPLACE VIEW sales_entries AS
+SELECT o.id AS order_id, 'sale' AS entry_type, NULL::text AS reason_code,
+       o.subtotal, o.tax_total, o.total, o.created_at AS occurred_at
+FROM orders o
+UNION ALL
+SELECT a.order_id, a.kind, a.reason_code,
+       a.subtotal, a.tax_total, a.total, a.created_at
+FROM order_adjustments a;
@@ -0,0 +1,17 @@
+-- Offline clients retry order creation. The key they send (a client-generated UUID or an
+-- Idempotency-Key header) is stored with the order so a retry finds it instead of
+-- inserting a second order; the request hash catches a key reused for a different order.
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS idempotency_key TEXT,
+    ADD COLUMN IF NOT EXISTS request_hash TEXT,
+    ADD COLUMN IF NOT EXISTS token_number INTEGER,
+    ADD COLUMN IF NOT EXISTS token_label TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_orders_idempotency_key
+    ON orders (idempotency_key) WHERE idempotency_key IS NOT NULL;
+
+-- The token is kept on the order too, since queue rows go away once an order is collected.
+UPDATE orders o
+SET token_number = qt.token_number, token_label = qt.token_label
+FROM queue_tokens qt
+WHERE qt.order_id = o.id AND o.token_label IS NULL;
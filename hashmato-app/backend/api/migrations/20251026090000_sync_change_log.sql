@@ -0,0 +1,100 @@
+-- Change log behind the sync cursors. Every insert, update and delete on a synced table
+-- appends a row here; clients pull by `change_id` and keep the last one as their cursor.
+--
+-- `change_id` is handed out when the writing transaction commits, not when the row is
+-- written, by a deferred trigger that holds the `sync_state` row lock until commit. So
+-- change ids become visible strictly in order, and a reader that has seen change N has
+-- also seen everything before it. Timestamps can't promise that.
+CREATE TABLE IF NOT EXISTS change_log (
+    id BIGSERIAL PRIMARY KEY,
+    change_id BIGINT UNIQUE,
+    entity TEXT NOT NULL,
+    entity_id INTEGER NOT NULL,
+    op TEXT NOT NULL CHECK (op IN ('upsert', 'delete')),
+    changed_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS idx_change_log_entity ON change_log (entity, entity_id, change_id);
+
+CREATE TABLE IF NOT EXISTS sync_state (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    last_change_id BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO sync_state (id, last_change_id) VALUES (true, 0) ON CONFLICT (id) DO NOTHING;
+
+CREATE OR REPLACE FUNCTION log_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    row_data JSONB;
+    change_op TEXT;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO change_log (entity, entity_id, op) VALUES (TG_ARGV[0], OLD.id, 'delete');
+        RETURN OLD;
+    END IF;
+
+    -- Soft-deleted rows are deletions as far as clients are concerned
+    row_data := to_jsonb(NEW);
+    change_op := CASE WHEN row_data->>'deleted_at' IS NOT NULL THEN 'delete' ELSE 'upsert' END;
+    INSERT INTO change_log (entity, entity_id, op) VALUES (TG_ARGV[0], NEW.id, change_op);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION assign_change_id()
+RETURNS TRIGGER AS $$
+DECLARE
+    next_id BIGINT;
+BEGIN
+    UPDATE sync_state SET last_change_id = last_change_id + 1 WHERE id RETURNING last_change_id INTO next_id;
+    UPDATE change_log SET change_id = next_id WHERE id = NEW.id;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_change_log_assign ON change_log;
+CREATE CONSTRAINT TRIGGER trg_change_log_assign
+    AFTER INSERT ON change_log
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW
+    EXECUTE FUNCTION assign_change_id();
+
+DROP TRIGGER IF EXISTS trg_menu_items_change ON menu_items;
+CREATE TRIGGER trg_menu_items_change
+    AFTER INSERT OR UPDATE OR DELETE ON menu_items
+    FOR EACH ROW EXECUTE FUNCTION log_change('menu_item');
+
+DROP TRIGGER IF EXISTS trg_menu_categories_change ON menu_categories;
+CREATE TRIGGER trg_menu_categories_change
+    AFTER INSERT OR UPDATE OR DELETE ON menu_categories
+    FOR EACH ROW EXECUTE FUNCTION log_change('menu_category');
+
+DROP TRIGGER IF EXISTS trg_modifier_groups_change ON modifier_groups;
+CREATE TRIGGER trg_modifier_groups_change
+    AFTER INSERT OR UPDATE OR DELETE ON modifier_groups
+    FOR EACH ROW EXECUTE FUNCTION log_change('modifier_group');
+
+DROP TRIGGER IF EXISTS trg_modifier_options_change ON modifier_options;
+CREATE TRIGGER trg_modifier_options_change
+    AFTER INSERT OR UPDATE OR DELETE ON modifier_options
+    FOR EACH ROW EXECUTE FUNCTION log_change('modifier_option');
+
+DROP TRIGGER IF EXISTS trg_tax_rates_change ON tax_rates;
+CREATE TRIGGER trg_tax_rates_change
+    AFTER INSERT OR UPDATE OR DELETE ON tax_rates
+    FOR EACH ROW EXECUTE FUNCTION log_change('tax_rate');
+
+DROP TRIGGER IF EXISTS trg_orders_change ON orders;
+CREATE TRIGGER trg_orders_change
+    AFTER INSERT OR UPDATE OR DELETE ON orders
+    FOR EACH ROW EXECUTE FUNCTION log_change('order');
+
+-- Seed the log with what already exists so a pull from cursor 0 is a full sync
+INSERT INTO change_log (entity, entity_id, op)
+SELECT 'menu_item', id, CASE WHEN deleted_at IS NULL THEN 'upsert' ELSE 'delete' END FROM menu_items
+UNION ALL SELECT 'menu_category', id, 'upsert' FROM menu_categories
+UNION ALL SELECT 'modifier_group', id, 'upsert' FROM modifier_groups
+UNION ALL SELECT 'modifier_option', id, 'upsert' FROM modifier_options
+UNION ALL SELECT 'tax_rate', id, 'upsert' FROM tax_rates
+UNION ALL SELECT 'order', id, 'upsert' FROM orders;
@@ -0,0 +1,76 @@
+-- Closed business days. Closing a day stamps every order, payment and void/refund not yet
+-- on a Z report with the new report's id, so rows that commit while the day is being
+-- closed simply land on the next report instead of falling between two. The figures are
+-- frozen in `report` when the day is closed and can't be changed afterwards.
+CREATE TABLE IF NOT EXISTS z_reports (
+    id SERIAL PRIMARY KEY,
+    business_date DATE NOT NULL,
+    period_start TIMESTAMP WITH TIME ZONE,
+    period_end TIMESTAMP WITH TIME ZONE NOT NULL,
+    opening_float NUMERIC(12, 2) NOT NULL DEFAULT 0,
+    counted_cash NUMERIC(12, 2),
+    -- Cash left in the drawer for the next day; becomes its opening float
+    float_left NUMERIC(12, 2) NOT NULL DEFAULT 0,
+    closed_by INTEGER REFERENCES staff(id),
+    report JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+CREATE OR REPLACE FUNCTION z_reports_immutable()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'Z reports are closed and cannot be changed';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_z_reports_immutable ON z_reports;
+CREATE TRIGGER trg_z_reports_immutable
+    BEFORE UPDATE OR DELETE ON z_reports
+    FOR EACH ROW
+    EXECUTE FUNCTION z_reports_immutable();
+
+-- Rows are stamped before the report row is written, hence the deferred checks
+ALTER TABLE orders
+    ADD COLUMN IF NOT EXISTS z_report_id INTEGER REFERENCES z_reports(id) DEFERRABLE INITIALLY DEFERRED;
+ALTER TABLE payments
+    ADD COLUMN IF NOT EXISTS z_report_id INTEGER REFERENCES z_reports(id) DEFERRABLE INITIALLY DEFERRED;
+ALTER TABLE order_adjustments
+    ADD COLUMN IF NOT EXISTS z_report_id INTEGER REFERENCES z_reports(id) DEFERRABLE INITIALLY DEFERRED;
+
+CREATE INDEX IF NOT EXISTS idx_orders_z_report ON orders (z_report_id);
+CREATE INDEX IF NOT EXISTS idx_payments_z_report ON payments (z_report_id);
+CREATE INDEX IF NOT EXISTS idx_order_adjustments_z_report ON order_adjustments (z_report_id);
+
+CREATE OR REPLACE VIEW sales_entries AS
+SELECT o.id AS order_id, 'sale' AS entry_type, NULL::text AS reason_code,
+       o.subtotal, o.tax_total, o.total, o.created_at AS occurred_at, o.z_report_id
+FROM orders o
+UNION ALL
+SELECT a.order_id, a.kind, a.reason_code,
+       a.subtotal, a.tax_total, a.total, a.created_at, a.z_report_id
+FROM order_adjustments a;
+
+-- Stamping orders at close isn't a change clients need to pull
+CREATE OR REPLACE FUNCTION log_change()
+RETURNS TRIGGER AS $$
+DECLARE
+    row_data JSONB;
+    change_op TEXT;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        INSERT INTO change_log (entity, entity_id, op) VALUES (TG_ARGV[0], OLD.id, 'delete');
+        RETURN OLD;
+    END IF;
+
+    row_data := to_jsonb(NEW);
+    IF TG_OP = 'UPDATE'
+        AND row_data - 'z_report_id' - 'updated_at' = to_jsonb(OLD) - 'z_report_id' - 'updated_at' THEN
+        RETURN NEW;
+    END IF;
+
+    -- Soft-deleted rows are deletions as far as clients are concerned
+    change_op := CASE WHEN row_data->>'deleted_at' IS NOT NULL THEN 'delete' ELSE 'upsert' END;
+    INSERT INTO change_log (entity, entity_id, op) VALUES (TG_ARGV[0], NEW.id, change_op);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
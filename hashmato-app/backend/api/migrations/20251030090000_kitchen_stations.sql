@@ -0,0 +1,50 @@
+-- Kitchen stations (grill, fryer, drinks...). Each menu item is made at one station, and
+-- each order line carries its own prep status so stations can work through their part of
+-- an order independently.
+CREATE TABLE IF NOT EXISTS kitchen_stations (
+    id SERIAL PRIMARY KEY,
+    -- Used in WebSocket topics (`station:grill`), so kept to lowercase letters, digits, - and _
+    code TEXT NOT NULL UNIQUE CHECK (code ~ '^[a-z0-9_-]+$'),
+    name TEXT NOT NULL,
+    display_order INTEGER NOT NULL DEFAULT 0,
+    active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now()
+);
+
+DROP TRIGGER IF EXISTS trg_kitchen_stations_updated ON kitchen_stations;
+CREATE TRIGGER trg_kitchen_stations_updated
+    BEFORE UPDATE ON kitchen_stations
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+DROP TRIGGER IF EXISTS trg_kitchen_stations_change ON kitchen_stations;
+CREATE TRIGGER trg_kitchen_stations_change
+    AFTER INSERT OR UPDATE OR DELETE ON kitchen_stations
+    FOR EACH ROW EXECUTE FUNCTION log_change('kitchen_station');
+
+INSERT INTO kitchen_stations (code, name, display_order) VALUES
+    ('grill', 'Grill', 1),
+    ('fryer', 'Fryer', 2),
+    ('drinks', 'Drinks', 3)
+ON CONFLICT (code) DO NOTHING;
+
+ALTER TABLE menu_items
+    ADD COLUMN IF NOT EXISTS station_id INTEGER REFERENCES kitchen_stations(id) ON DELETE SET NULL;
+
+-- The station is copied onto the line when the order is placed, so moving a menu item to
+-- another station doesn't move tickets already in the kitchen.
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS station_id INTEGER REFERENCES kitchen_stations(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'cooking', 'done')),
+    ADD COLUMN IF NOT EXISTS started_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS done_at TIMESTAMP WITH TIME ZONE;
+
+-- Lines of orders already past the kitchen are done
+UPDATE order_items oi
+SET status = 'done', done_at = o.updated_at
+FROM orders o
+WHERE o.id = oi.order_id AND o.status NOT IN ('pending', 'preparing');
+
+CREATE INDEX IF NOT EXISTS idx_order_items_station_open
+    ON order_items (station_id, status) WHERE status <> 'done';
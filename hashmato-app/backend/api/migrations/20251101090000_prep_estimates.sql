@@ -0,0 +1,9 @@
+-- Prep times behind the wait estimates on queue tokens
+ALTER TABLE menu_items
+    ADD COLUMN IF NOT EXISTS prep_seconds INTEGER NOT NULL DEFAULT 300 CHECK (prep_seconds >= 0);
+
+-- How many orders a station works on at once (e.g. grill space for several tickets)
+ALTER TABLE kitchen_stations
+    ADD COLUMN IF NOT EXISTS capacity INTEGER NOT NULL DEFAULT 1 CHECK (capacity > 0);
+
+CREATE INDEX IF NOT EXISTS idx_order_status_history_changed_at ON order_status_history (changed_at);
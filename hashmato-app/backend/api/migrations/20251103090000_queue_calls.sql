@@ -0,0 +1,18 @@
+-- Queue tokens are called (and called again) when an order is ready, and are archived
+-- instead of deleted once the order is collected, cancelled or never picked up, so the
+-- queue's history stays available for analysis.
+ALTER TABLE queue_tokens
+    ADD COLUMN IF NOT EXISTS ready_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS call_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS last_called_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS collected_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS no_show_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN IF NOT EXISTS archived_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE queue_tokens SET ready_at = created_at WHERE status = 'ready' AND ready_at IS NULL;
+
+ALTER TABLE queue_tokens DROP CONSTRAINT IF EXISTS queue_tokens_status_check;
+ALTER TABLE queue_tokens ADD CONSTRAINT queue_tokens_status_check
+    CHECK (status IN ('waiting', 'ready', 'called', 'collected', 'no_show', 'cancelled'));
+
+CREATE INDEX IF NOT EXISTS idx_queue_tokens_live ON queue_tokens (id) WHERE archived_at IS NULL;
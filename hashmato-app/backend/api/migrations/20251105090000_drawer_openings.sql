@@ -0,0 +1,16 @@
+-- Every time the cash drawer is opened: who opened it and why. Openings are stamped with
+-- the Z report of the day they fall in, like payments, so each close can show how often
+-- the drawer was opened without a sale.
+CREATE TABLE IF NOT EXISTS drawer_openings (
+    id SERIAL PRIMARY KEY,
+    staff_id INTEGER NOT NULL REFERENCES staff(id),
+    reason TEXT NOT NULL CHECK (reason IN ('sale', 'refund', 'no_sale', 'float', 'count')),
+    order_id INTEGER REFERENCES orders(id) ON DELETE SET NULL,
+    note TEXT,
+    opened_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    z_report_id INTEGER REFERENCES z_reports(id) DEFERRABLE INITIALLY DEFERRED,
+    -- Opening the drawer for no sale has to be explained
+    CHECK (reason <> 'no_sale' OR note IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_drawer_openings_z_report ON drawer_openings (z_report_id);
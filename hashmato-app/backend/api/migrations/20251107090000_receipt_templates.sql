@@ -0,0 +1,38 @@
+-- How printed receipts look. Tills fetch the default template and lay receipts out with
+-- it, so branding and wording change without an app update.
+CREATE TABLE IF NOT EXISTS receipt_templates (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    is_default BOOLEAN NOT NULL DEFAULT false,
+    paper_width INTEGER NOT NULL DEFAULT 80 CHECK (paper_width IN (58, 80)),
+    store_name TEXT NOT NULL DEFAULT '',
+    store_address TEXT,
+    tax_id TEXT,
+    header TEXT,
+    footer TEXT,
+    currency_symbol TEXT NOT NULL DEFAULT '$',
+    -- BCP 47 tag; decides thousands and decimal separators
+    locale TEXT NOT NULL DEFAULT 'en-US',
+    -- QR code content with {order_id} and {token} placeholders; no QR code when NULL
+    qr_code TEXT,
+    -- 1-bit raster, base64: rows of ceil(width / 8) bytes, most significant bit leftmost
+    logo_width INTEGER,
+    logo_height INTEGER,
+    logo_data TEXT,
+    -- Wording overrides: order, token, tax_id, subtotal, tax, total
+    labels JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    CHECK ((logo_data IS NULL) = (logo_width IS NULL) AND (logo_data IS NULL) = (logo_height IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_receipt_templates_default ON receipt_templates (is_default) WHERE is_default;
+
+DROP TRIGGER IF EXISTS trg_receipt_templates_updated ON receipt_templates;
+CREATE TRIGGER trg_receipt_templates_updated
+    BEFORE UPDATE ON receipt_templates
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
+
+INSERT INTO receipt_templates (name, is_default) VALUES ('Default', true)
+ON CONFLICT (name) DO NOTHING;
@@ -0,0 +1,37 @@
+-- Print jobs waiting for a till to pick them up. The server never talks to printers:
+-- each till claims the jobs for the printers it drives, prints them and reports back.
+-- A failed attempt goes back in the queue until the job runs out of attempts.
+CREATE TABLE IF NOT EXISTS print_jobs (
+    id SERIAL PRIMARY KEY,
+    -- Printer name as configured on the tills, e.g. 'receipt' or 'kitchen'
+    printer TEXT NOT NULL CHECK (printer ~ '^[a-z0-9_-]+$'),
+    kind TEXT NOT NULL CHECK (kind IN ('receipt')),
+    order_id INTEGER REFERENCES orders(id) ON DELETE CASCADE,
+    -- What to print, captured when the job was queued so reprints match the original
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'claimed', 'printed', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 3 CHECK (max_attempts > 0),
+    -- Failed attempts back off before the job can be claimed again
+    available_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT now(),
+    -- Whoever holds the claim; only they may report the result
+    claimed_by TEXT,
+    claimed_at TIMESTAMP WITH TIME ZONE,
+    last_error TEXT,
+    -- NULL for jobs a kiosk asked for
+    requested_by INTEGER REFERENCES staff(id),
+    printed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT now(),
+    CHECK ((status = 'claimed') = (claimed_by IS NOT NULL))
+);
+
+-- Claims take the oldest queued job per printer
+CREATE INDEX IF NOT EXISTS idx_print_jobs_queued ON print_jobs (printer, id) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_print_jobs_order ON print_jobs (order_id);
+
+DROP TRIGGER IF EXISTS trg_print_jobs_updated ON print_jobs;
+CREATE TRIGGER trg_print_jobs_updated
+    BEFORE UPDATE ON print_jobs
+    FOR EACH ROW
+    EXECUTE FUNCTION set_updated_at();
@@ -0,0 +1,21 @@
+-- Paper kitchen tickets. A station can have a ticket printer; with auto_print on, each
+-- new order queues a ticket for every station it has lines at.
+ALTER TABLE kitchen_stations
+    ADD COLUMN IF NOT EXISTS printer TEXT CHECK (printer ~ '^[a-z0-9_-]+$'),
+    ADD COLUMN IF NOT EXISTS auto_print BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE kitchen_stations DROP CONSTRAINT IF EXISTS kitchen_stations_auto_print_check;
+ALTER TABLE kitchen_stations
+    ADD CONSTRAINT kitchen_stations_auto_print_check CHECK (NOT auto_print OR printer IS NOT NULL);
+
+-- Cooking instructions for one line, e.g. "well done"
+ALTER TABLE order_items
+    ADD COLUMN IF NOT EXISTS note TEXT;
+
+ALTER TABLE print_jobs DROP CONSTRAINT IF EXISTS print_jobs_kind_check;
+ALTER TABLE print_jobs
+    ADD CONSTRAINT print_jobs_kind_check CHECK (kind IN ('receipt', 'kitchen_ticket'));
+
+-- Which station a kitchen ticket is for
+ALTER TABLE print_jobs
+    ADD COLUMN IF NOT EXISTS station_id INTEGER REFERENCES kitchen_stations(id) ON DELETE SET NULL;
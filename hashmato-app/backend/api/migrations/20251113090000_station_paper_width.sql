@@ -0,0 +1,3 @@
+-- Roll width of a station's ticket printer, which need not match the receipt printer's.
+ALTER TABLE kitchen_stations
+    ADD COLUMN IF NOT EXISTS paper_width INTEGER NOT NULL DEFAULT 80 CHECK (paper_width IN (58, 80));
@@ -0,0 +1,4 @@
+-- Openings are recorded before the drawer is kicked. When the kick then fails the till
+-- says why here, and the opening no longer counts as one.
+ALTER TABLE drawer_openings
+    ADD COLUMN IF NOT EXISTS error TEXT;
@@ -0,0 +1,178 @@
+use bigdecimal::BigDecimal;
+use sqlx::FromRow;
+use std::str::FromStr;
+use crate::models::Money;
+use crate::pricing::{price_line, LineTax};
+
+pub const VOID_REASONS: &[&str] = &["entered_in_error", "customer_changed_mind", "out_of_stock", "other"];
+pub const REFUND_REASONS: &[&str] = &["customer_request", "wrong_item", "quality_issue", "overcharged", "other"];
+
+/// When a refund needs a manager to approve it with their PIN.
+#[derive(Debug, Clone)]
+pub struct AdjustmentPolicy {
+    /// Refunds above this total need approval even from staff allowed to refund.
+    pub refund_approval_threshold: Money,
+}
+
+impl AdjustmentPolicy {
+    /// Reads `REFUND_APPROVAL_THRESHOLD` (default 50.00, in the store currency).
+    pub fn from_env() -> Self {
+        let refund_approval_threshold = std::env::var("REFUND_APPROVAL_THRESHOLD")
+            .ok()
+            .map(|v| BigDecimal::from_str(v.trim()).expect("REFUND_APPROVAL_THRESHOLD must be an amount"))
+            .map(Money::new)
+            .unwrap_or_else(|| Money::new(BigDecimal::from(50)));
+
+        AdjustmentPolicy { refund_approval_threshold }
+    }
+}
+
+/// An order line as sold, plus how much of it earlier voids and refunds already took back.
+#[derive(Debug, Clone, FromRow)]
+pub struct SoldLine {
+    pub id: i32,
+    pub quantity: i32,
+    pub unit_price: Money,
+    pub line_total: Money,
+    pub tax_amount: Money,
+    pub tax_rate: BigDecimal,
+    pub tax_inclusive: bool,
+    pub returned_quantity: i32,
+    /// Positive amounts already taken back.
+    pub returned_line_total: Money,
+    pub returned_tax: Money,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReturnedLine {
+    pub line_total: Money,
+    pub tax_amount: Money,
+    /// Line total plus exclusive tax, i.e. what comes off the amount owed.
+    pub total: Money,
+}
+
+impl SoldLine {
+    pub fn remaining_quantity(&self) -> i32 {
+        self.quantity - self.returned_quantity
+    }
+
+    /// Prices taking back `quantity` units. The last units returned get whatever is left of
+    /// the line, so rounding can never make a line return more or less than it sold for.
+    pub fn price_return(&self, quantity: i32) -> ReturnedLine {
+        let (line_total, tax_amount) = if quantity == self.remaining_quantity() {
+            (&self.line_total - &self.returned_line_total, &self.tax_amount - &self.returned_tax)
+        } else {
+            let tax = LineTax { rate: self.tax_rate.clone(), inclusive: self.tax_inclusive };
+            let priced = price_line(&self.unit_price, [], quantity, Some(&tax));
+            (priced.line_total, priced.tax_amount)
+        };
+
+        let total = if self.tax_inclusive { line_total.clone() } else { &line_total + &tax_amount };
+        ReturnedLine { line_total, tax_amount, total }
+    }
+}
+
+/// A payment that can still have money refunded against it.
+#[derive(Debug, Clone)]
+pub struct RefundablePayment {
+    pub id: i32,
+    pub tender: String,
+    /// Original amount less earlier refunds against it.
+    pub remaining: Money,
+}
+
+/// Spreads `amount` over the order's payments, most recent first, never refunding more
+/// than a payment took. Returns `None` if the payments can't cover it.
+pub fn allocate_refund(payments: &[RefundablePayment], amount: &Money) -> Option<Vec<(i32, Money)>> {
+    let mut left = amount.clone();
+    let mut allocations = Vec::new();
+
+    for payment in payments.iter().rev() {
+        if left.is_zero() {
+            break;
+        }
+        if payment.remaining.is_zero() || payment.remaining.is_negative() {
+            continue;
+        }
+        let take = payment.remaining.clone().min(left.clone());
+        left -= &take;
+        allocations.push((payment.id, take));
+    }
+
+    left.is_zero().then_some(allocations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Money {
+        amount.parse().unwrap()
+    }
+
+    /// Three at 3.33 with 10% tax on top: 9.99 plus 1.00 tax, though each unit alone is 0.33.
+    fn sold(returned: &[&ReturnedLine]) -> SoldLine {
+        SoldLine {
+            id: 1,
+            quantity: 3,
+            unit_price: money("3.33"),
+            line_total: money("9.99"),
+            tax_amount: money("1.00"),
+            tax_rate: BigDecimal::from(10),
+            tax_inclusive: false,
+            returned_quantity: returned.len() as i32,
+            returned_line_total: returned.iter().fold(Money::zero(), |sum, r| &sum + &r.line_total),
+            returned_tax: returned.iter().fold(Money::zero(), |sum, r| &sum + &r.tax_amount),
+        }
+    }
+
+    #[test]
+    fn returns_are_priced_like_the_sale() {
+        let returned = sold(&[]).price_return(1);
+        assert_eq!(returned.line_total, money("3.33"));
+        assert_eq!(returned.tax_amount, money("0.33"));
+        assert_eq!(returned.total, money("3.66"));
+    }
+
+    #[test]
+    fn the_last_units_take_whatever_is_left() {
+        let first = sold(&[]).price_return(1);
+        let second = sold(&[&first]).price_return(1);
+        let last = sold(&[&first, &second]).price_return(1);
+        assert_eq!((last.line_total, last.tax_amount), (money("3.33"), money("0.34")));
+
+        let all = sold(&[]).price_return(3);
+        assert_eq!((all.line_total, all.tax_amount), (money("9.99"), money("1.00")));
+    }
+
+    #[test]
+    fn inclusive_tax_does_not_add_to_the_total() {
+        let line = SoldLine { tax_inclusive: true, tax_amount: money("0.91"), ..sold(&[]) };
+        let returned = line.price_return(3);
+        assert_eq!(returned.total, money("9.99"));
+        assert_eq!(returned.tax_amount, money("0.91"));
+    }
+
+    fn payment(id: i32, tender: &str, remaining: &str) -> RefundablePayment {
+        RefundablePayment { id, tender: tender.to_string(), remaining: money(remaining) }
+    }
+
+    #[test]
+    fn refunds_go_back_to_the_latest_payments_first() {
+        let payments = [payment(1, "cash", "10.00"), payment(2, "card", "5.00")];
+        assert_eq!(allocate_refund(&payments, &money("3")), Some(vec![(2, money("3.00"))]));
+        assert_eq!(
+            allocate_refund(&payments, &money("8")),
+            Some(vec![(2, money("5.00")), (1, money("3.00"))]),
+        );
+    }
+
+    #[test]
+    fn refunded_payments_are_skipped_and_shortfalls_refused() {
+        let payments = [payment(1, "cash", "10.00"), payment(2, "card", "0.00")];
+        assert_eq!(allocate_refund(&payments, &money("4")), Some(vec![(1, money("4.00"))]));
+        assert_eq!(allocate_refund(&payments, &money("10.01")), None);
+        assert_eq!(allocate_refund(&[], &money("1")), None);
+        assert_eq!(allocate_refund(&[], &Money::zero()), Some(vec![]));
+    }
+}
@@ -0,0 +1,318 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use crate::models::Money;
+use crate::tokens::TokenSettings;
+
+/// Longest range one request may cover.
+pub const MAX_RANGE_DAYS: i64 = 366;
+/// Range used when `from` is left out.
+pub const DEFAULT_RANGE_DAYS: i64 = 30;
+
+/// `?from=2025-10-01&to=2025-10-31&format=csv`. Dates are business days in the store
+/// timezone (see `TokenSettings`), both ends included; `to` defaults to today.
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// `json` (the default) or `csv`.
+    pub format: Option<String>,
+    /// Item ranking: `quantity` (the default) or `revenue`.
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    /// The range up to `to`, starting at `from` or the default number of days before.
+    pub fn ending(from: Option<NaiveDate>, to: NaiveDate) -> Result<Self, String> {
+        let from = from.unwrap_or(to - chrono::Duration::days(DEFAULT_RANGE_DAYS - 1));
+
+        if from > to {
+            return Err("from must not be after to".to_string());
+        }
+        if (to - from).num_days() >= MAX_RANGE_DAYS {
+            return Err(format!("Date range cannot exceed {} days", MAX_RANGE_DAYS));
+        }
+        Ok(DateRange { from, to })
+    }
+}
+
+/// Sales are ex tax and net of voids and refunds, which count against the day the order
+/// was placed. Cancelled and fully refunded orders are left out.
+const ORDER_SALES: &str = r#"
+    WITH sales AS (
+        SELECT o.id, o.source,
+               o.created_at AT TIME ZONE $1 AS local_at,
+               o.total - o.tax_total + COALESCE(a.net, 0) AS net_sales
+        FROM orders o
+        LEFT JOIN (
+            SELECT order_id, SUM(total - tax_total) AS net
+            FROM order_adjustments
+            GROUP BY order_id
+        ) a ON a.order_id = o.id
+        WHERE o.status NOT IN ('cancelled', 'refunded')
+          AND ((o.created_at AT TIME ZONE $1) - make_interval(hours => $2))::date BETWEEN $3 AND $4
+    )
+"#;
+
+/// A report row that can be exported; `COLUMNS` are its field names in order, so an
+/// export with no rows still gets a header line.
+pub trait CsvRow: Serialize {
+    const COLUMNS: &'static [&'static str];
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ItemSales {
+    pub rank: i64,
+    pub menu_item_id: i32,
+    pub name: String,
+    pub quantity: i64,
+    pub net_sales: Money,
+    pub order_count: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct HourSales {
+    /// 0–23, store local time.
+    pub hour: i32,
+    pub order_count: i64,
+    pub net_sales: Money,
+    pub average_ticket: Money,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WeekdaySales {
+    /// ISO numbering: 1 is Monday, 7 is Sunday.
+    pub day_of_week: i32,
+    pub day_name: String,
+    pub order_count: i64,
+    pub net_sales: Money,
+    pub average_ticket: Money,
+}
+
+/// One cell of the weekday × hour grid.
+#[derive(Debug, Serialize, FromRow)]
+pub struct HeatmapCell {
+    pub day_of_week: i32,
+    pub hour: i32,
+    pub order_count: i64,
+    pub net_sales: Money,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SourceSales {
+    pub source: String,
+    pub order_count: i64,
+    pub net_sales: Money,
+    pub average_ticket: Money,
+}
+
+impl CsvRow for ItemSales {
+    const COLUMNS: &'static [&'static str] = &["rank", "menu_item_id", "name", "quantity", "net_sales", "order_count"];
+}
+
+impl CsvRow for HourSales {
+    const COLUMNS: &'static [&'static str] = &["hour", "order_count", "net_sales", "average_ticket"];
+}
+
+impl CsvRow for WeekdaySales {
+    const COLUMNS: &'static [&'static str] = &["day_of_week", "day_name", "order_count", "net_sales", "average_ticket"];
+}
+
+impl CsvRow for HeatmapCell {
+    const COLUMNS: &'static [&'static str] = &["day_of_week", "hour", "order_count", "net_sales"];
+}
+
+impl CsvRow for SourceSales {
+    const COLUMNS: &'static [&'static str] = &["source", "order_count", "net_sales", "average_ticket"];
+}
+
+/// Today's business day in the store timezone.
+pub async fn business_today(pool: &PgPool, settings: &TokenSettings) -> Result<NaiveDate, sqlx::Error> {
+    sqlx::query_scalar("SELECT ((now() AT TIME ZONE $1) - make_interval(hours => $2))::date")
+        .bind(&settings.timezone)
+        .bind(settings.cutoff_hour as i32)
+        .fetch_one(pool)
+        .await
+}
+
+async fn fetch<T>(pool: &PgPool, settings: &TokenSettings, range: DateRange, query: &str) -> Result<Vec<T>, sqlx::Error>
+where
+    T: for<'r> FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+{
+    sqlx::query_as::<_, T>(&format!("{}{}", ORDER_SALES, query))
+        .bind(&settings.timezone)
+        .bind(settings.cutoff_hour as i32)
+        .bind(range.from)
+        .bind(range.to)
+        .fetch_all(pool)
+        .await
+}
+
+/// Items ranked by units sold, or by revenue when `by_revenue` is set.
+pub async fn item_sales(
+    pool: &PgPool,
+    settings: &TokenSettings,
+    range: DateRange,
+    by_revenue: bool,
+) -> Result<Vec<ItemSales>, sqlx::Error> {
+    let order_by = if by_revenue { "net_sales DESC, quantity DESC" } else { "quantity DESC, net_sales DESC" };
+    let query = format!(
+        r#"
+        SELECT RANK() OVER (ORDER BY {order_by}) AS rank, *
+        FROM (
+            SELECT oi.menu_item_id, m.name,
+                   SUM(oi.quantity - COALESCE(r.quantity, 0))::bigint AS quantity,
+                   SUM(
+                       oi.line_total + COALESCE(r.line_total, 0)
+                       - CASE WHEN oi.tax_inclusive THEN oi.tax_amount + COALESCE(r.tax_amount, 0) ELSE 0 END
+                   ) AS net_sales,
+                   COUNT(DISTINCT oi.order_id) AS order_count
+            FROM sales s
+            JOIN order_items oi ON oi.order_id = s.id
+            JOIN menu_items m ON m.id = oi.menu_item_id
+            LEFT JOIN (
+                SELECT order_item_id, SUM(quantity) AS quantity,
+                       SUM(line_total) AS line_total, SUM(tax_amount) AS tax_amount
+                FROM order_adjustment_items
+                GROUP BY order_item_id
+            ) r ON r.order_item_id = oi.id
+            GROUP BY oi.menu_item_id, m.name
+        ) items
+        ORDER BY rank, menu_item_id
+        "#
+    );
+    fetch(pool, settings, range, &query).await
+}
+
+/// All 24 hours, including the ones with no sales.
+pub async fn hour_sales(pool: &PgPool, settings: &TokenSettings, range: DateRange) -> Result<Vec<HourSales>, sqlx::Error> {
+    fetch(pool, settings, range, r#"
+        SELECT h.hour,
+               COUNT(s.id) AS order_count,
+               COALESCE(SUM(s.net_sales), 0) AS net_sales,
+               COALESCE(AVG(s.net_sales), 0) AS average_ticket
+        FROM generate_series(0, 23) AS h(hour)
+        LEFT JOIN sales s ON EXTRACT(HOUR FROM s.local_at) = h.hour
+        GROUP BY h.hour
+        ORDER BY h.hour
+    "#).await
+}
+
+pub async fn weekday_sales(pool: &PgPool, settings: &TokenSettings, range: DateRange) -> Result<Vec<WeekdaySales>, sqlx::Error> {
+    fetch(pool, settings, range, r#"
+        SELECT d.day_of_week,
+               (ARRAY['Monday', 'Tuesday', 'Wednesday', 'Thursday', 'Friday', 'Saturday', 'Sunday'])[d.day_of_week] AS day_name,
+               COUNT(s.id) AS order_count,
+               COALESCE(SUM(s.net_sales), 0) AS net_sales,
+               COALESCE(AVG(s.net_sales), 0) AS average_ticket
+        FROM generate_series(1, 7) AS d(day_of_week)
+        LEFT JOIN sales s ON EXTRACT(ISODOW FROM s.local_at) = d.day_of_week
+        GROUP BY d.day_of_week
+        ORDER BY d.day_of_week
+    "#).await
+}
+
+/// All 168 weekday × hour cells, Monday 00:00 first.
+pub async fn sales_heatmap(pool: &PgPool, settings: &TokenSettings, range: DateRange) -> Result<Vec<HeatmapCell>, sqlx::Error> {
+    fetch(pool, settings, range, r#"
+        SELECT d.day_of_week, h.hour,
+               COUNT(s.id) AS order_count,
+               COALESCE(SUM(s.net_sales), 0) AS net_sales
+        FROM generate_series(1, 7) AS d(day_of_week)
+        CROSS JOIN generate_series(0, 23) AS h(hour)
+        LEFT JOIN sales s
+            ON EXTRACT(ISODOW FROM s.local_at) = d.day_of_week
+           AND EXTRACT(HOUR FROM s.local_at) = h.hour
+        GROUP BY d.day_of_week, h.hour
+        ORDER BY d.day_of_week, h.hour
+    "#).await
+}
+
+pub async fn source_sales(pool: &PgPool, settings: &TokenSettings, range: DateRange) -> Result<Vec<SourceSales>, sqlx::Error> {
+    fetch(pool, settings, range, r#"
+        SELECT source,
+               COUNT(*) AS order_count,
+               SUM(net_sales) AS net_sales,
+               AVG(net_sales) AS average_ticket
+        FROM sales
+        GROUP BY source
+        ORDER BY net_sales DESC, source
+    "#).await
+}
+
+/// Rows as CSV with a header line; amounts keep their two decimal places.
+pub fn to_csv<T: CsvRow>(rows: &[T]) -> Result<String, String> {
+    let mut writer = csv::WriterBuilder::new().has_headers(false).from_writer(Vec::new());
+    writer.write_record(T::COLUMNS).map_err(|e| e.to_string())?;
+    for row in rows {
+        writer.serialize(row).map_err(|e| e.to_string())?;
+    }
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ranges_default_to_the_last_thirty_days() {
+        let range = DateRange::ending(None, date("2025-10-31")).unwrap();
+        assert_eq!((range.from, range.to), (date("2025-10-02"), date("2025-10-31")));
+
+        let range = DateRange::ending(Some(date("2025-10-31")), date("2025-10-31")).unwrap();
+        assert_eq!(range.from, range.to);
+    }
+
+    #[test]
+    fn ranges_must_run_forwards_and_stay_under_a_year() {
+        assert_eq!(
+            DateRange::ending(Some(date("2025-11-01")), date("2025-10-31")).map(|_| ()),
+            Err("from must not be after to".to_string()),
+        );
+        // Both ends count, so 366 days back to front is the most allowed.
+        assert!(DateRange::ending(Some(date("2024-10-31")), date("2025-10-31")).is_ok());
+        assert_eq!(
+            DateRange::ending(Some(date("2024-10-30")), date("2025-10-31")).map(|_| ()),
+            Err("Date range cannot exceed 366 days".to_string()),
+        );
+    }
+
+    #[test]
+    fn csv_has_a_header_and_keeps_two_decimal_places() {
+        let rows = [
+            SourceSales {
+                source: "kiosk".to_string(),
+                order_count: 3,
+                net_sales: "30".parse().unwrap(),
+                average_ticket: "10".parse().unwrap(),
+            },
+            SourceSales {
+                source: "pos, till 2".to_string(),
+                order_count: 1,
+                net_sales: "4.5".parse().unwrap(),
+                average_ticket: "4.5".parse().unwrap(),
+            },
+        ];
+        assert_eq!(
+            to_csv(&rows).unwrap(),
+            "source,order_count,net_sales,average_ticket\nkiosk,3,30.00,10.00\n\"pos, till 2\",1,4.50,4.50\n",
+        );
+    }
+
+    #[test]
+    fn csv_of_nothing_is_just_the_header() {
+        assert_eq!(to_csv::<SourceSales>(&[]).unwrap(), "source,order_count,net_sales,average_ticket\n");
+        assert_eq!(to_csv::<HeatmapCell>(&[]).unwrap(), "day_of_week,hour,order_count,net_sales\n");
+    }
+}
@@ -0,0 +1,405 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::sync::OnceLock;
+use crate::permissions::{role_permissions, Forbidden, Permission};
+use crate::AppState;
+
+/// Failed attempts allowed before an account is locked, and for how long.
+const MAX_FAILED_LOGINS: i32 = 5;
+const LOCKOUT_MINUTES: i64 = 5;
+
+/// An account's run of failed logins.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Lockout {
+    failed_logins: i32,
+    locked: bool,
+}
+
+impl Lockout {
+    /// Counts a failed attempt. The one that reaches the limit locks the account, and
+    /// the count starts again once the lock runs out.
+    fn failed(self) -> Self {
+        let failed_logins = self.failed_logins + 1;
+        if failed_logins >= MAX_FAILED_LOGINS {
+            Lockout { failed_logins: 0, locked: true }
+        } else {
+            Lockout { failed_logins, locked: self.locked }
+        }
+    }
+
+    fn succeeded(self) -> Self {
+        Lockout::default()
+    }
+}
+
+/// Signing keys and lifetime for staff session tokens.
+pub struct AuthConfig {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    ttl: Duration,
+}
+
+impl AuthConfig {
+    /// Reads `JWT_SECRET` and `JWT_TTL_HOURS` (default 12). Without a secret a random one
+    /// is generated, so sessions don't survive a restart.
+    pub fn from_env() -> Self {
+        let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| {
+            eprintln!("JWT_SECRET is not set; using a random secret, sessions will end on restart");
+            format!("{}{}", uuid::Uuid::new_v4(), uuid::Uuid::new_v4())
+        });
+        let ttl_hours = std::env::var("JWT_TTL_HOURS")
+            .ok()
+            .and_then(|v| v.parse::<i64>().ok())
+            .unwrap_or(12);
+
+        AuthConfig {
+            encoding: EncodingKey::from_secret(secret.as_bytes()),
+            decoding: DecodingKey::from_secret(secret.as_bytes()),
+            ttl: Duration::hours(ttl_hours),
+        }
+    }
+
+    /// Returns the signed token and its expiry as a unix timestamp.
+    pub fn issue(&self, staff: &AuthStaff) -> Result<(String, i64), jsonwebtoken::errors::Error> {
+        let now = Utc::now();
+        let claims = Claims {
+            sub: staff.id,
+            name: staff.name.clone(),
+            iat: now.timestamp(),
+            exp: (now + self.ttl).timestamp(),
+        };
+        let token = encode(&Header::default(), &claims, &self.encoding)?;
+        Ok((token, claims.exp))
+    }
+
+    fn verify(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        decode::<Claims>(token, &self.decoding, &Validation::default()).map(|data| data.claims)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: i32,
+    name: String,
+    iat: i64,
+    exp: i64,
+}
+
+/// The signed-in staff member, available to handlers behind `require_staff`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthStaff {
+    pub id: i32,
+    pub name: String,
+    pub roles: Vec<String>,
+}
+
+impl AuthStaff {
+    pub fn can(&self, permission: Permission) -> bool {
+        self.roles.iter().any(|role| role_permissions(role).contains(&permission))
+    }
+
+    pub fn require(&self, permission: Permission) -> Result<(), Forbidden> {
+        if self.can(permission) {
+            Ok(())
+        } else {
+            Err(Forbidden { permission, roles: self.roles.clone(), approval_required: false })
+        }
+    }
+
+    pub fn permissions(&self) -> Vec<Permission> {
+        Permission::ALL.iter().copied().filter(|p| self.can(*p)).collect()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuthStaff {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthStaff>()
+            .cloned()
+            .ok_or((StatusCode::UNAUTHORIZED, "Authentication required".to_string()))
+    }
+}
+
+/// Rejects requests without a valid bearer token for an active staff member.
+pub async fn require_staff(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .ok_or((StatusCode::UNAUTHORIZED, "Authentication required".to_string()))?;
+
+    let staff = authenticate(&state, &token).await?;
+    req.extensions_mut().insert(staff);
+    Ok(next.run(req).await)
+}
+
+/// The active staff member a session token belongs to.
+pub async fn authenticate(state: &AppState, token: &str) -> Result<AuthStaff, (StatusCode, String)> {
+    let claims = state
+        .auth
+        .verify(token.trim())
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired session".to_string()))?;
+
+    // Checked on every request so deactivating someone or changing their roles takes
+    // effect immediately rather than when the token expires.
+    let (name, roles): (String, Vec<String>) = sqlx::query_as(
+        r#"
+        SELECT name, COALESCE(ARRAY(SELECT role FROM staff_roles WHERE staff_id = staff.id ORDER BY role), '{}')
+        FROM staff
+        WHERE id = $1 AND active = true
+        "#
+    )
+    .bind(claims.sub)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::UNAUTHORIZED, "Invalid or expired session".to_string()))?;
+
+    Ok(AuthStaff { id: claims.sub, name, roles })
+}
+
+pub fn hash_secret(secret: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default().hash_password(secret.as_bytes(), &salt)?.to_string())
+}
+
+pub fn verify_secret(secret: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|parsed| Argon2::default().verify_password(secret.as_bytes(), &parsed).is_ok())
+        .unwrap_or(false)
+}
+
+/// Checked against when an account has no hash to verify, so a login takes as long
+/// whether or not the username exists.
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_secret("not a real secret").expect("Failed to hash the dummy secret"))
+}
+
+/// Like `verify_secret`, but still does the work when there is no hash.
+fn verify_or_dummy(secret: &str, hash: Option<&str>) -> bool {
+    match hash {
+        Some(hash) => verify_secret(secret, hash),
+        None => {
+            verify_secret(secret, dummy_hash());
+            false
+        }
+    }
+}
+
+pub fn validate_pin(pin: &str) -> Result<(), (StatusCode, String)> {
+    if pin.len() < 4 || pin.len() > 8 || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err((StatusCode::BAD_REQUEST, "PIN must be 4 to 8 digits".to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> Result<(), (StatusCode, String)> {
+    if password.chars().count() < 8 {
+        return Err((StatusCode::BAD_REQUEST, "Password must be at least 8 characters".to_string()));
+    }
+    Ok(())
+}
+
+/// Outcome of checking a login attempt against the stored credentials.
+pub enum LoginCheck {
+    Ok(AuthStaff),
+    Invalid,
+    Locked,
+}
+
+#[derive(FromRow)]
+struct StaffCredentials {
+    id: i32,
+    name: String,
+    pin_hash: Option<String>,
+    password_hash: Option<String>,
+    failed_logins: i32,
+    locked: bool,
+    roles: Vec<String>,
+}
+
+/// Verifies a username with a PIN or password, counting failures towards a temporary lockout.
+pub async fn check_login(
+    pool: &PgPool,
+    username: &str,
+    pin: Option<&str>,
+    password: Option<&str>,
+) -> Result<LoginCheck, sqlx::Error> {
+    check_credentials(pool, username, pin, password, true).await
+}
+
+/// Verifies a manager's PIN for approving someone else's action. This is not a login: a
+/// correct PIN leaves the account untouched, though wrong ones still count towards the
+/// lockout so PINs can't be guessed through approvals.
+pub async fn check_approval(pool: &PgPool, username: &str, pin: &str) -> Result<LoginCheck, sqlx::Error> {
+    check_credentials(pool, username, Some(pin), None, false).await
+}
+
+async fn check_credentials(
+    pool: &PgPool,
+    username: &str,
+    pin: Option<&str>,
+    password: Option<&str>,
+    login: bool,
+) -> Result<LoginCheck, sqlx::Error> {
+    // The row stays locked until the attempt is recorded, so concurrent guesses can't
+    // both read the same count.
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query_as::<_, StaffCredentials>(
+        r#"
+        SELECT id, name, pin_hash, password_hash, failed_logins, COALESCE(locked_until > now(), false) AS locked,
+               ARRAY(SELECT role FROM staff_roles WHERE staff_id = staff.id ORDER BY role) AS roles
+        FROM staff
+        WHERE lower(username) = lower($1) AND active = true
+        FOR UPDATE
+        "#
+    )
+    .bind(username.trim())
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(StaffCredentials { id, name, pin_hash, password_hash, failed_logins, locked, roles }) = row else {
+        verify_or_dummy(pin.or(password).unwrap_or_default(), None);
+        return Ok(LoginCheck::Invalid);
+    };
+    if locked {
+        return Ok(LoginCheck::Locked);
+    }
+
+    let valid = match (pin, password) {
+        (Some(pin), _) => verify_or_dummy(pin, pin_hash.as_deref()),
+        (None, Some(password)) => verify_or_dummy(password, password_hash.as_deref()),
+        (None, None) => false,
+    };
+
+    if valid && !login {
+        return Ok(LoginCheck::Ok(AuthStaff { id, name, roles }));
+    }
+
+    let lockout = Lockout { failed_logins, locked };
+    let lockout = if valid { lockout.succeeded() } else { lockout.failed() };
+    sqlx::query(
+        r#"
+        UPDATE staff
+        SET failed_logins = $2,
+            locked_until = CASE WHEN $3 THEN now() + make_interval(mins => $4) END,
+            last_login_at = CASE WHEN $5 THEN now() ELSE last_login_at END
+        WHERE id = $1
+        "#
+    )
+    .bind(id)
+    .bind(lockout.failed_logins)
+    .bind(lockout.locked)
+    .bind(LOCKOUT_MINUTES as i32)
+    .bind(valid)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(if valid { LoginCheck::Ok(AuthStaff { id, name, roles }) } else { LoginCheck::Invalid })
+}
+
+/// Creates the first staff account from `ADMIN_USERNAME` / `ADMIN_PASSWORD` when the
+/// staff table is empty, so a fresh install can be signed into.
+pub async fn bootstrap_admin(pool: &PgPool) {
+    let has_staff: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM staff)")
+        .fetch_one(pool)
+        .await
+        .expect("Failed to check staff accounts");
+    if has_staff {
+        return;
+    }
+
+    let (Ok(username), Ok(password)) = (std::env::var("ADMIN_USERNAME"), std::env::var("ADMIN_PASSWORD")) else {
+        eprintln!("No staff accounts exist; set ADMIN_USERNAME and ADMIN_PASSWORD to create one");
+        return;
+    };
+    validate_password(&password).expect("ADMIN_PASSWORD must be at least 8 characters");
+    let hash = hash_secret(&password).expect("Failed to hash ADMIN_PASSWORD");
+
+    sqlx::query(
+        r#"
+        WITH admin AS (
+            INSERT INTO staff (name, username, password_hash) VALUES ($1, $1, $2) RETURNING id
+        )
+        INSERT INTO staff_roles (staff_id, role) SELECT id, 'admin' FROM admin
+        "#
+    )
+    .bind(username.trim())
+    .bind(hash)
+    .execute(pool)
+    .await
+    .expect("Failed to create admin account");
+    println!("Created admin account '{}'", username.trim());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_fifth_failure_in_a_row_locks_the_account() {
+        let mut lockout = Lockout::default();
+        let locked: Vec<bool> = (0..MAX_FAILED_LOGINS + 2)
+            .map(|_| {
+                lockout = lockout.failed();
+                lockout.locked
+            })
+            .collect();
+        assert_eq!(locked, [false, false, false, false, true, true, true]);
+    }
+
+    #[test]
+    fn the_count_starts_again_when_the_account_locks() {
+        let lockout = (0..MAX_FAILED_LOGINS).fold(Lockout::default(), |lockout, _| lockout.failed());
+        assert_eq!(lockout, Lockout { failed_logins: 0, locked: true });
+
+        // Once the lock has run out the account gets the full number of attempts again.
+        let expired = Lockout { locked: false, ..lockout };
+        assert_eq!(expired.failed(), Lockout { failed_logins: 1, locked: false });
+    }
+
+    #[test]
+    fn a_successful_login_clears_the_count() {
+        let lockout = Lockout::default().failed().failed().failed();
+        assert_eq!(lockout.failed_logins, 3);
+        assert_eq!(lockout.succeeded(), Lockout::default());
+        assert_eq!(lockout.succeeded().failed(), Lockout { failed_logins: 1, locked: false });
+    }
+
+    #[test]
+    fn verifies_secrets_against_their_hash() {
+        let hash = hash_secret("1234").unwrap();
+        assert!(verify_secret("1234", &hash));
+        assert!(!verify_secret("4321", &hash));
+        assert!(!verify_secret("1234", "not a hash"));
+    }
+
+    #[test]
+    fn a_missing_hash_never_verifies() {
+        assert!(!verify_or_dummy("not a real secret", None));
+        assert!(!verify_or_dummy("", None));
+    }
+}
@@ -1,36 +1,292 @@
 use axum::{
     extract::{State, Path, Multipart, WebSocketUpgrade, Query},
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
 };
+use base64::Engine;
 use serde_json::json;
 use sqlx::Row;
-use std::{fs, sync::Arc};
+use std::{collections::{HashMap, HashSet}, fs};
 use uuid::Uuid;
-use chrono::{Utc, DateTime};
+use chrono::{DateTime, NaiveDate, Utc};
 use bigdecimal::BigDecimal;
-use std::str::FromStr;
 use crate::models::{
     MenuItem, Order, QueueToken, CreateOrder, OrderDetailed,
-    OrderItemDetailed, UpdateOrderStatus,
+    OrderItemDetailed, UpdateOrderStatus, MenuCategory, MenuCategoryPayload,
+    MenuCategoryNode, MenuTree, ModifierGroup, ModifierOption, ModifierGroupDetailed,
+    ModifierGroupPayload, ModifierOptionPayload, OrderItemModifier, OrderItem, TaxRate,
+    TaxRatePayload, Money, UpdateMenuItem, UpdateAvailability,
+    OrderStatusChange, Staff, CreateStaff, UpdateStaff, UpdateStaffRoles, LoginRequest,
+    Payment, CreatePayment, OrderAdjustment, OrderAdjustmentItem, OrderAdjustmentDetailed,
+    CreateAdjustment, RefundTender, SyncChange, SyncMutation, SyncPush, ZReport, CloseDay,
+    KitchenStation, KitchenStationPayload, UpdateItemStatus, DrawerOpening, DrawerKickFailed, OpenDrawer,
+    DrawerOpeningQuery, ReceiptTemplate, ReceiptTemplatePayload, PrintJob, QueuePrint,
+    PrintJobQuery, ClaimPrintJob, PrintJobResult, ReprintKitchenTickets,
 };
+use crate::pricing::{price_line, LineTax, OrderTotals};
+use crate::tokens::allocate_token;
+use crate::auth::{self, AuthStaff, LoginCheck};
+use crate::permissions::{role_permissions, status_permission, Forbidden, Permission, ROLES};
+use crate::reports::{build_report, SalesReport};
+use crate::analytics::{self, AnalyticsQuery, DateRange};
+use crate::adjustments::{allocate_refund, RefundablePayment, SoldLine, REFUND_REASONS, VOID_REASONS};
+use crate::events::{is_known_topic, is_public_topic, ClientMessage, Event, Replay, PROTOCOL_VERSION, PUBLIC_TOPICS, TOPICS};
+use crate::kitchen::{self, StationTicket, ITEM_STATUSES};
+use crate::estimates;
+use crate::printing::{self, valid_printer_name};
+use tokio::sync::broadcast;
 use axum::extract::ws::{Message, WebSocket};
 use crate::AppState;
 
-const ALLOWED_STATUSES: &[&str] = &["pending", "preparing", "ready", "completed"];
+const ALLOWED_STATUSES: &[&str] = &["pending", "preparing", "ready", "completed", "cancelled", "refunded"];
 const ALLOWED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp"];
 const MAX_FILE_SIZE: usize = 5 * 1024 * 1024; // 5MB
 
-pub async fn list_menu(State(state): State<AppState>) -> Result<Json<Vec<MenuItem>>, (StatusCode, String)> {
+#[derive(serde::Deserialize)]
+pub struct MenuQuery {
+    pub category: Option<i32>,
+}
+
+pub async fn list_menu(
+    State(state): State<AppState>,
+    Query(q): Query<MenuQuery>
+) -> Result<Json<Vec<MenuItem>>, (StatusCode, String)> {
+    let items = if let Some(category_id) = q.category {
+        // Include items from every sub-category so "Drinks" also lists "Hot Drinks".
+        sqlx::query_as::<_, MenuItem>(
+            r#"
+            WITH RECURSIVE scope AS (
+                SELECT id FROM menu_categories WHERE id = $1
+                UNION
+                SELECT c.id FROM menu_categories c JOIN scope s ON c.parent_id = s.id
+            )
+            SELECT * FROM menu_items
+            WHERE available = true AND deleted_at IS NULL AND category_id IN (SELECT id FROM scope)
+            ORDER BY id ASC
+            "#
+        )
+        .bind(category_id)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    } else {
+        sqlx::query_as::<_, MenuItem>(
+            "SELECT * FROM menu_items WHERE available = true AND deleted_at IS NULL ORDER BY id ASC"
+        )
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    };
+
+    Ok(Json(items))
+}
+
+pub async fn menu_tree(State(state): State<AppState>) -> Result<Json<MenuTree>, (StatusCode, String)> {
+    let categories = sqlx::query_as::<_, MenuCategory>(
+        "SELECT * FROM menu_categories WHERE active = true ORDER BY display_order ASC, name ASC"
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
     let items = sqlx::query_as::<_, MenuItem>(
-        "SELECT * FROM menu_items WHERE available = true ORDER BY id ASC"
+        "SELECT * FROM menu_items WHERE available = true AND deleted_at IS NULL ORDER BY id ASC"
     )
     .fetch_all(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
-    
-    Ok(Json(items))
+
+    Ok(Json(build_menu_tree(categories, items)))
+}
+
+/// Nests active categories under their parents and hangs items off their category.
+/// Items whose category is missing or inactive end up in `uncategorized`, and
+/// categories whose parent is inactive are promoted to the top level.
+fn build_menu_tree(categories: Vec<MenuCategory>, items: Vec<MenuItem>) -> MenuTree {
+    let known: HashSet<i32> = categories.iter().map(|c| c.id).collect();
+
+    let mut items_by_category: HashMap<i32, Vec<MenuItem>> = HashMap::new();
+    let mut uncategorized = Vec::new();
+    for item in items {
+        match item.category_id.filter(|id| known.contains(id)) {
+            Some(id) => items_by_category.entry(id).or_default().push(item),
+            None => uncategorized.push(item),
+        }
+    }
+
+    let mut children_by_parent: HashMap<Option<i32>, Vec<MenuCategory>> = HashMap::new();
+    for category in categories {
+        let parent = category.parent_id.filter(|id| known.contains(id));
+        children_by_parent.entry(parent).or_default().push(category);
+    }
+
+    fn build(
+        parent: Option<i32>,
+        children_by_parent: &mut HashMap<Option<i32>, Vec<MenuCategory>>,
+        items_by_category: &mut HashMap<i32, Vec<MenuItem>>,
+    ) -> Vec<MenuCategoryNode> {
+        children_by_parent
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|category| {
+                let id = category.id;
+                MenuCategoryNode {
+                    items: items_by_category.remove(&id).unwrap_or_default(),
+                    children: build(Some(id), children_by_parent, items_by_category),
+                    category,
+                }
+            })
+            .collect()
+    }
+
+    let categories = build(None, &mut children_by_parent, &mut items_by_category);
+    MenuTree { categories, uncategorized }
+}
+
+pub async fn list_categories(State(state): State<AppState>) -> Result<Json<Vec<MenuCategory>>, (StatusCode, String)> {
+    let categories = sqlx::query_as::<_, MenuCategory>(
+        "SELECT * FROM menu_categories ORDER BY display_order ASC, name ASC"
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(categories))
+}
+
+pub async fn create_category(
+    State(state): State<AppState>,
+    Json(payload): Json<MenuCategoryPayload>
+) -> Result<Json<MenuCategory>, (StatusCode, String)> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()));
+    }
+    if let Some(parent_id) = payload.parent_id {
+        ensure_category_exists(&state, parent_id).await?;
+    }
+
+    let category = sqlx::query_as::<_, MenuCategory>(
+        "INSERT INTO menu_categories (name, parent_id, display_order, active) VALUES ($1, $2, $3, $4) RETURNING *"
+    )
+    .bind(name)
+    .bind(payload.parent_id)
+    .bind(payload.display_order)
+    .bind(payload.active)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(category))
+}
+
+pub async fn update_category(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<MenuCategoryPayload>
+) -> Result<Json<MenuCategory>, (StatusCode, String)> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()));
+    }
+    if let Some(parent_id) = payload.parent_id {
+        ensure_category_exists(&state, parent_id).await?;
+
+        // Reject moves that would make a category its own ancestor.
+        let creates_cycle: bool = sqlx::query_scalar(
+            r#"
+            WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id FROM menu_categories WHERE id = $1
+                UNION
+                SELECT c.id, c.parent_id FROM menu_categories c JOIN ancestors a ON c.id = a.parent_id
+            )
+            SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $2)
+            "#
+        )
+        .bind(parent_id)
+        .bind(id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+        if creates_cycle {
+            return Err((StatusCode::BAD_REQUEST, "A category cannot be nested inside itself".to_string()));
+        }
+    }
+
+    let category = sqlx::query_as::<_, MenuCategory>(
+        "UPDATE menu_categories SET name = $1, parent_id = $2, display_order = $3, active = $4 WHERE id = $5 RETURNING *"
+    )
+    .bind(name)
+    .bind(payload.parent_id)
+    .bind(payload.display_order)
+    .bind(payload.active)
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Category not found".to_string()))?;
+
+    Ok(Json(category))
+}
+
+pub async fn delete_category(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    // Items and sub-categories are detached by the ON DELETE SET NULL foreign keys.
+    let result = sqlx::query("DELETE FROM menu_categories WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Category not found".to_string()));
+    }
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
+}
+
+async fn ensure_category_exists(state: &AppState, id: i32) -> Result<(), (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM menu_categories WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err((StatusCode::BAD_REQUEST, format!("Category {} does not exist", id)));
+    }
+    Ok(())
+}
+
+async fn ensure_tax_rate_exists(state: &AppState, id: i32) -> Result<(), (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tax_rates WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err((StatusCode::BAD_REQUEST, format!("Tax rate {} does not exist", id)));
+    }
+    Ok(())
+}
+
+async fn ensure_station_exists(state: &AppState, id: i32) -> Result<(), (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM kitchen_stations WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err((StatusCode::BAD_REQUEST, format!("Kitchen station {} does not exist", id)));
+    }
+    Ok(())
 }
 
 pub async fn create_menu_item(
@@ -43,363 +299,3925 @@ pub async fn create_menu_item(
         .ok_or((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()))?;
     
     let price = payload.get("price")
-        .and_then(|v| v.as_f64())
-        .filter(|p| *p >= 0.0)
+        .cloned()
+        .and_then(|v| serde_json::from_value::<Money>(v).ok())
+        .filter(|p| !p.is_negative())
         .ok_or((StatusCode::BAD_REQUEST, "Valid price is required".to_string()))?;
-    
-    let price_decimal = BigDecimal::from_str(&price.to_string())
-        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid price format".to_string()))?;
-    
+
     let image_url = payload.get("image_url").and_then(|v| v.as_str());
 
-    let rec = sqlx::query(
-        "INSERT INTO menu_items (name, price, available, image_url) VALUES ($1, $2, true, $3) RETURNING id"
+    let category_id = match payload.get("category_id").filter(|v| !v.is_null()) {
+        Some(v) => Some(
+            v.as_i64()
+                .and_then(|id| i32::try_from(id).ok())
+                .ok_or((StatusCode::BAD_REQUEST, "Invalid category_id".to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(category_id) = category_id {
+        ensure_category_exists(&state, category_id).await?;
+    }
+
+    let tax_rate_id = match payload.get("tax_rate_id").filter(|v| !v.is_null()) {
+        Some(v) => Some(
+            v.as_i64()
+                .and_then(|id| i32::try_from(id).ok())
+                .ok_or((StatusCode::BAD_REQUEST, "Invalid tax_rate_id".to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(tax_rate_id) = tax_rate_id {
+        ensure_tax_rate_exists(&state, tax_rate_id).await?;
+    }
+
+    let station_id = match payload.get("station_id").filter(|v| !v.is_null()) {
+        Some(v) => Some(
+            v.as_i64()
+                .and_then(|id| i32::try_from(id).ok())
+                .ok_or((StatusCode::BAD_REQUEST, "Invalid station_id".to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(station_id) = station_id {
+        ensure_station_exists(&state, station_id).await?;
+    }
+
+    let prep_seconds = match payload.get("prep_seconds").filter(|v| !v.is_null()) {
+        Some(v) => Some(
+            v.as_i64()
+                .and_then(|s| i32::try_from(s).ok())
+                .filter(|s| *s >= 0)
+                .ok_or((StatusCode::BAD_REQUEST, "prep_seconds must be a whole number of seconds".to_string()))?,
+        ),
+        None => None,
+    };
+
+    let item = sqlx::query_as::<_, MenuItem>(
+        r#"
+        INSERT INTO menu_items (name, price, available, image_url, category_id, tax_rate_id, station_id, prep_seconds)
+        VALUES ($1, $2, true, $3, $4, $5, $6, COALESCE($7, 300))
+        RETURNING *
+        "#
     )
     .bind(name.trim())
-    .bind(price_decimal)
+    .bind(&price)
     .bind(image_url)
+    .bind(category_id)
+    .bind(tax_rate_id)
+    .bind(station_id)
+    .bind(prep_seconds)
     .fetch_one(&state.pool)
     .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            (StatusCode::CONFLICT, format!("A menu item named '{}' already exists", name.trim()))
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    })?;
 
-    let id: i32 = rec.get("id");
-    Ok(Json(json!({"id": id, "status": "created"})))
+    broadcast_menu_change(&state, "created", &item);
+
+    Ok(Json(json!({"id": item.id, "status": "created"})))
 }
 
-pub async fn upload_image(
-    mut multipart: Multipart,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let upload_dir = std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
-    fs::create_dir_all(&upload_dir)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create upload directory: {}", e)))?;
+pub async fn update_menu_item(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateMenuItem>
+) -> Result<Json<MenuItem>, (StatusCode, String)> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()));
+    }
+    if payload.price.is_negative() {
+        return Err((StatusCode::BAD_REQUEST, "Valid price is required".to_string()));
+    }
+    if let Some(category_id) = payload.category_id {
+        ensure_category_exists(&state, category_id).await?;
+    }
+    if let Some(tax_rate_id) = payload.tax_rate_id {
+        ensure_tax_rate_exists(&state, tax_rate_id).await?;
+    }
+    if let Some(station_id) = payload.station_id {
+        ensure_station_exists(&state, station_id).await?;
+    }
+    if payload.prep_seconds.is_some_and(|s| s < 0) {
+        return Err((StatusCode::BAD_REQUEST, "prep_seconds cannot be negative".to_string()));
+    }
 
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?
-    {
-        let name = field.name().unwrap_or("file").to_string();
-        let file_name = field.file_name()
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| format!("upload-{}", Uuid::new_v4()));
-        let data = field.bytes()
-            .await
-            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read file data: {}", e)))?;
-        if data.len() > MAX_FILE_SIZE {
-            return Err((StatusCode::PAYLOAD_TOO_LARGE, "File size exceeds 5MB limit".to_string()));
+    // Past orders keep their own price and station snapshot, so changes only affect new orders.
+    let item = sqlx::query_as::<_, MenuItem>(
+        r#"
+        UPDATE menu_items
+        SET name = $1, price = $2, image_url = $3, category_id = $4, tax_rate_id = $5,
+            station_id = $6, available = COALESCE($7, available), prep_seconds = COALESCE($8, prep_seconds)
+        WHERE id = $9 AND deleted_at IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(name)
+    .bind(&payload.price)
+    .bind(&payload.image_url)
+    .bind(payload.category_id)
+    .bind(payload.tax_rate_id)
+    .bind(payload.station_id)
+    .bind(payload.available)
+    .bind(payload.prep_seconds)
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            (StatusCode::CONFLICT, format!("A menu item named '{}' already exists", name))
         }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    })?
+    .ok_or((StatusCode::NOT_FOUND, "Menu item not found".to_string()))?;
 
-        let ext = std::path::Path::new(&file_name)
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("bin")
-            .to_lowercase();
-        
-        if !ALLOWED_EXTENSIONS.contains(&ext.as_str()) {
-            return Err((StatusCode::BAD_REQUEST, "Invalid file type. Only images are allowed".to_string()));
-        }
+    broadcast_menu_change(&state, "updated", &item);
 
-        let out_name = format!("{}-{}.{}", name, Uuid::new_v4(), ext);
-        let out_path = std::path::Path::new(&upload_dir).join(&out_name);
-        
-        tokio::fs::write(&out_path, &data)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save file: {}", e)))?;
+    Ok(Json(item))
+}
 
-        let url = format!("/uploads/{}", out_name);
-        return Ok(Json(json!({"url": url})));
-    }
+pub async fn set_menu_item_availability(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateAvailability>
+) -> Result<Json<MenuItem>, (StatusCode, String)> {
+    let item = sqlx::query_as::<_, MenuItem>(
+        "UPDATE menu_items SET available = $1 WHERE id = $2 AND deleted_at IS NULL RETURNING *"
+    )
+    .bind(payload.available)
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Menu item not found".to_string()))?;
 
-    Err((StatusCode::BAD_REQUEST, "No file field found".to_string()))
+    broadcast_menu_change(&state, "availability_changed", &item);
+
+    Ok(Json(item))
 }
 
-pub async fn create_order(
+pub async fn delete_menu_item(
     State(state): State<AppState>,
-    Json(payload): Json<CreateOrder>
+    Path(id): Path<i32>
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    if payload.items.is_empty() {
-        return Err((StatusCode::BAD_REQUEST, "Order must contain at least one item".to_string()));
+    // Soft delete: order_items still reference the row, and sync reports it as a tombstone.
+    let item = sqlx::query_as::<_, MenuItem>(
+        "UPDATE menu_items SET deleted_at = now(), available = false WHERE id = $1 AND deleted_at IS NULL RETURNING *"
+    )
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Menu item not found".to_string()))?;
+
+    broadcast_menu_change(&state, "deleted", &item);
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
+}
+
+fn broadcast_menu_change(state: &AppState, action: &str, item: &MenuItem) {
+    state.bcast.publish("menu.updated", json!({ "action": action, "item": item }));
+}
+
+pub async fn list_item_modifiers(
+    State(state): State<AppState>,
+    Path(menu_item_id): Path<i32>
+) -> Result<Json<Vec<ModifierGroupDetailed>>, (StatusCode, String)> {
+    let mut conn = state.pool.acquire()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(load_modifier_groups(&mut conn, menu_item_id).await?))
+}
+
+pub async fn create_modifier_group(
+    State(state): State<AppState>,
+    Path(menu_item_id): Path<i32>,
+    Json(payload): Json<ModifierGroupPayload>
+) -> Result<Json<ModifierGroupDetailed>, (StatusCode, String)> {
+    validate_modifier_group(&payload)?;
+    for option in payload.options.iter() {
+        validate_modifier_option(option)?;
     }
 
     let mut tx = state.pool.begin()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
 
-    let rec = sqlx::query("INSERT INTO orders (source, status) VALUES ($1, 'pending') RETURNING id")
-        .bind(&payload.source)
-        .fetch_one(&mut *tx)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create order: {}", e)))?;
-
-    let order_id: i32 = rec.get("id");
-
-    for item in payload.items.iter() {
-        if item.quantity <= 0 {
-            return Err((StatusCode::BAD_REQUEST, "Item quantity must be greater than 0".to_string()));
+    let group = sqlx::query_as::<_, ModifierGroup>(
+        "INSERT INTO modifier_groups (menu_item_id, name, min_select, max_select, display_order) VALUES ($1, $2, $3, $4, $5) RETURNING *"
+    )
+    .bind(menu_item_id)
+    .bind(payload.name.trim())
+    .bind(payload.min_select)
+    .bind(payload.max_select)
+    .bind(payload.display_order)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => {
+            (StatusCode::NOT_FOUND, "Menu item not found".to_string())
         }
-        
-        sqlx::query("INSERT INTO order_items (order_id, menu_item_id, quantity) VALUES ($1, $2, $3)")
-            .bind(order_id)
-            .bind(item.menu_item_id)
-            .bind(item.quantity)
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to add order item: {}", e)))?;
-    }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    })?;
 
-    sqlx::query("INSERT INTO queue_tokens (order_id, token_number, status) VALUES ($1, $2, 'waiting')")
-        .bind(order_id)
-        .bind(order_id)
-        .execute(&mut *tx)
+    let mut options = Vec::with_capacity(payload.options.len());
+    for option in payload.options.iter() {
+        let option = sqlx::query_as::<_, ModifierOption>(
+            "INSERT INTO modifier_options (group_id, name, price_delta, available, display_order) VALUES ($1, $2, $3, $4, $5) RETURNING *"
+        )
+        .bind(group.id)
+        .bind(option.name.trim())
+        .bind(&option.price_delta)
+        .bind(option.available)
+        .bind(option.display_order)
+        .fetch_one(&mut *tx)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create queue token: {}", e)))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        options.push(option);
+    }
 
     tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
 
-    // Broadcast queue update
-    tokio::spawn({
-        let state = state.clone();
-        async move { broadcast_queue_inner(&state).await; }
-    });
+    Ok(Json(ModifierGroupDetailed { group, options }))
+}
+
+pub async fn update_modifier_group(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<ModifierGroupPayload>
+) -> Result<Json<ModifierGroup>, (StatusCode, String)> {
+    validate_modifier_group(&payload)?;
+
+    let group = sqlx::query_as::<_, ModifierGroup>(
+        "UPDATE modifier_groups SET name = $1, min_select = $2, max_select = $3, display_order = $4 WHERE id = $5 RETURNING *"
+    )
+    .bind(payload.name.trim())
+    .bind(payload.min_select)
+    .bind(payload.max_select)
+    .bind(payload.display_order)
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Modifier group not found".to_string()))?;
 
-    Ok(Json(json!({ "order_id": order_id, "status": "created" })))
+    Ok(Json(group))
 }
 
-pub async fn list_orders(State(state): State<AppState>) -> Result<Json<Vec<Order>>, (StatusCode, String)> {
-    let orders = sqlx::query_as::<_, Order>("SELECT * FROM orders ORDER BY created_at DESC")
-        .fetch_all(&state.pool)
+pub async fn delete_modifier_group(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let result = sqlx::query("DELETE FROM modifier_groups WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
-    
-    Ok(Json(orders))
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Modifier group not found".to_string()));
+    }
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
 }
 
-pub async fn list_orders_detailed(State(state): State<AppState>) -> Result<Json<Vec<OrderDetailed>>, (StatusCode, String)> {
-    let orders = sqlx::query_as::<_, Order>("SELECT * FROM orders ORDER BY created_at DESC")
-        .fetch_all(&state.pool)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+pub async fn create_modifier_option(
+    State(state): State<AppState>,
+    Path(group_id): Path<i32>,
+    Json(payload): Json<ModifierOptionPayload>
+) -> Result<Json<ModifierOption>, (StatusCode, String)> {
+    validate_modifier_option(&payload)?;
 
-    let mut result = Vec::new();
-    for order in orders.into_iter() {
-        let items = sqlx::query!(
-            r#"
-            SELECT oi.id, oi.order_id, oi.menu_item_id, oi.quantity,
-                   mi.name as menu_name, mi.price as "menu_price!", mi.image_url as menu_image
-            FROM order_items oi
-            JOIN menu_items mi ON mi.id = oi.menu_item_id
-            WHERE oi.order_id = $1
-            "#,
-            order.id
+    let option = sqlx::query_as::<_, ModifierOption>(
+        "INSERT INTO modifier_options (group_id, name, price_delta, available, display_order) VALUES ($1, $2, $3, $4, $5) RETURNING *"
+    )
+    .bind(group_id)
+    .bind(payload.name.trim())
+    .bind(&payload.price_delta)
+    .bind(payload.available)
+    .bind(payload.display_order)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => {
+            (StatusCode::NOT_FOUND, "Modifier group not found".to_string())
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    })?;
+
+    Ok(Json(option))
+}
+
+pub async fn update_modifier_option(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<ModifierOptionPayload>
+) -> Result<Json<ModifierOption>, (StatusCode, String)> {
+    validate_modifier_option(&payload)?;
+
+    let option = sqlx::query_as::<_, ModifierOption>(
+        "UPDATE modifier_options SET name = $1, price_delta = $2, available = $3, display_order = $4 WHERE id = $5 RETURNING *"
+    )
+    .bind(payload.name.trim())
+    .bind(&payload.price_delta)
+    .bind(payload.available)
+    .bind(payload.display_order)
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Modifier option not found".to_string()))?;
+
+    Ok(Json(option))
+}
+
+pub async fn delete_modifier_option(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let result = sqlx::query("DELETE FROM modifier_options WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Modifier option not found".to_string()));
+    }
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
+}
+
+fn validate_modifier_group(payload: &ModifierGroupPayload) -> Result<(), (StatusCode, String)> {
+    if payload.name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()));
+    }
+    if payload.min_select < 0 || payload.max_select < 1 || payload.max_select < payload.min_select {
+        return Err((StatusCode::BAD_REQUEST, "Selection rules must satisfy 0 <= min_select <= max_select and max_select >= 1".to_string()));
+    }
+    Ok(())
+}
+
+fn validate_modifier_option(payload: &ModifierOptionPayload) -> Result<(), (StatusCode, String)> {
+    if payload.name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Option name is required and cannot be empty".to_string()));
+    }
+    Ok(())
+}
+
+async fn load_modifier_groups(
+    conn: &mut sqlx::PgConnection,
+    menu_item_id: i32,
+) -> Result<Vec<ModifierGroupDetailed>, (StatusCode, String)> {
+    let groups = sqlx::query_as::<_, ModifierGroup>(
+        "SELECT * FROM modifier_groups WHERE menu_item_id = $1 ORDER BY display_order ASC, id ASC"
+    )
+    .bind(menu_item_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let group_ids: Vec<i32> = groups.iter().map(|g| g.id).collect();
+    let options = sqlx::query_as::<_, ModifierOption>(
+        "SELECT * FROM modifier_options WHERE group_id = ANY($1) ORDER BY display_order ASC, id ASC"
+    )
+    .bind(&group_ids)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut options_by_group: HashMap<i32, Vec<ModifierOption>> = HashMap::new();
+    for option in options {
+        options_by_group.entry(option.group_id).or_default().push(option);
+    }
+
+    Ok(groups.into_iter().map(|group| {
+        let options = options_by_group.remove(&group.id).unwrap_or_default();
+        ModifierGroupDetailed { group, options }
+    }).collect())
+}
+
+/// Checks the chosen option ids against the item's groups and their min/max rules,
+/// returning each chosen option alongside the group it belongs to.
+fn resolve_modifiers<'a>(
+    groups: &'a [ModifierGroupDetailed],
+    selected: &[i32],
+) -> Result<Vec<(&'a ModifierGroup, &'a ModifierOption)>, String> {
+    let mut seen = HashSet::new();
+    let mut chosen = Vec::with_capacity(selected.len());
+    for option_id in selected {
+        if !seen.insert(*option_id) {
+            return Err(format!("modifier option {} was selected more than once", option_id));
+        }
+        let (group, option) = groups.iter()
+            .find_map(|g| g.options.iter().find(|o| o.id == *option_id).map(|o| (&g.group, o)))
+            .ok_or_else(|| format!("modifier option {} does not belong to this item", option_id))?;
+        if !option.available {
+            return Err(format!("'{}' is currently unavailable", option.name));
+        }
+        chosen.push((group, option));
+    }
+
+    for g in groups {
+        let count = chosen.iter().filter(|(group, _)| group.id == g.group.id).count() as i32;
+        if count < g.group.min_select || count > g.group.max_select {
+            return Err(if g.group.min_select == g.group.max_select {
+                format!("choose exactly {} option(s) for '{}'", g.group.min_select, g.group.name)
+            } else {
+                format!("choose between {} and {} option(s) for '{}'", g.group.min_select, g.group.max_select, g.group.name)
+            });
+        }
+    }
+
+    Ok(chosen)
+}
+
+async fn load_order_item_modifiers(
+    state: &AppState,
+    order_item_ids: &[i32],
+) -> Result<HashMap<i32, Vec<OrderItemModifier>>, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, OrderItemModifier>(
+        "SELECT * FROM order_item_modifiers WHERE order_item_id = ANY($1) ORDER BY id ASC"
+    )
+    .bind(order_item_ids)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut by_item: HashMap<i32, Vec<OrderItemModifier>> = HashMap::new();
+    for row in rows {
+        by_item.entry(row.order_item_id).or_default().push(row);
+    }
+    Ok(by_item)
+}
+
+pub async fn list_tax_rates(State(state): State<AppState>) -> Result<Json<Vec<TaxRate>>, (StatusCode, String)> {
+    let rates = sqlx::query_as::<_, TaxRate>("SELECT * FROM tax_rates ORDER BY id ASC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(rates))
+}
+
+pub async fn create_tax_rate(
+    State(state): State<AppState>,
+    Json(payload): Json<TaxRatePayload>
+) -> Result<Json<TaxRate>, (StatusCode, String)> {
+    validate_tax_rate(&payload)?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    if payload.is_default {
+        clear_default_tax_rate(&mut tx).await?;
+    }
+
+    let rate = sqlx::query_as::<_, TaxRate>(
+        "INSERT INTO tax_rates (name, rate, inclusive, is_default, active) VALUES ($1, $2, $3, $4, $5) RETURNING *"
+    )
+    .bind(payload.name.trim())
+    .bind(&payload.rate)
+    .bind(payload.inclusive)
+    .bind(payload.is_default)
+    .bind(payload.active)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(rate))
+}
+
+pub async fn update_tax_rate(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<TaxRatePayload>
+) -> Result<Json<TaxRate>, (StatusCode, String)> {
+    validate_tax_rate(&payload)?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    if payload.is_default {
+        clear_default_tax_rate(&mut tx).await?;
+    }
+
+    // Orders keep the rate they were taxed at, so editing a rate only affects new orders.
+    let rate = sqlx::query_as::<_, TaxRate>(
+        "UPDATE tax_rates SET name = $1, rate = $2, inclusive = $3, is_default = $4, active = $5 WHERE id = $6 RETURNING *"
+    )
+    .bind(payload.name.trim())
+    .bind(&payload.rate)
+    .bind(payload.inclusive)
+    .bind(payload.is_default)
+    .bind(payload.active)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Tax rate not found".to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(rate))
+}
+
+pub async fn delete_tax_rate(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let result = sqlx::query("DELETE FROM tax_rates WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Tax rate not found".to_string()));
+    }
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
+}
+
+pub async fn list_stations(State(state): State<AppState>) -> Result<Json<Vec<KitchenStation>>, (StatusCode, String)> {
+    let stations = sqlx::query_as::<_, KitchenStation>("SELECT * FROM kitchen_stations ORDER BY display_order, id")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(stations))
+}
+
+fn validate_station(payload: &KitchenStationPayload) -> Result<(), (StatusCode, String)> {
+    if payload.name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()));
+    }
+    if !kitchen::valid_station_code(payload.code.trim()) {
+        return Err((StatusCode::BAD_REQUEST, "Code must be lowercase letters, digits, '-' or '_'".to_string()));
+    }
+    if payload.capacity < 1 {
+        return Err((StatusCode::BAD_REQUEST, "Capacity must be at least 1".to_string()));
+    }
+    if ![58, 80].contains(&payload.paper_width) {
+        return Err((StatusCode::BAD_REQUEST, "paper_width must be 58 or 80".to_string()));
+    }
+    match payload.printer.as_deref().map(str::trim) {
+        Some(printer) if !valid_printer_name(printer) => {
+            return Err((StatusCode::BAD_REQUEST, "Printer names use lowercase letters, digits, '-' and '_'".to_string()));
+        }
+        None if payload.auto_print => {
+            return Err((StatusCode::BAD_REQUEST, "Printing tickets automatically needs a printer".to_string()));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn station_write_error(e: sqlx::Error, code: &str) -> (StatusCode, String) {
+    match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            (StatusCode::CONFLICT, format!("A station with code '{}' already exists", code))
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    }
+}
+
+pub async fn create_station(
+    State(state): State<AppState>,
+    Json(payload): Json<KitchenStationPayload>
+) -> Result<Json<KitchenStation>, (StatusCode, String)> {
+    validate_station(&payload)?;
+    let code = payload.code.trim();
+
+    let station = sqlx::query_as::<_, KitchenStation>(
+        r#"
+        INSERT INTO kitchen_stations (code, name, display_order, active, capacity, printer, auto_print, paper_width)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING *
+        "#
+    )
+    .bind(code)
+    .bind(payload.name.trim())
+    .bind(payload.display_order)
+    .bind(payload.active)
+    .bind(payload.capacity)
+    .bind(payload.printer.as_deref().map(str::trim))
+    .bind(payload.auto_print)
+    .bind(payload.paper_width)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| station_write_error(e, code))?;
+
+    Ok(Json(station))
+}
+
+pub async fn update_station(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<KitchenStationPayload>
+) -> Result<Json<KitchenStation>, (StatusCode, String)> {
+    validate_station(&payload)?;
+    let code = payload.code.trim();
+
+    let station = sqlx::query_as::<_, KitchenStation>(
+        r#"
+        UPDATE kitchen_stations
+        SET code = $1, name = $2, display_order = $3, active = $4, capacity = $5, printer = $6, auto_print = $7,
+            paper_width = $8
+        WHERE id = $9
+        RETURNING *
+        "#
+    )
+    .bind(code)
+    .bind(payload.name.trim())
+    .bind(payload.display_order)
+    .bind(payload.active)
+    .bind(payload.capacity)
+    .bind(payload.printer.as_deref().map(str::trim))
+    .bind(payload.auto_print)
+    .bind(payload.paper_width)
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| station_write_error(e, code))?
+    .ok_or((StatusCode::NOT_FOUND, "Kitchen station not found".to_string()))?;
+
+    Ok(Json(station))
+}
+
+pub async fn delete_station(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    // Menu items and order lines on this station become unrouted.
+    let result = sqlx::query("DELETE FROM kitchen_stations WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Kitchen station not found".to_string()));
+    }
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
+}
+
+fn validate_tax_rate(payload: &TaxRatePayload) -> Result<(), (StatusCode, String)> {
+    if payload.name.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required and cannot be empty".to_string()));
+    }
+    if payload.rate < BigDecimal::from(0) || payload.rate > BigDecimal::from(100) {
+        return Err((StatusCode::BAD_REQUEST, "Rate must be a percentage between 0 and 100".to_string()));
+    }
+    Ok(())
+}
+
+async fn clear_default_tax_rate(conn: &mut sqlx::PgConnection) -> Result<(), (StatusCode, String)> {
+    sqlx::query("UPDATE tax_rates SET is_default = false WHERE is_default")
+        .execute(conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    Ok(())
+}
+
+/// Wording a receipt template can override.
+const RECEIPT_LABELS: &[&str] = &["order", "token", "tax_id", "subtotal", "tax", "total"];
+/// Tallest logo accepted, in dots.
+const MAX_LOGO_HEIGHT: i32 = 512;
+
+pub async fn list_receipt_templates(State(state): State<AppState>) -> Result<Json<Vec<ReceiptTemplate>>, (StatusCode, String)> {
+    let templates = sqlx::query_as::<_, ReceiptTemplate>("SELECT * FROM receipt_templates ORDER BY id ASC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(templates))
+}
+
+/// The template tills print receipts with.
+pub async fn get_default_receipt_template(State(state): State<AppState>) -> Result<Json<ReceiptTemplate>, (StatusCode, String)> {
+    let template = sqlx::query_as::<_, ReceiptTemplate>("SELECT * FROM receipt_templates WHERE is_default")
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "No default receipt template".to_string()))?;
+
+    Ok(Json(template))
+}
+
+fn validate_receipt_template(payload: &ReceiptTemplatePayload) -> Result<(), (StatusCode, String)> {
+    let bad = |msg: &str| Err((StatusCode::BAD_REQUEST, msg.to_string()));
+    if payload.name.trim().is_empty() {
+        return bad("Name is required and cannot be empty");
+    }
+    let max_logo_width = match payload.paper_width {
+        58 => 384,
+        80 => 576,
+        _ => return bad("paper_width must be 58 or 80"),
+    };
+    if payload.currency_symbol.chars().count() > 8 {
+        return bad("currency_symbol is too long");
+    }
+    // Enough of BCP 47 to catch typos: a language, then optional region/script subtags.
+    let mut subtags = payload.locale.split(['-', '_']);
+    let language_ok = subtags.next().is_some_and(|l| (2..=3).contains(&l.len()) && l.chars().all(|c| c.is_ascii_alphabetic()));
+    if !language_ok || !subtags.all(|t| (2..=8).contains(&t.len()) && t.chars().all(|c| c.is_ascii_alphanumeric())) {
+        return bad("locale must be a language tag such as en-US or de-DE");
+    }
+    if payload.qr_code.as_ref().is_some_and(|q| q.len() > 500) {
+        return bad("qr_code is too long");
+    }
+    if let Some(key) = payload.labels.keys().find(|k| !RECEIPT_LABELS.contains(&k.as_str())) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown label '{}'. Allowed: {}", key, RECEIPT_LABELS.join(", "))));
+    }
+
+    match (payload.logo_width, payload.logo_height, payload.logo_data.as_deref()) {
+        (None, None, None) => {}
+        (Some(width), Some(height), Some(data)) => {
+            if !(1..=max_logo_width).contains(&width) {
+                return Err((StatusCode::BAD_REQUEST, format!("logo_width must be 1-{} dots for {}mm paper", max_logo_width, payload.paper_width)));
+            }
+            if !(1..=MAX_LOGO_HEIGHT).contains(&height) {
+                return Err((StatusCode::BAD_REQUEST, format!("logo_height must be 1-{} dots", MAX_LOGO_HEIGHT)));
+            }
+            let bytes = base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(|_| (StatusCode::BAD_REQUEST, "logo_data must be base64".to_string()))?;
+            let expected = (width as usize).div_ceil(8) * height as usize;
+            if bytes.len() != expected {
+                return Err((StatusCode::BAD_REQUEST, format!("logo_data should be {} bytes for a {}x{} logo, got {}", expected, width, height, bytes.len())));
+            }
+        }
+        _ => return bad("logo_width, logo_height and logo_data go together"),
+    }
+    Ok(())
+}
+
+/// Binds the payload as $1-$15, in `receipt_templates` column order.
+fn bind_receipt_template<'q>(
+    query: sqlx::query::QueryAs<'q, sqlx::Postgres, ReceiptTemplate, sqlx::postgres::PgArguments>,
+    payload: &'q ReceiptTemplatePayload,
+) -> sqlx::query::QueryAs<'q, sqlx::Postgres, ReceiptTemplate, sqlx::postgres::PgArguments> {
+    let text = |value: &'q Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty());
+    let labels: HashMap<&str, &str> = payload
+        .labels
+        .iter()
+        .map(|(k, v)| (k.as_str(), v.trim()))
+        .filter(|(_, v)| !v.is_empty())
+        .collect();
+
+    query
+        .bind(payload.name.trim())
+        .bind(payload.is_default)
+        .bind(payload.paper_width)
+        .bind(payload.store_name.trim())
+        .bind(text(&payload.store_address))
+        .bind(text(&payload.tax_id))
+        .bind(text(&payload.header))
+        .bind(text(&payload.footer))
+        .bind(payload.currency_symbol.trim())
+        .bind(payload.locale.trim())
+        .bind(text(&payload.qr_code))
+        .bind(payload.logo_width)
+        .bind(payload.logo_height)
+        .bind(payload.logo_data.as_deref())
+        .bind(json!(labels))
+}
+
+fn receipt_template_write_error(e: sqlx::Error, name: &str) -> (StatusCode, String) {
+    match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            (StatusCode::CONFLICT, format!("A receipt template named '{}' already exists", name.trim()))
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    }
+}
+
+async fn clear_default_receipt_template(conn: &mut sqlx::PgConnection) -> Result<(), (StatusCode, String)> {
+    sqlx::query("UPDATE receipt_templates SET is_default = false WHERE is_default")
+        .execute(conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    Ok(())
+}
+
+pub async fn create_receipt_template(
+    State(state): State<AppState>,
+    Json(payload): Json<ReceiptTemplatePayload>
+) -> Result<Json<ReceiptTemplate>, (StatusCode, String)> {
+    validate_receipt_template(&payload)?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    if payload.is_default {
+        clear_default_receipt_template(&mut tx).await?;
+    }
+
+    let query = sqlx::query_as::<_, ReceiptTemplate>(
+        r#"
+        INSERT INTO receipt_templates
+            (name, is_default, paper_width, store_name, store_address, tax_id, header, footer,
+             currency_symbol, locale, qr_code, logo_width, logo_height, logo_data, labels)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        RETURNING *
+        "#
+    );
+    let template = bind_receipt_template(query, &payload)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| receipt_template_write_error(e, &payload.name))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(template))
+}
+
+pub async fn update_receipt_template(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<ReceiptTemplatePayload>
+) -> Result<Json<ReceiptTemplate>, (StatusCode, String)> {
+    validate_receipt_template(&payload)?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    if payload.is_default {
+        clear_default_receipt_template(&mut tx).await?;
+    }
+
+    let query = sqlx::query_as::<_, ReceiptTemplate>(
+        r#"
+        UPDATE receipt_templates
+        SET name = $1, is_default = $2, paper_width = $3, store_name = $4, store_address = $5,
+            tax_id = $6, header = $7, footer = $8, currency_symbol = $9, locale = $10,
+            qr_code = $11, logo_width = $12, logo_height = $13, logo_data = $14, labels = $15
+        WHERE id = $16
+        RETURNING *
+        "#
+    );
+    let template = bind_receipt_template(query, &payload)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| receipt_template_write_error(e, &payload.name))?
+        .ok_or((StatusCode::NOT_FOUND, "Receipt template not found".to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(template))
+}
+
+pub async fn delete_receipt_template(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    // Tills always need something to print with.
+    let is_default: bool = sqlx::query_scalar("SELECT is_default FROM receipt_templates WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Receipt template not found".to_string()))?;
+    if is_default {
+        return Err((StatusCode::CONFLICT, "Make another template the default before deleting this one".to_string()));
+    }
+
+    sqlx::query("DELETE FROM receipt_templates WHERE id = $1")
+        .bind(id)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({"id": id, "status": "deleted"})))
+}
+
+pub async fn upload_image(
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let upload_dir = std::env::var("UPLOAD_DIR").unwrap_or_else(|_| "./uploads".to_string());
+    fs::create_dir_all(&upload_dir)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create upload directory: {}", e)))?;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Multipart error: {}", e)))?
+    {
+        // Plain form fields sent alongside the image are skipped.
+        let Some(file_name) = field.file_name().map(|s| s.to_string()) else {
+            continue;
+        };
+        let name = field.name().unwrap_or("file").to_string();
+        let data = field.bytes()
+            .await
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Failed to read file data: {}", e)))?;
+        if data.len() > MAX_FILE_SIZE {
+            return Err((StatusCode::PAYLOAD_TOO_LARGE, "File size exceeds 5MB limit".to_string()));
+        }
+
+        let ext = std::path::Path::new(&file_name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("bin")
+            .to_lowercase();
+        
+        if !ALLOWED_EXTENSIONS.contains(&ext.as_str()) {
+            return Err((StatusCode::BAD_REQUEST, "Invalid file type. Only images are allowed".to_string()));
+        }
+
+        let out_name = format!("{}-{}.{}", name, Uuid::new_v4(), ext);
+        let out_path = std::path::Path::new(&upload_dir).join(&out_name);
+        
+        tokio::fs::write(&out_path, &data)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save file: {}", e)))?;
+
+        let url = format!("/uploads/{}", out_name);
+        return Ok(Json(json!({"url": url})));
+    }
+
+    Err((StatusCode::BAD_REQUEST, "No file field found".to_string()))
+}
+
+/// The retry key for an order: the `Idempotency-Key` header or the body's `client_order_id`.
+fn idempotency_key(headers: &HeaderMap, payload: &CreateOrder) -> Result<Option<String>, (StatusCode, String)> {
+    let header = headers
+        .get("idempotency-key")
+        .map(|v| v.to_str().map(|v| v.trim().to_string()))
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Idempotency-Key must be plain text".to_string()))?;
+    if header.as_ref().is_some_and(|k| k.is_empty() || k.len() > 255) {
+        return Err((StatusCode::BAD_REQUEST, "Idempotency-Key must be 1 to 255 characters".to_string()));
+    }
+    // UUID keys are stored in canonical form, so a client that changes case or drops
+    // the hyphens between retries still matches its first attempt.
+    let header = header.map(|k| Uuid::parse_str(&k).map(|id| id.to_string()).unwrap_or(k));
+
+    let client_order_id = payload.client_order_id
+        .as_deref()
+        .map(|id| Uuid::parse_str(id.trim())
+            .map(|id| id.to_string())
+            .map_err(|_| (StatusCode::BAD_REQUEST, "client_order_id must be a UUID".to_string())))
+        .transpose()?;
+
+    match (header, client_order_id) {
+        (Some(header), Some(id)) if header != id => Err((
+            StatusCode::BAD_REQUEST,
+            "Idempotency-Key and client_order_id don't match".to_string(),
+        )),
+        (header, id) => Ok(header.or(id)),
+    }
+}
+
+pub async fn create_order(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateOrder>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let idempotency_key = idempotency_key(&headers, &payload)?;
+    place_order(&state, &payload, idempotency_key).await.map(Json)
+}
+
+/// Kitchen tickets print notes in full, so they stay short.
+const MAX_ITEM_NOTE: usize = 200;
+
+/// Prices and saves an order, allocates its queue token and announces it. With an
+/// idempotency key, a repeat of an earlier request returns that order instead.
+async fn place_order(
+    state: &AppState,
+    payload: &CreateOrder,
+    idempotency_key: Option<String>,
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    if payload.items.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Order must contain at least one item".to_string()));
+    }
+
+    let request = serde_json::to_string(payload)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to encode order: {}", e)))?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    // A concurrent retry with the same key waits here until the first request commits,
+    // then finds the order instead of inserting.
+    let order_id: i32 = match sqlx::query_scalar(
+        r#"
+        INSERT INTO orders (source, status, currency, idempotency_key, request_hash)
+        VALUES ($1, 'pending', $2, $3, md5($4))
+        ON CONFLICT (idempotency_key) WHERE idempotency_key IS NOT NULL DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(&payload.source)
+    .bind(&*state.currency)
+    .bind(&idempotency_key)
+    .bind(&request)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create order: {}", e)))?
+    {
+        Some(id) => id,
+        None => {
+            drop(tx);
+            let key = idempotency_key.unwrap_or_default();
+            return replay_order(state, &key, &request).await;
+        }
+    };
+
+    let mut totals = OrderTotals::default();
+
+    for item in payload.items.iter() {
+        if item.quantity <= 0 {
+            return Err((StatusCode::BAD_REQUEST, "Item quantity must be greater than 0".to_string()));
+        }
+        let note = item.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+        if note.is_some_and(|n| n.chars().count() > MAX_ITEM_NOTE) {
+            return Err((StatusCode::BAD_REQUEST, format!("Item notes are limited to {} characters", MAX_ITEM_NOTE)));
+        }
+
+        let priced = sqlx::query(
+            r#"
+            SELECT mi.price, mi.available, mi.station_id, t.rate, t.inclusive
+            FROM menu_items mi
+            LEFT JOIN tax_rates t ON t.active AND t.id = COALESCE(
+                mi.tax_rate_id,
+                (SELECT id FROM tax_rates WHERE is_default AND active)
+            )
+            WHERE mi.id = $1 AND mi.deleted_at IS NULL
+            "#
+        )
+        .bind(item.menu_item_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::BAD_REQUEST, format!("Menu item {} not found", item.menu_item_id)))?;
+        if !priced.get::<bool, _>("available") {
+            return Err((StatusCode::BAD_REQUEST, format!("Menu item {} is unavailable", item.menu_item_id)));
+        }
+
+        let base_price: Money = priced.get("price");
+        let tax = priced.get::<Option<BigDecimal>, _>("rate").map(|rate| LineTax {
+            rate,
+            inclusive: priced.get::<Option<bool>, _>("inclusive").unwrap_or(false),
+        });
+
+        let groups = load_modifier_groups(&mut tx, item.menu_item_id).await?;
+        let chosen = resolve_modifiers(&groups, &item.modifiers)
+            .map_err(|msg| (StatusCode::BAD_REQUEST, format!("Menu item {}: {}", item.menu_item_id, msg)))?;
+
+        let line = price_line(&base_price, chosen.iter().map(|(_, o)| &o.price_delta), item.quantity, tax.as_ref());
+        let tax_inclusive = tax.as_ref().is_some_and(|t| t.inclusive);
+        totals.add_line(&line, tax_inclusive);
+
+        let order_item = sqlx::query_as::<_, OrderItem>(
+            r#"
+            INSERT INTO order_items
+                (order_id, menu_item_id, quantity, base_price, unit_price, line_total, tax_rate, tax_inclusive, tax_amount, station_id, note)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING *
+            "#
+        )
+        .bind(order_id)
+        .bind(item.menu_item_id)
+        .bind(item.quantity)
+        .bind(&base_price)
+        .bind(&line.unit_price)
+        .bind(&line.line_total)
+        .bind(tax.as_ref().map(|t| t.rate.clone()).unwrap_or_default())
+        .bind(tax_inclusive)
+        .bind(&line.tax_amount)
+        .bind(priced.get::<Option<i32>, _>("station_id"))
+        .bind(note)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to add order item: {}", e)))?;
+
+        for (group, option) in chosen {
+            sqlx::query(
+                "INSERT INTO order_item_modifiers (order_item_id, modifier_option_id, group_name, option_name, price_delta) VALUES ($1, $2, $3, $4, $5)"
+            )
+            .bind(order_item.id)
+            .bind(option.id)
+            .bind(&group.name)
+            .bind(&option.name)
+            .bind(&option.price_delta)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to add order item modifier: {}", e)))?;
+        }
+    }
+
+    sqlx::query("INSERT INTO order_status_history (order_id, from_status, to_status) VALUES ($1, NULL, 'pending')")
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record status history: {}", e)))?;
+
+    let token = allocate_token(&mut tx, &state.tokens, &payload.source)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to allocate queue token: {}", e)))?;
+
+    sqlx::query(
+        "UPDATE orders SET subtotal = $1, tax_total = $2, total = $3, token_number = $4, token_label = $5 WHERE id = $6"
+    )
+    .bind(&totals.subtotal)
+    .bind(&totals.tax_total)
+    .bind(&totals.total)
+    .bind(token.number)
+    .bind(&token.label)
+    .bind(order_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save order totals: {}", e)))?;
+
+    sqlx::query(
+        "INSERT INTO queue_tokens (order_id, token_number, token_label, business_date, status) VALUES ($1, $2, $3, $4, 'waiting')"
+    )
+    .bind(order_id)
+    .bind(token.number)
+    .bind(&token.label)
+    .bind(token.business_date)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create queue token: {}", e)))?;
+
+    let receipt_job = if payload.print_receipt {
+        let receipt = printing::receipt_job(&mut tx, order_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        let job = printing::NewPrintJob {
+            printer: &state.printing.kiosk_printer,
+            kind: "receipt",
+            order_id: Some(order_id),
+            station_id: None,
+            payload: json!(receipt),
+            requested_by: None,
+        };
+        let job = printing::enqueue(&mut tx, &state.printing, job)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to queue receipt: {}", e)))?;
+        Some(job)
+    } else {
+        None
+    };
+    let kitchen_tickets = printing::queue_kitchen_tickets(&mut tx, &state.printing, order_id, None, false, None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to queue kitchen tickets: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    for job in receipt_job.iter().chain(kitchen_tickets.iter()) {
+        state.bcast.publish("print.queued", print_event(job));
+    }
+    state.bcast.publish("order.created", json!({
+        "order_id": order_id,
+        "source": payload.source,
+        "token": token.label,
+        "token_number": token.number,
+        "total": totals.total,
+    }));
+
+    // Broadcast queue update
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            broadcast_queue_inner(&state).await;
+            broadcast_station_tickets(&state, order_id).await;
+        }
+    });
+
+    Ok(json!({
+        "order_id": order_id,
+        "status": "created",
+        "token_number": token.number,
+        "token": token.label,
+        "subtotal": totals.subtotal,
+        "tax_total": totals.tax_total,
+        "total": totals.total,
+        "currency": &*state.currency,
+        "print_job_id": receipt_job.map(|j| j.id),
+        "replayed": false,
+    }))
+}
+
+/// Answers a retried `POST /orders` with the order the first attempt created.
+async fn replay_order(
+    state: &AppState,
+    idempotency_key: &str,
+    request: &str,
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    let order = sqlx::query_as::<_, Order>("SELECT * FROM orders WHERE idempotency_key = $1")
+        .bind(idempotency_key)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let same_request: bool = sqlx::query_scalar("SELECT $1::text IS NOT DISTINCT FROM md5($2)")
+        .bind(&order.request_hash)
+        .bind(request)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !same_request {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "This idempotency key was already used for a different order".to_string(),
+        ));
+    }
+
+    // The receipt the first attempt queued; staff reprints carry who asked for them.
+    let print_job_id: Option<i32> = sqlx::query_scalar(
+        "SELECT min(id) FROM print_jobs WHERE order_id = $1 AND kind = 'receipt' AND requested_by IS NULL"
+    )
+    .bind(order.id)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(json!({
+        "order_id": order.id,
+        "status": "created",
+        "token_number": order.token_number,
+        "token": order.token_label,
+        "subtotal": order.subtotal,
+        "tax_total": order.tax_total,
+        "total": order.total,
+        "currency": order.currency,
+        "print_job_id": print_job_id,
+        "replayed": true,
+    }))
+}
+
+pub async fn list_orders(State(state): State<AppState>) -> Result<Json<Vec<Order>>, (StatusCode, String)> {
+    let orders = sqlx::query_as::<_, Order>("SELECT * FROM orders ORDER BY created_at DESC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    
+    Ok(Json(orders))
+}
+
+pub async fn list_orders_detailed(State(state): State<AppState>) -> Result<Json<Vec<OrderDetailed>>, (StatusCode, String)> {
+    let orders = sqlx::query_as::<_, Order>("SELECT * FROM orders ORDER BY created_at DESC")
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut result = Vec::new();
+    for order in orders.into_iter() {
+        let items = load_order_items_detailed(&state, order.id).await?;
+        result.push(OrderDetailed { order, items });
+    }
+
+    Ok(Json(result))
+}
+
+pub async fn list_queue(State(state): State<AppState>) -> Result<Json<Vec<QueueToken>>, (StatusCode, String)> {
+    let q = fetch_queue(&state)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(q))
+}
+
+pub async fn broadcast_queue(State(state): State<AppState>) -> Json<serde_json::Value> {
+    broadcast_queue_inner(&state).await;
+    Json(json!({"status": "queue broadcast sent"}))
+}
+
+async fn broadcast_queue_inner(state: &AppState) {
+    if let Ok(queue) = fetch_queue(state).await {
+        state.bcast.publish("queue.snapshot", json!({ "tokens": queue }));
+    }
+}
+
+/// Sends the order's current ticket to each station that has a part in it.
+async fn broadcast_station_tickets(state: &AppState, order_id: i32) {
+    let Ok(mut conn) = state.pool.acquire().await else { return };
+    match kitchen::load_tickets(&mut conn, None, Some(order_id)).await {
+        Ok(tickets) => {
+            for ticket in tickets {
+                state.bcast.publish_to(&kitchen::station_topic(&ticket.station_code), "station.ticket", json!(ticket));
+            }
+        }
+        Err(e) => eprintln!("Failed to load station tickets for order {}: {}", order_id, e),
+    }
+}
+
+/// The queue with a ready-time estimate on every token still waiting.
+async fn fetch_queue(state: &AppState) -> Result<Vec<QueueToken>, sqlx::Error> {
+    let mut conn = state.pool.acquire().await?;
+    let mut tokens = sqlx::query_as::<_, QueueToken>("SELECT * FROM queue_tokens WHERE archived_at IS NULL ORDER BY id ASC")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let estimates = estimates::estimate_ready_times(&mut conn, state.calibration.factor()).await?;
+    let now = Utc::now();
+    for token in tokens.iter_mut().filter(|t| t.status == "waiting") {
+        if let Some(ready_at) = estimates.get(&token.order_id) {
+            token.estimated_ready_at = Some(*ready_at);
+            token.estimated_wait_seconds = Some((*ready_at - now).num_seconds().max(0));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Announces a ready token on the queue screens. `token` is the label customers see,
+/// e.g. `K-012`.
+pub async fn call_token(
+    State(state): State<AppState>,
+    Path(token): Path<String>
+) -> Result<Json<QueueToken>, (StatusCode, String)> {
+    announce_token(&state, &token, false).await.map(Json)
+}
+
+/// Announces a token again for a customer who missed the first call.
+pub async fn recall_token(
+    State(state): State<AppState>,
+    Path(token): Path<String>
+) -> Result<Json<QueueToken>, (StatusCode, String)> {
+    announce_token(&state, &token, true).await.map(Json)
+}
+
+/// A ready token gets called, and only a called one recalled.
+fn can_announce(label: &str, status: &str, recall: bool) -> Result<(), String> {
+    match (status, recall) {
+        ("ready", false) | ("called", true) => Ok(()),
+        ("waiting", _) => Err(format!("Order {} is not ready yet", label)),
+        ("called", false) => Err(format!("Token {} was already called; recall it instead", label)),
+        ("ready", true) => Err(format!("Token {} hasn't been called yet", label)),
+        (other, _) => Err(format!("Token {} is {}", label, other)),
+    }
+}
+
+async fn announce_token(state: &AppState, label: &str, recall: bool) -> Result<QueueToken, (StatusCode, String)> {
+    let label = label.trim().to_uppercase();
+    let current: (i32, String) = sqlx::query_as(
+        "SELECT id, status FROM queue_tokens WHERE token_label = $1 AND archived_at IS NULL ORDER BY business_date DESC LIMIT 1"
+    )
+    .bind(&label)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, format!("Token {} is not in the queue", label)))?;
+
+    let (id, status) = current;
+    can_announce(&label, &status, recall).map_err(|e| (StatusCode::CONFLICT, e))?;
+
+    // The status check is repeated so two tills pressing call at once announce it once.
+    let token = sqlx::query_as::<_, QueueToken>(
+        r#"
+        UPDATE queue_tokens
+        SET status = 'called', call_count = call_count + 1, last_called_at = now()
+        WHERE id = $1 AND status = $2 AND archived_at IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(&status)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::CONFLICT, format!("Token {} changed while being called", label)))?;
+
+    state.bcast.publish("queue.call", json!({
+        "token": token.token_label,
+        "order_id": token.order_id,
+        "call_count": token.call_count,
+        "recall": recall,
+    }));
+    tokio::spawn({
+        let state = state.clone();
+        async move { broadcast_queue_inner(&state).await; }
+    });
+
+    Ok(token)
+}
+
+/// Archives ready tokens nobody collected within `QUEUE_NO_SHOW_MINUTES` of their last
+/// call. Runs on a timer from `main`.
+pub async fn mark_no_shows(state: &AppState) {
+    if state.queue.no_show_minutes == 0 {
+        return;
+    }
+
+    let expired = sqlx::query_as::<_, QueueToken>(
+        r#"
+        UPDATE queue_tokens
+        SET status = 'no_show', no_show_at = now(), archived_at = now()
+        WHERE archived_at IS NULL
+          AND status IN ('ready', 'called')
+          AND COALESCE(last_called_at, ready_at, created_at) < now() - make_interval(mins => $1)
+        RETURNING *
+        "#
+    )
+    .bind(state.queue.no_show_minutes as i32)
+    .fetch_all(&state.pool)
+    .await;
+
+    match expired {
+        Ok(tokens) if !tokens.is_empty() => {
+            for token in tokens.iter() {
+                state.bcast.publish("queue.no_show", json!({
+                    "token": token.token_label,
+                    "order_id": token.order_id,
+                    "call_count": token.call_count,
+                }));
+            }
+            broadcast_queue_inner(state).await;
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to mark no-show tokens: {}", e),
+    }
+}
+
+/// Statuses an order may move to from `from`. `cancelled` and `refunded` are terminal.
+fn allowed_transitions(from: &str) -> &'static [&'static str] {
+    match from {
+        "pending" => &["preparing", "ready", "cancelled"],
+        "preparing" => &["ready", "cancelled"],
+        "ready" => &["completed", "cancelled"],
+        "completed" => &["refunded"],
+        _ => &[],
+    }
+}
+
+pub async fn update_order_status(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateOrderStatus>
+) -> Result<Json<serde_json::Value>, Response> {
+    let new_status = payload.status.trim().to_lowercase();
+
+    if !ALLOWED_STATUSES.contains(&new_status.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid status. Allowed: {}", ALLOWED_STATUSES.join(", "))).into_response());
+    }
+
+    if new_status == "cancelled" || new_status == "refunded" {
+        return Err((
+            StatusCode::CONFLICT,
+            "Cancel or refund an order through its void or refund endpoint so the reversal is recorded".to_string(),
+        ).into_response());
+    }
+
+    // The route only needs `orders.view`; what the staff member may move the order to depends on their role.
+    staff.require(status_permission(&new_status)).map_err(IntoResponse::into_response)?;
+
+    let old_status = apply_order_status(&state, id, &new_status, &staff)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok(Json(json!({ "order_id": id, "old_status": old_status, "new_status": new_status })))
+}
+
+async fn apply_order_status(
+    state: &AppState,
+    id: i32,
+    new_status: &str,
+    staff: &AuthStaff,
+) -> Result<String, (StatusCode, String)> {
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    let old_status = transition_order_status(&mut tx, id, new_status, Some(staff)).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    state.bcast.publish("order.status_changed", json!({
+        "order_id": id,
+        "old_status": old_status,
+        "new_status": new_status,
+    }));
+
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            broadcast_queue_inner(&state).await;
+            broadcast_station_tickets(&state, id).await;
+        }
+    });
+
+    Ok(old_status)
+}
+
+/// Moves an order along the transition graph, records the change in `order_status_history`
+/// and applies the queue side effects. Returns the previous status.
+async fn transition_order_status(
+    conn: &mut sqlx::PgConnection,
+    order_id: i32,
+    new_status: &str,
+    staff: Option<&AuthStaff>,
+) -> Result<String, (StatusCode, String)> {
+    let old_status: String = sqlx::query_scalar("SELECT status FROM orders WHERE id = $1 FOR UPDATE")
+        .bind(order_id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))?;
+
+    if !allowed_transitions(&old_status).contains(&new_status) {
+        return Err((StatusCode::CONFLICT, format!("Cannot move order from '{}' to '{}'", old_status, new_status)));
+    }
+
+    sqlx::query("UPDATE orders SET status = $1, updated_at = now() WHERE id = $2")
+        .bind(new_status)
+        .bind(order_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    sqlx::query("INSERT INTO order_status_history (order_id, from_status, to_status, actor, staff_id) VALUES ($1, $2, $3, $4, $5)")
+        .bind(order_id)
+        .bind(&old_status)
+        .bind(new_status)
+        .bind(staff.map(|s| s.name.as_str()))
+        .bind(staff.map(|s| s.id))
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record status history: {}", e)))?;
+
+    match new_status {
+        "ready" => {
+            sqlx::query("UPDATE queue_tokens SET status = 'ready', ready_at = now() WHERE order_id = $1 AND archived_at IS NULL")
+                .bind(order_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Queue update error: {}", e)))?;
+
+            // Marking the whole order ready clears it from every station.
+            sqlx::query(
+                r#"
+                UPDATE order_items
+                SET status = 'done', started_at = COALESCE(started_at, now()), done_at = now()
+                WHERE order_id = $1 AND status <> 'done'
+                "#
+            )
+            .bind(order_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        }
+        // Tokens are archived rather than deleted. A no-show collected late keeps its
+        // status but still gets `collected_at`.
+        "completed" => {
+            sqlx::query(
+                r#"
+                UPDATE queue_tokens
+                SET status = CASE WHEN status = 'no_show' THEN status ELSE 'collected' END,
+                    collected_at = now(), archived_at = COALESCE(archived_at, now())
+                WHERE order_id = $1
+                "#
+            )
+            .bind(order_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Queue cleanup error: {}", e)))?;
+        }
+        "cancelled" => {
+            sqlx::query("UPDATE queue_tokens SET status = 'cancelled', archived_at = now() WHERE order_id = $1 AND archived_at IS NULL")
+                .bind(order_id)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Queue cleanup error: {}", e)))?;
+        }
+        _ => {}
+    }
+
+    Ok(old_status)
+}
+
+pub async fn get_order_history(
+    State(state): State<AppState>,
+    Path(order_id): Path<i32>
+) -> Result<Json<Vec<OrderStatusChange>>, (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM orders WHERE id = $1)")
+        .bind(order_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Order not found".to_string()));
+    }
+
+    // Each stage lasts until the next transition; the current stage has no duration yet.
+    let history = sqlx::query_as::<_, OrderStatusChange>(
+        r#"
+        SELECT id, order_id, from_status, to_status, actor, staff_id, changed_at,
+               EXTRACT(EPOCH FROM (LEAD(changed_at) OVER w - changed_at))::float8 AS duration_seconds
+        FROM order_status_history
+        WHERE order_id = $1
+        WINDOW w AS (ORDER BY changed_at, id)
+        ORDER BY changed_at, id
+        "#
+    )
+    .bind(order_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(history))
+}
+
+/// Orders still in the kitchen with lines for this station, oldest first. Station displays
+/// load this once and then follow `station:<code>` on `/ws`.
+pub async fn list_station_tickets(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<Vec<StationTicket>>, (StatusCode, String)> {
+    let mut conn = state.pool.acquire()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let code: String = sqlx::query_scalar("SELECT code FROM kitchen_stations WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Kitchen station not found".to_string()))?;
+
+    let tickets = kitchen::load_tickets(&mut conn, Some(&code), None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(tickets))
+}
+
+/// Moves one order line through queued → cooking → done. The first line started takes
+/// the order to `preparing`; once every line routed to a station is done the order
+/// becomes `ready` on its own.
+pub async fn update_order_item_status(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateItemStatus>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let new_status = payload.status.trim().to_lowercase();
+    if !ITEM_STATUSES.contains(&new_status.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid status. Allowed: {}", ITEM_STATUSES.join(", "))));
+    }
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    let order_id: i32 = sqlx::query_scalar("SELECT order_id FROM order_items WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Order item not found".to_string()))?;
+
+    // The order row is locked first so two stations finishing at once can't both miss
+    // that the other was the last line.
+    let order_status: String = sqlx::query_scalar("SELECT status FROM orders WHERE id = $1 FOR UPDATE")
+        .bind(order_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if order_status != "pending" && order_status != "preparing" {
+        return Err((StatusCode::CONFLICT, format!("Order is already {}", order_status)));
+    }
+
+    let (old_status, remaining): (String, i64) = sqlx::query_as(
+        r#"
+        SELECT oi.status, oi.quantity - COALESCE((
+            SELECT SUM(ai.quantity) FROM order_adjustment_items ai WHERE ai.order_item_id = oi.id
+        ), 0)
+        FROM order_items oi
+        WHERE oi.id = $1
+        FOR UPDATE
+        "#
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if remaining <= 0 {
+        return Err((StatusCode::CONFLICT, "This line was voided".to_string()));
+    }
+
+    let item = sqlx::query_as::<_, OrderItem>(
+        r#"
+        UPDATE order_items
+        SET status = $1,
+            started_at = CASE WHEN $1 = 'queued' THEN NULL ELSE COALESCE(started_at, now()) END,
+            done_at = CASE WHEN $1 = 'done' THEN COALESCE(done_at, now()) ELSE NULL END
+        WHERE id = $2
+        RETURNING *
+        "#
+    )
+    .bind(&new_status)
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut status_changes: Vec<(String, &str)> = Vec::new();
+    if order_status == "pending" && new_status != "queued" {
+        let from = transition_order_status(&mut tx, order_id, "preparing", Some(&staff)).await?;
+        status_changes.push((from, "preparing"));
+    }
+
+    // Lines no station makes, and lines voided in full, don't hold the order back.
+    let all_done: bool = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(bool_and(oi.status = 'done'), false)
+        FROM order_items oi
+        WHERE oi.order_id = $1 AND oi.station_id IS NOT NULL
+          AND oi.quantity > COALESCE((
+              SELECT SUM(ai.quantity) FROM order_adjustment_items ai WHERE ai.order_item_id = oi.id
+          ), 0)
+        "#
+    )
+    .bind(order_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if all_done {
+        let from = transition_order_status(&mut tx, order_id, "ready", Some(&staff)).await?;
+        status_changes.push((from, "ready"));
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    if old_status != new_status {
+        state.bcast.publish("order.item_status_changed", json!({
+            "order_id": order_id,
+            "item_id": id,
+            "station_id": item.station_id,
+            "old_status": old_status,
+            "new_status": new_status,
+        }));
+    }
+    for (from, to) in status_changes.iter() {
+        state.bcast.publish("order.status_changed", json!({
+            "order_id": order_id,
+            "old_status": from,
+            "new_status": to,
+        }));
+    }
+
+    // Wait estimates move with every line, not just with the order status.
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            broadcast_queue_inner(&state).await;
+            broadcast_station_tickets(&state, order_id).await;
+        }
+    });
+
+    let order_status = status_changes.last().map_or(order_status.as_str(), |(_, to)| to).to_string();
+    Ok(Json(json!({ "item": item, "order_status": order_status })))
+}
+
+const TENDERS: &[&str] = &["cash", "card", "other"];
+
+/// How much of a tender goes towards the order, and the change handed back.
+#[derive(Debug, PartialEq)]
+struct Settlement {
+    amount: Money,
+    tendered: Money,
+    change_given: Money,
+}
+
+/// Works out a payment against `balance_due`. Without an amount the payment covers what
+/// was tendered, or the whole balance; cash handed over beyond the balance is change.
+fn settle(
+    tender: &str,
+    amount: Option<Money>,
+    tendered: Option<Money>,
+    balance_due: &Money,
+) -> Result<Settlement, String> {
+    let amount = match (amount, &tendered) {
+        (Some(amount), _) => amount,
+        (None, Some(tendered)) if tender == "cash" => tendered.clone().min(balance_due.clone()),
+        (None, Some(tendered)) => tendered.clone(),
+        (None, None) => balance_due.clone(),
+    };
+    if &amount > balance_due {
+        return Err(format!("Payment of {} exceeds the balance due of {}", amount, balance_due));
+    }
+
+    let tendered = tendered.unwrap_or_else(|| amount.clone());
+    if tendered < amount {
+        return Err("Tendered amount is less than the payment amount".to_string());
+    }
+    if tender != "cash" && tendered != amount {
+        return Err("Only cash payments can give change".to_string());
+    }
+    let change_given = &tendered - &amount;
+    Ok(Settlement { amount, tendered, change_given })
+}
+
+pub async fn record_payment(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(order_id): Path<i32>,
+    Json(payload): Json<CreatePayment>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let tender = payload.tender.trim().to_lowercase();
+    if !TENDERS.contains(&tender.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid tender. Allowed: {}", TENDERS.join(", "))));
+    }
+    for value in [&payload.amount, &payload.tendered].into_iter().flatten() {
+        if value.is_negative() || value.is_zero() {
+            return Err((StatusCode::BAD_REQUEST, "Payment amounts must be greater than zero".to_string()));
+        }
+    }
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    // Locked so two tills can't both settle the same balance.
+    // Voided lines no longer count towards what is owed.
+    let (status, total, amount_paid): (String, Money, Money) = sqlx::query_as(
+        "SELECT status, total + adjustment_total, amount_paid FROM orders WHERE id = $1 FOR UPDATE"
+    )
+    .bind(order_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))?;
+
+    if status == "cancelled" || status == "refunded" {
+        return Err((StatusCode::CONFLICT, format!("Cannot take payment for a {} order", status)));
+    }
+
+    let balance_due = &total - &amount_paid;
+    if balance_due.is_negative() || balance_due.is_zero() {
+        return Err((StatusCode::CONFLICT, "Order is already paid".to_string()));
+    }
+
+    let Settlement { amount, tendered, change_given } =
+        settle(&tender, payload.amount, payload.tendered, &balance_due).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let payment = sqlx::query_as::<_, Payment>(
+        r#"
+        INSERT INTO payments (order_id, tender, amount, tendered, change_given, reference, staff_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#
+    )
+    .bind(order_id)
+    .bind(&tender)
+    .bind(&amount)
+    .bind(&tendered)
+    .bind(&change_given)
+    .bind(payload.reference.as_deref().map(str::trim).filter(|r| !r.is_empty()))
+    .bind(staff.id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record payment: {}", e)))?;
+
+    let amount_paid = &amount_paid + &amount;
+    let balance_due = &total - &amount_paid;
+    let payment_status = payment_status(&total, &amount_paid);
+
+    sqlx::query("UPDATE orders SET amount_paid = $1, payment_status = $2, updated_at = now() WHERE id = $3")
+        .bind(&amount_paid)
+        .bind(payment_status)
+        .bind(order_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    state.bcast.publish("order.payment_recorded", json!({
+        "order_id": order_id,
+        "payment_id": payment.id,
+        "tender": payment.tender,
+        "amount": payment.amount,
+        "amount_paid": amount_paid,
+        "balance_due": balance_due,
+        "payment_status": payment_status,
+    }));
+
+    // Only a cash tender should pop the drawer; the till opens it when told to here.
+    Ok(Json(json!({
+        "open_drawer": tender == "cash",
+        "amount_paid": amount_paid,
+        "balance_due": balance_due,
+        "payment_status": payment_status,
+        "payment": payment,
+    })))
+}
+
+fn payment_status(amount_due: &Money, amount_paid: &Money) -> &'static str {
+    if amount_paid.is_zero() {
+        "unpaid"
+    } else if amount_paid >= amount_due {
+        "paid"
+    } else {
+        "partially_paid"
+    }
+}
+
+pub async fn list_payments(
+    State(state): State<AppState>,
+    Path(order_id): Path<i32>
+) -> Result<Json<Vec<Payment>>, (StatusCode, String)> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM orders WHERE id = $1)")
+        .bind(order_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Order not found".to_string()));
+    }
+
+    let payments = sqlx::query_as::<_, Payment>("SELECT * FROM payments WHERE order_id = $1 ORDER BY created_at, id")
+        .bind(order_id)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(payments))
+}
+
+pub async fn void_order_items(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(order_id): Path<i32>,
+    Json(payload): Json<CreateAdjustment>
+) -> Result<Json<serde_json::Value>, Response> {
+    apply_adjustment(&state, &staff, order_id, "void", payload).await
+}
+
+pub async fn refund_order_items(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(order_id): Path<i32>,
+    Json(payload): Json<CreateAdjustment>
+) -> Result<Json<serde_json::Value>, Response> {
+    apply_adjustment(&state, &staff, order_id, "refund", payload).await
+}
+
+pub async fn list_adjustment_reasons() -> Json<serde_json::Value> {
+    Json(json!({ "void": VOID_REASONS, "refund": REFUND_REASONS }))
+}
+
+/// Records a void (`kind = "void"`, before the order is paid) or a refund (after). Both take
+/// back whole or partial lines; refunds also pay the money back through the original tenders.
+/// Taking back everything left on the order cancels it, or marks it refunded if it was completed.
+async fn apply_adjustment(
+    state: &AppState,
+    staff: &AuthStaff,
+    order_id: i32,
+    kind: &str,
+    payload: CreateAdjustment,
+) -> Result<Json<serde_json::Value>, Response> {
+    let (reasons, permission) = match kind {
+        "void" => (VOID_REASONS, Permission::OrdersVoid),
+        _ => (REFUND_REASONS, Permission::OrdersRefund),
+    };
+
+    let reason_code = payload.reason_code.trim().to_lowercase();
+    if !reasons.contains(&reason_code.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid reason code. Allowed: {}", reasons.join(", "))).into_response());
+    }
+    let note = payload.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    if reason_code == "other" && note.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "A note is required when the reason is 'other'".to_string()).into_response());
+    }
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)).into_response())?;
+
+    let (status, amount_due, amount_paid, current_payment_status): (String, Money, Money, String) = sqlx::query_as(
+        "SELECT status, total + adjustment_total, amount_paid, payment_status FROM orders WHERE id = $1 FOR UPDATE"
+    )
+    .bind(order_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response())?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Order not found".to_string()).into_response())?;
+
+    if status == "cancelled" || status == "refunded" {
+        return Err((StatusCode::CONFLICT, format!("Order is already {}", status)).into_response());
+    }
+    if kind == "void" && current_payment_status == "paid" {
+        return Err((StatusCode::CONFLICT, "Order has been paid; refund it instead".to_string()).into_response());
+    }
+    if kind == "refund" && current_payment_status != "paid" {
+        return Err((StatusCode::CONFLICT, "Only paid orders can be refunded; void unpaid items instead".to_string()).into_response());
+    }
+
+    let lines = sqlx::query_as::<_, SoldLine>(
+        r#"
+        SELECT oi.id, oi.quantity, oi.unit_price, oi.line_total, oi.tax_amount, oi.tax_rate, oi.tax_inclusive,
+               COALESCE(SUM(ai.quantity), 0)::int4 AS returned_quantity,
+               COALESCE(-SUM(ai.line_total), 0) AS returned_line_total,
+               COALESCE(-SUM(ai.tax_amount), 0) AS returned_tax
+        FROM order_items oi
+        LEFT JOIN order_adjustment_items ai ON ai.order_item_id = oi.id
+        WHERE oi.order_id = $1
+        GROUP BY oi.id
+        ORDER BY oi.id
+        "#
+    )
+    .bind(order_id)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response())?;
+
+    let requested: Vec<(i32, i32)> = match &payload.items {
+        Some(items) => items.iter().map(|i| (i.order_item_id, i.quantity)).collect(),
+        None => lines.iter()
+            .filter(|l| l.remaining_quantity() > 0)
+            .map(|l| (l.id, l.remaining_quantity()))
+            .collect(),
+    };
+    if requested.is_empty() {
+        return Err((StatusCode::CONFLICT, format!("Nothing left on this order to {}", kind)).into_response());
+    }
+
+    let mut returned = Vec::new();
+    let mut totals = OrderTotals::default();
+    for (order_item_id, quantity) in requested {
+        let line = lines.iter()
+            .find(|l| l.id == order_item_id)
+            .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Item {} is not part of this order", order_item_id)).into_response())?;
+        if returned.iter().any(|(id, _, _)| *id == order_item_id) {
+            return Err((StatusCode::BAD_REQUEST, format!("Item {} is listed more than once", order_item_id)).into_response());
+        }
+        if quantity <= 0 || quantity > line.remaining_quantity() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Item {} has {} left to {}", order_item_id, line.remaining_quantity(), kind),
+            ).into_response());
+        }
+
+        let priced = line.price_return(quantity);
+        totals.subtotal += &priced.line_total;
+        totals.tax_total += &priced.tax_amount;
+        totals.total += &priced.total;
+        returned.push((order_item_id, quantity, priced));
+    }
+
+    if kind == "void" && &amount_due - &totals.total < amount_paid {
+        return Err((
+            StatusCode::CONFLICT,
+            "Voiding these items would leave less owed than has already been paid".to_string(),
+        ).into_response());
+    }
+
+    // Staff without the permission, and anyone refunding a large amount, need a manager's PIN.
+    let high_value = kind == "refund" && totals.total > state.adjustments.refund_approval_threshold;
+    let approved_by = if staff.can(permission) && !high_value {
+        None
+    } else {
+        let Some(approval) = &payload.approval else {
+            return Err(Forbidden { permission, roles: staff.roles.clone(), approval_required: true }.into_response());
+        };
+        let check = auth::check_approval(&state.pool, &approval.username, &approval.pin)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response())?;
+        match check {
+            // Approving your own adjustment counts as no approval at all.
+            LoginCheck::Ok(approver) if approver.id == staff.id => {
+                return Err(Forbidden { permission, roles: staff.roles.clone(), approval_required: true }.into_response());
+            }
+            LoginCheck::Ok(approver) if approver.can(permission) => Some(approver.id),
+            LoginCheck::Ok(approver) => {
+                return Err(Forbidden { permission, roles: approver.roles, approval_required: true }.into_response());
+            }
+            LoginCheck::Invalid => {
+                return Err((StatusCode::UNAUTHORIZED, "Invalid approval credentials".to_string()).into_response());
+            }
+            LoginCheck::Locked => {
+                return Err((StatusCode::TOO_MANY_REQUESTS, "Approver is locked out, try again later".to_string()).into_response());
+            }
+        }
+    };
+
+    let adjustment = sqlx::query_as::<_, OrderAdjustment>(
+        r#"
+        INSERT INTO order_adjustments (order_id, kind, reason_code, note, subtotal, tax_total, total, staff_id, approved_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING *
+        "#
+    )
+    .bind(order_id)
+    .bind(kind)
+    .bind(&reason_code)
+    .bind(note)
+    .bind(-totals.subtotal.clone())
+    .bind(-totals.tax_total.clone())
+    .bind(-totals.total.clone())
+    .bind(staff.id)
+    .bind(approved_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record {}: {}", kind, e)).into_response())?;
+
+    let mut items = Vec::with_capacity(returned.len());
+    for (order_item_id, quantity, priced) in &returned {
+        let item = sqlx::query_as::<_, OrderAdjustmentItem>(
+            r#"
+            INSERT INTO order_adjustment_items (adjustment_id, order_item_id, quantity, line_total, tax_amount)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#
+        )
+        .bind(adjustment.id)
+        .bind(order_item_id)
+        .bind(quantity)
+        .bind(-priced.line_total.clone())
+        .bind(-priced.tax_amount.clone())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record {}: {}", kind, e)).into_response())?;
+        items.push(item);
+    }
+
+    let refunds = if kind == "refund" {
+        refund_payments(&mut tx, staff, order_id, adjustment.id, &totals.total, payload.tenders.as_deref()).await?
+    } else {
+        Vec::new()
+    };
+    let refunded = refunds.iter().fold(Money::zero(), |acc, p| &acc - &p.amount);
+
+    let amount_due = &amount_due - &totals.total;
+    let amount_paid = &amount_paid - &refunded;
+    let new_payment_status = payment_status(&amount_due, &amount_paid);
+
+    sqlx::query(
+        r#"
+        UPDATE orders
+        SET adjustment_total = adjustment_total - $1, amount_paid = $2, payment_status = $3, updated_at = now()
+        WHERE id = $4
+        "#
+    )
+    .bind(&totals.total)
+    .bind(&amount_paid)
+    .bind(new_payment_status)
+    .bind(order_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response())?;
+
+    let everything_returned = lines.iter().all(|line| {
+        let now_returned = returned.iter()
+            .find(|(id, _, _)| *id == line.id)
+            .map_or(0, |(_, quantity, _)| *quantity);
+        line.remaining_quantity() == now_returned
+    });
+    let new_status = match (everything_returned, status.as_str()) {
+        (false, _) => None,
+        (true, "completed") => Some("refunded"),
+        (true, _) => Some("cancelled"),
+    };
+    if let Some(new_status) = new_status {
+        transition_order_status(&mut tx, order_id, new_status, Some(staff))
+            .await
+            .map_err(IntoResponse::into_response)?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)).into_response())?;
+
+    let event = if kind == "void" { "order.voided" } else { "order.refunded" };
+    state.bcast.publish(event, json!({
+        "order_id": order_id,
+        "adjustment_id": adjustment.id,
+        "reason_code": adjustment.reason_code,
+        "total": adjustment.total,
+        "payment_status": new_payment_status,
+    }));
+    if let Some(new_status) = new_status {
+        state.bcast.publish("order.status_changed", json!({
+            "order_id": order_id,
+            "old_status": status,
+            "new_status": new_status,
+        }));
+    }
+    // Voided lines come off the station displays either way.
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            if new_status.is_some() {
+                broadcast_queue_inner(&state).await;
+            }
+            broadcast_station_tickets(&state, order_id).await;
+        }
+    });
+
+    Ok(Json(json!({
+        "open_drawer": refunds.iter().any(|p| p.tender == "cash"),
+        "status": new_status.unwrap_or(&status),
+        "amount_due": amount_due,
+        "amount_paid": amount_paid,
+        "payment_status": new_payment_status,
+        "adjustment": OrderAdjustmentDetailed { adjustment, items, refunds },
+    })))
+}
+
+/// Pays `amount` back through the order's original payments, as negative payment rows.
+async fn refund_payments(
+    conn: &mut sqlx::PgConnection,
+    staff: &AuthStaff,
+    order_id: i32,
+    adjustment_id: i32,
+    amount: &Money,
+    tenders: Option<&[RefundTender]>,
+) -> Result<Vec<Payment>, Response> {
+    let payments: Vec<RefundablePayment> = sqlx::query(
+        r#"
+        SELECT p.id, p.tender,
+               p.amount + COALESCE((SELECT SUM(r.amount) FROM payments r WHERE r.refund_of = p.id), 0) AS remaining
+        FROM payments p
+        WHERE p.order_id = $1 AND p.refund_of IS NULL
+        ORDER BY p.created_at, p.id
+        "#
+    )
+    .bind(order_id)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)).into_response())?
+    .into_iter()
+    .map(|row| RefundablePayment { id: row.get("id"), tender: row.get("tender"), remaining: row.get("remaining") })
+    .collect();
+
+    let allocations = match tenders {
+        Some(tenders) => {
+            let mut allocated = Money::zero();
+            for tender in tenders {
+                let payment = payments.iter()
+                    .find(|p| p.id == tender.payment_id)
+                    .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Payment {} is not part of this order", tender.payment_id)).into_response())?;
+                if tender.amount.is_negative() || tender.amount.is_zero() || tender.amount > payment.remaining {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("Payment {} can be refunded at most {}", payment.id, payment.remaining),
+                    ).into_response());
+                }
+                allocated += &tender.amount;
+            }
+            if &allocated != amount {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Refund tenders add up to {} but the refund is {}", allocated, amount),
+                ).into_response());
+            }
+            tenders.iter().map(|t| (t.payment_id, t.amount.clone())).collect()
+        }
+        None => allocate_refund(&payments, amount).ok_or_else(|| (
+            StatusCode::CONFLICT,
+            format!("Payments on this order can't cover a refund of {}", amount),
+        ).into_response())?,
+    };
+
+    let mut refunds = Vec::with_capacity(allocations.len());
+    for (payment_id, refund_amount) in allocations {
+        let tender = payments.iter().find(|p| p.id == payment_id).map(|p| p.tender.clone()).unwrap_or_default();
+        let refund = sqlx::query_as::<_, Payment>(
+            r#"
+            INSERT INTO payments (order_id, tender, amount, tendered, change_given, staff_id, refund_of, adjustment_id)
+            VALUES ($1, $2, $3, $3, 0, $4, $5, $6)
+            RETURNING *
+            "#
+        )
+        .bind(order_id)
+        .bind(tender)
+        .bind(-refund_amount)
+        .bind(staff.id)
+        .bind(payment_id)
+        .bind(adjustment_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to record refund: {}", e)).into_response())?;
+        refunds.push(refund);
+    }
+
+    Ok(refunds)
+}
+
+pub async fn list_order_adjustments(
+    State(state): State<AppState>,
+    Path(order_id): Path<i32>
+) -> Result<Json<Vec<OrderAdjustmentDetailed>>, (StatusCode, String)> {
+    let adjustments = sqlx::query_as::<_, OrderAdjustment>(
+        "SELECT * FROM order_adjustments WHERE order_id = $1 ORDER BY created_at, id"
+    )
+    .bind(order_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let ids: Vec<i32> = adjustments.iter().map(|a| a.id).collect();
+
+    let items = sqlx::query_as::<_, OrderAdjustmentItem>(
+        "SELECT * FROM order_adjustment_items WHERE adjustment_id = ANY($1) ORDER BY id"
+    )
+    .bind(&ids)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let refunds = sqlx::query_as::<_, Payment>(
+        "SELECT * FROM payments WHERE adjustment_id = ANY($1) ORDER BY id"
+    )
+    .bind(&ids)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let mut items_by_adjustment: HashMap<i32, Vec<OrderAdjustmentItem>> = HashMap::new();
+    for item in items {
+        items_by_adjustment.entry(item.adjustment_id).or_default().push(item);
+    }
+    let mut refunds_by_adjustment: HashMap<i32, Vec<Payment>> = HashMap::new();
+    for refund in refunds {
+        if let Some(adjustment_id) = refund.adjustment_id {
+            refunds_by_adjustment.entry(adjustment_id).or_default().push(refund);
+        }
+    }
+
+    Ok(Json(adjustments.into_iter().map(|adjustment| OrderAdjustmentDetailed {
+        items: items_by_adjustment.remove(&adjustment.id).unwrap_or_default(),
+        refunds: refunds_by_adjustment.remove(&adjustment.id).unwrap_or_default(),
+        adjustment,
+    }).collect()))
+}
+
+const STAFF_COLUMNS: &str = r#"
+    id, name, username, active,
+    pin_hash IS NOT NULL AS has_pin, password_hash IS NOT NULL AS has_password,
+    locked_until, last_login_at, created_at,
+    ARRAY(SELECT role FROM staff_roles WHERE staff_id = staff.id ORDER BY role) AS roles
+"#;
+
+pub async fn login(
+    State(state): State<AppState>,
+    Json(payload): Json<LoginRequest>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if payload.username.trim().is_empty() || (payload.pin.is_none() && payload.password.is_none()) {
+        return Err((StatusCode::BAD_REQUEST, "Username and a PIN or password are required".to_string()));
+    }
+
+    let check = auth::check_login(&state.pool, &payload.username, payload.pin.as_deref(), payload.password.as_deref())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let staff = match check {
+        LoginCheck::Ok(staff) => staff,
+        LoginCheck::Invalid => return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string())),
+        LoginCheck::Locked => return Err((StatusCode::TOO_MANY_REQUESTS, "Too many failed attempts, try again later".to_string())),
+    };
+
+    let (token, expires_at) = state.auth.issue(&staff)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to issue token: {}", e)))?;
+
+    Ok(Json(json!({
+        "token": token,
+        "expires_at": expires_at,
+        "permissions": staff.permissions(),
+        "staff": staff,
+    })))
+}
+
+pub async fn me(staff: AuthStaff) -> Json<serde_json::Value> {
+    Json(json!({ "permissions": staff.permissions(), "staff": staff }))
+}
+
+pub async fn list_roles() -> Json<Vec<serde_json::Value>> {
+    Json(ROLES.iter().map(|role| json!({
+        "role": role,
+        "permissions": role_permissions(role),
+    })).collect())
+}
+
+fn validate_roles(roles: &[String]) -> Result<Vec<String>, (StatusCode, String)> {
+    let mut valid: Vec<String> = Vec::new();
+    for role in roles {
+        let role = role.trim().to_lowercase();
+        if !ROLES.contains(&role.as_str()) {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown role '{}'. Allowed: {}", role, ROLES.join(", "))));
+        }
+        if !valid.contains(&role) {
+            valid.push(role);
+        }
+    }
+    Ok(valid)
+}
+
+async fn fetch_staff(conn: &mut sqlx::PgConnection, id: i32) -> Result<Staff, (StatusCode, String)> {
+    sqlx::query_as::<_, Staff>(&format!("SELECT {} FROM staff WHERE id = $1", STAFF_COLUMNS))
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Staff member not found".to_string()))
+}
+
+async fn replace_staff_roles(
+    conn: &mut sqlx::PgConnection,
+    staff_id: i32,
+    roles: &[String],
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query("DELETE FROM staff_roles WHERE staff_id = $1")
+        .bind(staff_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    sqlx::query("INSERT INTO staff_roles (staff_id, role) SELECT $1, unnest($2::text[])")
+        .bind(staff_id)
+        .bind(roles)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(())
+}
+
+/// Refuses changes that would leave nobody able to manage staff.
+async fn ensure_admin_remains(conn: &mut sqlx::PgConnection) -> Result<(), (StatusCode, String)> {
+    let has_admin: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM staff s JOIN staff_roles r ON r.staff_id = s.id
+            WHERE r.role = 'admin' AND s.active = true
+        )
+        "#
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    if !has_admin {
+        return Err((StatusCode::CONFLICT, "At least one active admin is required".to_string()));
+    }
+    Ok(())
+}
+
+pub async fn list_staff(
+    State(state): State<AppState>
+) -> Result<Json<Vec<Staff>>, (StatusCode, String)> {
+    let staff = sqlx::query_as::<_, Staff>(&format!("SELECT {} FROM staff ORDER BY name ASC, id ASC", STAFF_COLUMNS))
+        .fetch_all(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(staff))
+}
+
+/// Hashes whichever credentials were supplied after checking their format.
+fn hash_credentials(
+    pin: Option<&str>,
+    password: Option<&str>,
+) -> Result<(Option<String>, Option<String>), (StatusCode, String)> {
+    let hash = |secret: &str| auth::hash_secret(secret)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to hash credential: {}", e)));
+
+    let pin_hash = match pin {
+        Some(pin) => {
+            auth::validate_pin(pin)?;
+            Some(hash(pin)?)
+        }
+        None => None,
+    };
+    let password_hash = match password {
+        Some(password) => {
+            auth::validate_password(password)?;
+            Some(hash(password)?)
+        }
+        None => None,
+    };
+
+    Ok((pin_hash, password_hash))
+}
+
+pub async fn create_staff(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateStaff>
+) -> Result<Json<Staff>, (StatusCode, String)> {
+    let name = payload.name.trim();
+    let username = payload.username.trim();
+    if name.is_empty() || username.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name and username are required".to_string()));
+    }
+    if payload.pin.is_none() && payload.password.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "A PIN or password is required".to_string()));
+    }
+
+    let mut roles = validate_roles(&payload.roles)?;
+    if roles.is_empty() {
+        roles.push("cashier".to_string());
+    }
+
+    let (pin_hash, password_hash) = hash_credentials(payload.pin.as_deref(), payload.password.as_deref())?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    let id: i32 = sqlx::query_scalar(
+        "INSERT INTO staff (name, username, pin_hash, password_hash) VALUES ($1, $2, $3, $4) RETURNING id"
+    )
+    .bind(name)
+    .bind(username)
+    .bind(pin_hash)
+    .bind(password_hash)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db) if db.is_unique_violation() => {
+            (StatusCode::CONFLICT, format!("Username '{}' is already taken", username))
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+    })?;
+
+    replace_staff_roles(&mut tx, id, &roles).await?;
+    let staff = fetch_staff(&mut tx, id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(staff))
+}
+
+pub async fn update_staff(
+    State(state): State<AppState>,
+    current: AuthStaff,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateStaff>
+) -> Result<Json<Staff>, (StatusCode, String)> {
+    let name = payload.name.as_deref().map(str::trim);
+    if name.is_some_and(str::is_empty) {
+        return Err((StatusCode::BAD_REQUEST, "Name cannot be empty".to_string()));
+    }
+    if id == current.id && payload.active == Some(false) {
+        return Err((StatusCode::BAD_REQUEST, "You cannot deactivate your own account".to_string()));
+    }
+
+    let (pin_hash, password_hash) = hash_credentials(payload.pin.as_deref(), payload.password.as_deref())?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    // Resetting a credential also clears any lockout.
+    let staff = sqlx::query_as::<_, Staff>(&format!(
+        r#"
+        UPDATE staff
+        SET name = COALESCE($1, name),
+            pin_hash = COALESCE($2, pin_hash),
+            password_hash = COALESCE($3, password_hash),
+            active = COALESCE($4, active),
+            failed_logins = CASE WHEN $2 IS NULL AND $3 IS NULL THEN failed_logins ELSE 0 END,
+            locked_until = CASE WHEN $2 IS NULL AND $3 IS NULL THEN locked_until ELSE NULL END
+        WHERE id = $5
+        RETURNING {}
+        "#,
+        STAFF_COLUMNS
+    ))
+    .bind(name)
+    .bind(pin_hash)
+    .bind(password_hash)
+    .bind(payload.active)
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Staff member not found".to_string()))?;
+
+    if payload.active == Some(false) {
+        ensure_admin_remains(&mut tx).await?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(staff))
+}
+
+pub async fn set_staff_roles(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Json(payload): Json<UpdateStaffRoles>
+) -> Result<Json<Staff>, (StatusCode, String)> {
+    let roles = validate_roles(&payload.roles)?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    fetch_staff(&mut tx, id).await?;
+    replace_staff_roles(&mut tx, id, &roles).await?;
+    ensure_admin_remains(&mut tx).await?;
+    let staff = fetch_staff(&mut tx, id).await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok(Json(staff))
+}
+
+#[derive(serde::Deserialize)]
+pub struct WsQuery {
+    /// Comma separated topics to start with; every topic the client may see when omitted.
+    pub topics: Option<String>,
+    /// Staff session token, needed for everything but the queue and menu. It can also be
+    /// sent later in an `auth` message.
+    pub token: Option<String>,
+    /// Epoch and last seen sequence from a previous connection, to resume from.
+    pub epoch: Option<String>,
+    pub last_seq: Option<u64>,
+}
+
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(q): Query<WsQuery>
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let signed_in = match q.token.as_deref() {
+        Some(token) => {
+            auth::authenticate(&state, token).await?;
+            true
+        }
+        None => false,
+    };
+    let topics: HashSet<String> = match q.topics {
+        Some(list) => list.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect(),
+        None if signed_in => TOPICS.iter().map(|t| t.to_string()).collect(),
+        None => PUBLIC_TOPICS.iter().map(|t| t.to_string()).collect(),
+    };
+    let resume_from = q.epoch.zip(q.last_seq);
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, topics, signed_in, resume_from)))
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    state: AppState,
+    mut topics: HashSet<String>,
+    mut signed_in: bool,
+    resume_from: Option<(String, u64)>,
+) {
+    // Dropped before the replay or snapshot below, so neither can include them.
+    let unauthorized = withhold_private(&mut topics, signed_in);
+    // Subscribe before anything else so no event falls between a replay and the live feed;
+    // duplicates are dropped by comparing against `last_sent`.
+    let mut rx = state.bcast.subscribe();
+    let mut last_sent = state.bcast.current_seq();
+
+    let hello = json!({
+        "type": "hello",
+        "v": PROTOCOL_VERSION,
+        "epoch": state.bcast.epoch(),
+        "seq": last_sent,
+        "topics": sorted(&topics),
+        "unauthorized": unauthorized,
+    });
+    if socket.send(Message::Text(hello.to_string())).await.is_err() {
+        return;
+    }
+
+    let started = match resume_from {
+        Some((epoch, last_seq)) => resume(&mut socket, &state, &topics, &epoch, last_seq, &mut last_sent).await,
+        None if topics.contains("queue") => send_queue_snapshot(&mut socket, &state).await,
+        None => Ok(()),
+    };
+    if started.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            msg = socket.recv() => {
+                match msg {
+                    Some(Ok(Message::Text(t))) => {
+                        let reply = match serde_json::from_str::<ClientMessage>(&t) {
+                            Ok(ClientMessage::Subscribe { topics: requested }) => {
+                                let (known, unknown): (Vec<String>, Vec<String>) = requested
+                                    .into_iter()
+                                    .partition(|t| is_known_topic(t));
+                                let mut known: HashSet<String> = known.into_iter().collect();
+                                let unauthorized = withhold_private(&mut known, signed_in);
+                                let newly_queue = known.contains("queue") && !topics.contains("queue");
+                                topics.extend(known);
+                                if newly_queue && send_queue_snapshot(&mut socket, &state).await.is_err() {
+                                    break;
+                                }
+                                Some(json!({
+                                    "type": "subscribed",
+                                    "topics": sorted(&topics),
+                                    "unknown": unknown,
+                                    "unauthorized": unauthorized,
+                                }))
+                            }
+                            Ok(ClientMessage::Auth { token }) => match auth::authenticate(&state, &token).await {
+                                Ok(staff) => {
+                                    signed_in = true;
+                                    Some(json!({ "type": "authenticated", "staff_id": staff.id, "name": staff.name }))
+                                }
+                                Err((_, message)) => Some(json!({ "type": "error", "message": message })),
+                            },
+                            Ok(ClientMessage::Unsubscribe { topics: requested }) => {
+                                for t in requested.iter() {
+                                    topics.remove(t);
+                                }
+                                Some(json!({ "type": "subscribed", "topics": sorted(&topics) }))
+                            }
+                            Ok(ClientMessage::Resume { epoch, last_seq }) => {
+                                if resume(&mut socket, &state, &topics, &epoch, last_seq, &mut last_sent).await.is_err() {
+                                    break;
+                                }
+                                None
+                            }
+                            Ok(ClientMessage::Ping) => Some(json!({ "type": "pong" })),
+                            Err(e) => Some(json!({ "type": "error", "message": format!("Invalid message: {}", e) })),
+                        };
+                        if let Some(reply) = reply {
+                            if socket.send(Message::Text(reply.to_string())).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Ping(data))) => {
+                        let _ = socket.send(Message::Pong(data)).await;
+                    }
+                    Some(Ok(Message::Close(_))) | None => {
+                        let _ = socket.close().await;
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("WebSocket error: {}", e);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            res = rx.recv() => {
+                match res {
+                    Ok(event) => {
+                        if event.seq <= last_sent {
+                            continue;
+                        }
+                        if send_event(&mut socket, &topics, &event, &mut last_sent).await.is_err() {
+                            let _ = socket.close().await;
+                            break;
+                        }
+                    }
+                    // This socket fell behind the broadcast channel; catch up from the replay buffer.
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        let epoch = state.bcast.epoch().to_string();
+                        if resume(&mut socket, &state, &topics, &epoch, last_sent, &mut last_sent).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+}
+
+async fn send_event(
+    socket: &mut WebSocket,
+    topics: &HashSet<String>,
+    event: &Event,
+    last_sent: &mut u64,
+) -> Result<(), axum::Error> {
+    *last_sent = event.seq;
+    if !topics.contains(&event.topic) {
+        return Ok(());
+    }
+    match serde_json::to_string(event) {
+        Ok(text) => socket.send(Message::Text(text)).await,
+        Err(_) => Ok(()),
+    }
+}
+
+/// Replays what the client missed since `last_seq`, or sends a full snapshot when the
+/// gap can no longer be filled.
+async fn resume(
+    socket: &mut WebSocket,
+    state: &AppState,
+    topics: &HashSet<String>,
+    epoch: &str,
+    last_seq: u64,
+    last_sent: &mut u64,
+) -> Result<(), axum::Error> {
+    match state.bcast.replay_since(epoch, last_seq) {
+        Replay::Events(events) => {
+            for event in events.iter() {
+                send_event(socket, topics, event, last_sent).await?;
+            }
+            Ok(())
+        }
+        Replay::Resync(reason) => send_full_snapshot(socket, state, topics, reason, last_sent).await,
+    }
+}
+
+/// Sends the current state of every subscribed topic. Its `seq` is the last event the
+/// snapshot already covers, so the client can resume from it later.
+///
+/// Clients replace their state with the snapshot, so a section that fails to load fails
+/// the whole snapshot rather than going out empty; the socket closes and the client
+/// reconnects for another try.
+async fn send_full_snapshot(
+    socket: &mut WebSocket,
+    state: &AppState,
+    topics: &HashSet<String>,
+    reason: &str,
+    last_sent: &mut u64,
+) -> Result<(), axum::Error> {
+    // Read the sequence first: anything published while loading is sent live afterwards.
+    let seq = state.bcast.current_seq();
+
+    let failed = |e: sqlx::Error| {
+        eprintln!("Failed to load snapshot: {}", e);
+        axum::Error::new(e)
+    };
+
+    let mut payload = json!({ "reason": reason });
+    if topics.contains("queue") {
+        payload["queue"] = json!(fetch_queue(state).await.map_err(failed)?);
+    }
+    if topics.contains("order") {
+        let orders = sqlx::query_as::<_, Order>(
+            "SELECT * FROM orders WHERE status IN ('pending', 'preparing', 'ready') ORDER BY created_at ASC"
+        )
+        .fetch_all(&state.pool)
+        .await
+        .map_err(failed)?;
+        payload["orders"] = json!(orders);
+    }
+    if topics.contains("menu") {
+        let menu = sqlx::query_as::<_, MenuItem>("SELECT * FROM menu_items WHERE deleted_at IS NULL ORDER BY id ASC")
+            .fetch_all(&state.pool)
+            .await
+            .map_err(failed)?;
+        payload["menu"] = json!(menu);
+    }
+    if topics.contains("print") {
+        let waiting = sqlx::query_as::<_, PrintJob>("SELECT * FROM print_jobs WHERE status = 'queued' ORDER BY id ASC")
+            .fetch_all(&state.pool)
+            .await
+            .map_err(failed)?;
+        payload["print_jobs"] = json!(waiting.iter().map(print_event).collect::<Vec<_>>());
+    }
+    let stations: Vec<&str> = topics.iter().filter_map(|t| t.strip_prefix(kitchen::STATION_TOPIC_PREFIX)).collect();
+    if !stations.is_empty() {
+        let mut tickets = serde_json::Map::new();
+        let mut conn = state.pool.acquire().await.map_err(failed)?;
+        for code in stations {
+            let open = kitchen::load_tickets(&mut conn, Some(code), None).await.map_err(failed)?;
+            tickets.insert(code.to_string(), json!(open));
+        }
+        payload["stations"] = serde_json::Value::Object(tickets);
+    }
+
+    let event = Event {
+        v: PROTOCOL_VERSION,
+        seq,
+        kind: "sync.snapshot".to_string(),
+        topic: "sync".to_string(),
+        at: Utc::now(),
+        payload,
+    };
+    *last_sent = (*last_sent).max(seq);
+    let text = serde_json::to_string(&event).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
+
+/// Sends the current queue straight to one socket, outside the broadcast sequence.
+async fn send_queue_snapshot(socket: &mut WebSocket, state: &AppState) -> Result<(), axum::Error> {
+    let queue = match fetch_queue(state).await {
+        Ok(queue) => queue,
+        Err(e) => {
+            eprintln!("Failed to load queue snapshot: {}", e);
+            return Ok(());
+        }
+    };
+    let event = Event {
+        v: PROTOCOL_VERSION,
+        seq: 0,
+        kind: "queue.snapshot".to_string(),
+        topic: "queue".to_string(),
+        at: Utc::now(),
+        payload: json!({ "tokens": queue }),
+    };
+    let text = serde_json::to_string(&event).unwrap_or_default();
+    socket.send(Message::Text(text)).await
+}
+
+/// Removes the topics that need a staff session from a socket that has none, and
+/// returns them so the client can be told.
+fn withhold_private(topics: &mut HashSet<String>, signed_in: bool) -> Vec<String> {
+    if signed_in {
+        return Vec::new();
+    }
+    let mut withheld: Vec<String> = topics.iter().filter(|t| !is_public_topic(t)).cloned().collect();
+    topics.retain(|t| is_public_topic(t));
+    withheld.sort();
+    withheld
+}
+
+fn sorted(topics: &HashSet<String>) -> Vec<&String> {
+    let mut list: Vec<&String> = topics.iter().collect();
+    list.sort();
+    list
+}
+
+/// Entities a kiosk or till needs to show the menu; public.
+const MENU_ENTITIES: &[&str] = &["menu_item", "menu_category", "modifier_group", "modifier_option", "tax_rate", "kitchen_station"];
+const ORDER_ENTITIES: &[&str] = &["order"];
+const SYNC_PAGE_SIZE: i64 = 500;
+const SYNC_MAX_PAGE_SIZE: i64 = 2000;
+
+#[derive(serde::Deserialize)]
+pub struct SyncQuery {
+    /// `next_cursor` from the previous page; 0 (the default) pulls everything.
+    pub cursor: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl SyncQuery {
+    /// The cursor to pull after and the page size, clamped to what the server allows.
+    fn page(&self) -> Result<(i64, i64), String> {
+        let cursor = self.cursor.unwrap_or(0);
+        if cursor < 0 {
+            return Err("cursor must not be negative".to_string());
+        }
+        Ok((cursor, self.limit.unwrap_or(SYNC_PAGE_SIZE).clamp(1, SYNC_MAX_PAGE_SIZE)))
+    }
+}
+
+pub async fn sync_menu(
+    State(state): State<AppState>,
+    Query(q): Query<SyncQuery>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    pull_changes(&state, MENU_ENTITIES, q).await.map(Json)
+}
+
+pub async fn sync_orders(
+    State(state): State<AppState>,
+    Query(q): Query<SyncQuery>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    pull_changes(&state, ORDER_ENTITIES, q).await.map(Json)
+}
+
+/// One page of the change log after `cursor`. Only the latest change to each row is sent,
+/// with the row as it is now, so a client that fell far behind doesn't replay history.
+async fn pull_changes(
+    state: &AppState,
+    entities: &[&str],
+    q: SyncQuery,
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    let (cursor, limit) = q.page().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT c.change_id, c.entity, c.entity_id, c.op
+        FROM change_log c
+        WHERE c.change_id > $1
+          AND c.entity = ANY($2)
+          AND NOT EXISTS (
+              SELECT 1 FROM change_log n
+              WHERE n.entity = c.entity AND n.entity_id = c.entity_id AND n.change_id > c.change_id
+          )
+        ORDER BY c.change_id
+        LIMIT $3
+        "#
+    )
+    .bind(cursor)
+    .bind(entities)
+    .bind(limit + 1)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let has_more = rows.len() as i64 > limit;
+    let mut changes: Vec<SyncChange> = rows.iter().take(limit as usize).map(|row| SyncChange {
+        change_id: row.get("change_id"),
+        entity: row.get("entity"),
+        id: row.get("entity_id"),
+        op: row.get("op"),
+        data: None,
+    }).collect();
+
+    for entity in entities {
+        let ids: Vec<i32> = changes.iter()
+            .filter(|c| c.entity == *entity && c.op == "upsert")
+            .map(|c| c.id)
+            .collect();
+        if ids.is_empty() {
+            continue;
+        }
+
+        let rows = load_sync_rows(state, entity, &ids).await?;
+        attach_rows(&mut changes, entity, rows);
+    }
+
+    let next_cursor = changes.last().map_or(cursor, |c| c.change_id);
+
+    Ok(json!({
+        "changes": changes,
+        "next_cursor": next_cursor,
+        "has_more": has_more,
+    }))
+}
+
+/// Fills in the current row for each upsert of `entity`.
+fn attach_rows(changes: &mut [SyncChange], entity: &str, mut rows: HashMap<i32, serde_json::Value>) {
+    for change in changes.iter_mut().filter(|c| c.entity == entity && c.op == "upsert") {
+        match rows.remove(&change.id) {
+            Some(data) => change.data = Some(data),
+            // Deleted after this change was logged; its delete comes in a later page.
+            None => change.op = "delete".to_string(),
+        }
+    }
+}
+
+async fn load_sync_rows(
+    state: &AppState,
+    entity: &str,
+    ids: &[i32],
+) -> Result<HashMap<i32, serde_json::Value>, (StatusCode, String)> {
+    fn keyed<T: serde::Serialize>(rows: Vec<T>, id: impl Fn(&T) -> i32) -> HashMap<i32, serde_json::Value> {
+        rows.into_iter().map(|row| (id(&row), json!(row))).collect()
+    }
+
+    let db_err = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e));
+
+    let rows = match entity {
+        "menu_item" => keyed(
+            sqlx::query_as::<_, MenuItem>("SELECT * FROM menu_items WHERE id = ANY($1) AND deleted_at IS NULL")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        "menu_category" => keyed(
+            sqlx::query_as::<_, MenuCategory>("SELECT * FROM menu_categories WHERE id = ANY($1)")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        "modifier_group" => keyed(
+            sqlx::query_as::<_, ModifierGroup>("SELECT * FROM modifier_groups WHERE id = ANY($1)")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        "modifier_option" => keyed(
+            sqlx::query_as::<_, ModifierOption>("SELECT * FROM modifier_options WHERE id = ANY($1)")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        "tax_rate" => keyed(
+            sqlx::query_as::<_, TaxRate>("SELECT * FROM tax_rates WHERE id = ANY($1)")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        "kitchen_station" => keyed(
+            sqlx::query_as::<_, KitchenStation>("SELECT * FROM kitchen_stations WHERE id = ANY($1)")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        "order" => keyed(
+            sqlx::query_as::<_, Order>("SELECT * FROM orders WHERE id = ANY($1)")
+                .bind(ids).fetch_all(&state.pool).await.map_err(db_err)?,
+            |r| r.id,
+        ),
+        _ => HashMap::new(),
+    };
+
+    Ok(rows)
+}
+
+/// Applies changes a client made offline, in order, and reports each one's outcome:
+///
+/// - `order.create` is keyed by `client_order_id`; a resend reports `duplicate` with the
+///   original order.
+/// - `order.status` follows the same transition rules as the status endpoint. Moving an
+///   order to the status it already has is a `duplicate`; a move the server's state no
+///   longer allows (say the order was completed elsewhere) is a `conflict`.
+/// - `menu_item.availability` is last-writer-wins, unless the item changed on the server
+///   after the client's `base_change_id`, in which case the server copy wins (`conflict`).
+///
+/// Conflicts carry the server's current row so the client can take it. Failures don't stop
+/// later mutations; the response includes a `cursor` to pull from afterwards.
+pub async fn sync_push(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Json(payload): Json<SyncPush>
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let mut results = Vec::with_capacity(payload.mutations.len());
+
+    for mutation in payload.mutations {
+        let result = match mutation {
+            SyncMutation::CreateOrder(order) => push_create_order(&state, order).await.map_err(Rejected::from),
+            SyncMutation::OrderStatus { order_id, status } => push_order_status(&state, &staff, order_id, status).await,
+            SyncMutation::MenuItemAvailability { id, available, base_change_id } => {
+                push_availability(&state, &staff, id, available, base_change_id).await
+            }
+        };
+        results.push(result.unwrap_or_else(Rejected::into_result));
+    }
+
+    let cursor: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(change_id), 0) FROM change_log")
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(json!({ "results": results, "cursor": cursor })))
+}
+
+/// Why `/sync/push` refused one mutation.
+enum Rejected {
+    Error(StatusCode, String),
+    /// Reported with the same body as a 403 from the matching endpoint.
+    Forbidden(Forbidden),
+}
+
+impl From<(StatusCode, String)> for Rejected {
+    fn from((code, message): (StatusCode, String)) -> Self {
+        Rejected::Error(code, message)
+    }
+}
+
+impl From<Forbidden> for Rejected {
+    fn from(forbidden: Forbidden) -> Self {
+        Rejected::Forbidden(forbidden)
+    }
+}
+
+impl Rejected {
+    fn into_result(self) -> serde_json::Value {
+        match self {
+            Rejected::Error(code, message) => json!({ "result": "rejected", "status": code.as_u16(), "error": message }),
+            Rejected::Forbidden(forbidden) => {
+                let mut result = forbidden.body();
+                result["result"] = json!("rejected");
+                result["status"] = json!(StatusCode::FORBIDDEN.as_u16());
+                result
+            }
+        }
+    }
+}
+
+async fn push_create_order(
+    state: &AppState,
+    order: CreateOrder,
+) -> Result<serde_json::Value, (StatusCode, String)> {
+    if order.client_order_id.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "client_order_id is required when pushing orders".to_string()));
+    }
+    let key = idempotency_key(&HeaderMap::new(), &order)?;
+    let created = place_order(state, &order, key).await?;
+    let result = if created["replayed"] == json!(true) { "duplicate" } else { "applied" };
+
+    Ok(json!({ "result": result, "order": created }))
+}
+
+async fn push_order_status(
+    state: &AppState,
+    staff: &AuthStaff,
+    order_id: i32,
+    status: String,
+) -> Result<serde_json::Value, Rejected> {
+    let new_status = status.trim().to_lowercase();
+    if !ALLOWED_STATUSES.contains(&new_status.as_str()) || new_status == "cancelled" || new_status == "refunded" {
+        return Err(Rejected::Error(StatusCode::BAD_REQUEST, format!("Status '{}' can't be pushed", new_status)));
+    }
+    staff.require(status_permission(&new_status))?;
+
+    match apply_order_status(state, order_id, &new_status, staff).await {
+        Ok(old_status) => Ok(json!({ "result": "applied", "order_id": order_id, "old_status": old_status })),
+        Err((StatusCode::CONFLICT, message)) => {
+            let order = sqlx::query_as::<_, Order>("SELECT * FROM orders WHERE id = $1")
+                .bind(order_id)
+                .fetch_one(&state.pool)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+            let result = if order.status == new_status { "duplicate" } else { "conflict" };
+            Ok(json!({ "result": result, "error": message, "current": order }))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn push_availability(
+    state: &AppState,
+    staff: &AuthStaff,
+    id: i32,
+    available: bool,
+    base_change_id: Option<i64>,
+) -> Result<serde_json::Value, Rejected> {
+    staff.require(Permission::MenuEdit)?;
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    // The row lock means any competing change has committed, and has its change id, by now.
+    let current = sqlx::query_as::<_, MenuItem>("SELECT * FROM menu_items WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Menu item not found".to_string()))?;
+
+    if let Some(base) = base_change_id {
+        let changed_since: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM change_log WHERE entity = 'menu_item' AND entity_id = $1 AND change_id > $2)"
         )
+        .bind(id)
+        .bind(base)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+        if changed_since {
+            return Ok(json!({ "result": "conflict", "error": "Menu item changed on the server", "current": current }));
+        }
+    }
+
+    let item = sqlx::query_as::<_, MenuItem>("UPDATE menu_items SET available = $1 WHERE id = $2 RETURNING *")
+        .bind(available)
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    broadcast_menu_change(state, "availability_changed", &item);
+
+    Ok(json!({ "result": "applied", "item": item }))
+}
+
+/// Where the open period starts and what float it opened with: the last closed day.
+async fn last_close(conn: &mut sqlx::PgConnection) -> Result<(Option<DateTime<Utc>>, Money), (StatusCode, String)> {
+    let last: Option<(DateTime<Utc>, Money)> = sqlx::query_as(
+        "SELECT period_end, float_left FROM z_reports ORDER BY id DESC LIMIT 1"
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(match last {
+        Some((period_end, float_left)) => (Some(period_end), float_left),
+        None => (None, Money::zero()),
+    })
+}
+
+/// Takings since the last Z report. Nothing is stored.
+pub async fn x_report(State(state): State<AppState>) -> Result<Json<SalesReport>, (StatusCode, String)> {
+    let mut conn = state.pool.acquire()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let (period_start, opening_float) = last_close(&mut conn).await?;
+    let report = build_report(&mut conn, &state.currency, None, period_start, Utc::now(), opening_float)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(report))
+}
+
+/// Closes the business day: everything not yet on a Z report goes on this one, and the
+/// report is stored as it stands.
+pub async fn close_day(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    payload: Option<Json<CloseDay>>,
+) -> Result<(StatusCode, Json<ZReport>), (StatusCode, String)> {
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+    for value in [&payload.counted_cash, &payload.float_left].into_iter().flatten() {
+        if value.is_negative() {
+            return Err((StatusCode::BAD_REQUEST, "Cash amounts cannot be negative".to_string()));
+        }
+    }
+    let float_left = payload.float_left.unwrap_or_default();
+    if payload.counted_cash.as_ref().is_some_and(|counted| &float_left > counted) {
+        return Err((StatusCode::BAD_REQUEST, "float_left cannot be more than the cash counted".to_string()));
+    }
+
+    let mut tx = state.pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction error: {}", e)))?;
+
+    // One close at a time, so two tills can't both claim the same rows.
+    sqlx::query("LOCK TABLE z_reports IN EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let (period_start, opening_float) = last_close(&mut tx).await?;
+
+    let (id, period_end, business_date): (i32, DateTime<Utc>, NaiveDate) = sqlx::query_as(
+        r#"
+        SELECT nextval(pg_get_serial_sequence('z_reports', 'id'))::int,
+               now(),
+               ((now() AT TIME ZONE $1) - make_interval(hours => $2))::date
+        "#
+    )
+    .bind(&state.tokens.timezone)
+    .bind(state.tokens.cutoff_hour as i32)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    // Rows still being written commit after this and go on the next report.
+    for table in ["orders", "payments", "order_adjustments", "drawer_openings"] {
+        sqlx::query(&format!("UPDATE {} SET z_report_id = $1 WHERE z_report_id IS NULL", table))
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    }
+
+    let mut report = build_report(&mut tx, &state.currency, Some(id), period_start, period_end, opening_float.clone())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if let Some(counted) = payload.counted_cash.clone() {
+        report.count_drawer(counted);
+    }
+
+    let closed = sqlx::query_as::<_, ZReport>(
+        r#"
+        INSERT INTO z_reports
+            (id, business_date, period_start, period_end, opening_float, counted_cash, float_left, closed_by, report)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(business_date)
+    .bind(period_start)
+    .bind(period_end)
+    .bind(&opening_float)
+    .bind(&payload.counted_cash)
+    .bind(&float_left)
+    .bind(staff.id)
+    .bind(json!(report))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Transaction commit failed: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(closed)))
+}
+
+pub async fn list_z_reports(State(state): State<AppState>) -> Result<Json<Vec<ZReport>>, (StatusCode, String)> {
+    let reports = sqlx::query_as::<_, ZReport>("SELECT * FROM z_reports ORDER BY id DESC")
         .fetch_all(&state.pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
 
-        let items_detailed: Vec<OrderItemDetailed> = items.into_iter().map(|r| {
-            OrderItemDetailed {
-                id: r.id,
-                order_id: r.order_id,
-                menu_item_id: r.menu_item_id,
-                quantity: r.quantity,
-                menu_name: r.menu_name,
-                menu_price: r.menu_price, // Now this is already Decimal from the database
-                menu_image: r.menu_image,
-            }
-        }).collect();
+    Ok(Json(reports))
+}
+
+pub async fn get_z_report(
+    State(state): State<AppState>,
+    Path(id): Path<i32>
+) -> Result<Json<ZReport>, (StatusCode, String)> {
+    let report = sqlx::query_as::<_, ZReport>("SELECT * FROM z_reports WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Z report not found".to_string()))?;
+
+    Ok(Json(report))
+}
+
+async fn analytics_range(state: &AppState, query: &AnalyticsQuery) -> Result<DateRange, (StatusCode, String)> {
+    let to = match query.to {
+        Some(to) => to,
+        None => analytics::business_today(&state.pool, &state.tokens)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?,
+    };
+    DateRange::ending(query.from, to).map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+/// JSON by default; `?format=csv` returns just the rows as a download.
+fn analytics_response<T: analytics::CsvRow>(
+    state: &AppState,
+    name: &str,
+    range: DateRange,
+    format: Option<&str>,
+    rows: Vec<T>,
+) -> Result<Response, (StatusCode, String)> {
+    match format.unwrap_or("json") {
+        "json" => Ok(Json(json!({
+            "from": range.from,
+            "to": range.to,
+            "currency": &*state.currency,
+            "rows": rows,
+        })).into_response()),
+        "csv" => {
+            let body = analytics::to_csv(&rows)
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("CSV error: {}", e)))?;
+            let disposition = format!("attachment; filename=\"{}_{}_{}.csv\"", name, range.from, range.to);
+            Ok((
+                [(header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+                body,
+            ).into_response())
+        }
+        _ => Err((StatusCode::BAD_REQUEST, "format must be json or csv".to_string())),
+    }
+}
+
+pub async fn sales_by_item(
+    State(state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>
+) -> Result<Response, (StatusCode, String)> {
+    let by_revenue = match query.sort.as_deref().unwrap_or("quantity") {
+        "quantity" => false,
+        "revenue" => true,
+        _ => return Err((StatusCode::BAD_REQUEST, "sort must be quantity or revenue".to_string())),
+    };
+    let range = analytics_range(&state, &query).await?;
+    let rows = analytics::item_sales(&state.pool, &state.tokens, range, by_revenue)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    analytics_response(&state, "items", range, query.format.as_deref(), rows)
+}
+
+pub async fn sales_by_hour(
+    State(state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>
+) -> Result<Response, (StatusCode, String)> {
+    let range = analytics_range(&state, &query).await?;
+    let rows = analytics::hour_sales(&state.pool, &state.tokens, range)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    analytics_response(&state, "hours", range, query.format.as_deref(), rows)
+}
+
+pub async fn sales_by_weekday(
+    State(state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>
+) -> Result<Response, (StatusCode, String)> {
+    let range = analytics_range(&state, &query).await?;
+    let rows = analytics::weekday_sales(&state.pool, &state.tokens, range)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    analytics_response(&state, "weekdays", range, query.format.as_deref(), rows)
+}
 
-        result.push(OrderDetailed { order, items: items_detailed });
-    }
+pub async fn sales_heatmap(
+    State(state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>
+) -> Result<Response, (StatusCode, String)> {
+    let range = analytics_range(&state, &query).await?;
+    let rows = analytics::sales_heatmap(&state.pool, &state.tokens, range)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
 
-    Ok(Json(result))
+    analytics_response(&state, "heatmap", range, query.format.as_deref(), rows)
 }
 
-pub async fn list_queue(State(state): State<AppState>) -> Result<Json<Vec<QueueToken>>, (StatusCode, String)> {
-    let q = sqlx::query_as::<_, QueueToken>("SELECT * FROM queue_tokens ORDER BY id ASC")
-        .fetch_all(&state.pool)
+pub async fn sales_by_source(
+    State(state): State<AppState>,
+    Query(query): Query<AnalyticsQuery>
+) -> Result<Response, (StatusCode, String)> {
+    let range = analytics_range(&state, &query).await?;
+    let rows = analytics::source_sales(&state.pool, &state.tokens, range)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
-    
-    Ok(Json(q))
+
+    analytics_response(&state, "sources", range, query.format.as_deref(), rows)
 }
 
-pub async fn broadcast_queue(State(state): State<AppState>) -> Json<serde_json::Value> {
-    broadcast_queue_inner(&state).await;
-    Json(json!({"status": "queue broadcast sent"}))
+/// What WebSocket clients hear about a job. The payload stays behind the API; tills
+/// claim the job to get it.
+fn print_event(job: &PrintJob) -> serde_json::Value {
+    json!({
+        "id": job.id,
+        "printer": job.printer,
+        "kind": job.kind,
+        "order_id": job.order_id,
+        "station_id": job.station_id,
+        "status": job.status,
+        "attempts": job.attempts,
+        "last_error": job.last_error,
+    })
 }
 
-async fn broadcast_queue_inner(state: &AppState) {
-    if let Ok(queue) = sqlx::query_as::<_, QueueToken>("SELECT * FROM queue_tokens ORDER BY id ASC")
-        .fetch_all(&state.pool)
-        .await
-    {
-        if let Ok(msg) = serde_json::to_string(&queue) {
-            let _ = state.bcast.send(msg);
-        }
-    }
+/// `print.printed`, `print.failed`, or `print.queued` for a job going round again.
+fn publish_print_job(state: &AppState, job: &PrintJob) {
+    let kind = match job.status.as_str() {
+        "printed" => "print.printed",
+        "failed" => "print.failed",
+        _ => "print.queued",
+    };
+    state.bcast.publish(kind, print_event(job));
 }
 
-pub async fn update_order_status(
+/// Queues a receipt for an order on the `receipt` printer, or the one named in the body.
+/// Whichever till drives that printer prints it.
+pub async fn print_receipt(
     State(state): State<AppState>,
-    Path(id): Path<i32>,
-    Json(payload): Json<UpdateOrderStatus>
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let new_status = payload.status.trim().to_lowercase();
-
-    if !ALLOWED_STATUSES.contains(&new_status.as_str()) {
-        return Err((StatusCode::BAD_REQUEST, format!("Invalid status. Allowed: {}", ALLOWED_STATUSES.join(", "))));
+    staff: AuthStaff,
+    Path(order_id): Path<i32>,
+    payload: Option<Json<QueuePrint>>
+) -> Result<(StatusCode, Json<PrintJob>), (StatusCode, String)> {
+    let Json(payload) = payload.unwrap_or_default();
+    let printer = payload.printer.as_deref().map(str::trim).unwrap_or("receipt");
+    if !valid_printer_name(printer) {
+        return Err((StatusCode::BAD_REQUEST, "Printer names use lowercase letters, digits, '-' and '_'".to_string()));
     }
 
-    let result = sqlx::query("UPDATE orders SET status = $1, updated_at = now() WHERE id = $2")
-        .bind(&new_status)
-        .bind(id)
-        .execute(&state.pool)
+    let mut conn = state.pool.acquire()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    let receipt = printing::receipt_job(&mut conn, order_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+        .ok_or((StatusCode::NOT_FOUND, "Order not found".to_string()))?;
+    let job = printing::NewPrintJob {
+        printer,
+        kind: "receipt",
+        order_id: Some(order_id),
+        station_id: None,
+        payload: json!(receipt),
+        requested_by: Some(staff.id),
+    };
+    let job = printing::enqueue(&mut conn, &state.printing, job)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to queue print job: {}", e)))?;
+
+    publish_print_job(&state, &job);
+    Ok((StatusCode::CREATED, Json(job)))
+}
+
+/// Prints an order's kitchen tickets again, marked as reprints: at every station with a
+/// printer, or just the one named in the body.
+pub async fn reprint_kitchen_tickets(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(order_id): Path<i32>,
+    payload: Option<Json<ReprintKitchenTickets>>
+) -> Result<(StatusCode, Json<Vec<PrintJob>>), (StatusCode, String)> {
+    let Json(payload) = payload.unwrap_or_default();
+    let mut conn = state.pool.acquire()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
 
-    if result.rows_affected() == 0 {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM orders WHERE id = $1)")
+        .bind(order_id)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+    if !exists {
         return Err((StatusCode::NOT_FOUND, "Order not found".to_string()));
     }
 
-    match new_status.as_str() {
-        "ready" => {
-            sqlx::query("UPDATE queue_tokens SET status = 'ready' WHERE order_id = $1")
-                .bind(id)
-                .execute(&state.pool)
-                .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Queue update error: {}", e)))?;
-        }
-        "completed" => {
-            sqlx::query("DELETE FROM queue_tokens WHERE order_id = $1")
-                .bind(id)
-                .execute(&state.pool)
-                .await
-                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Queue cleanup error: {}", e)))?;
-        }
-        _ => {}
+    let jobs = printing::queue_kitchen_tickets(&mut conn, &state.printing, order_id, payload.station_id, true, Some(staff.id))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to queue kitchen tickets: {}", e)))?;
+    if jobs.is_empty() {
+        return Err((StatusCode::CONFLICT, "No station with a ticket printer has lines on this order".to_string()));
     }
 
-    tokio::spawn({
-        let state = state.clone();
-        async move { broadcast_queue_inner(&state).await; }
-    });
-
-    Ok(Json(json!({ "order_id": id, "new_status": new_status })))
-}
-
-pub async fn ws_handler(
-    ws: WebSocketUpgrade,
-    State(state): State<AppState>
-) -> impl IntoResponse {
-    let bcast = state.bcast.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, bcast))
+    for job in jobs.iter() {
+        publish_print_job(&state, job);
+    }
+    Ok((StatusCode::CREATED, Json(jobs)))
 }
 
-async fn handle_socket(mut socket: WebSocket, bcast: Arc<tokio::sync::broadcast::Sender<String>>) {
-    let mut rx = bcast.subscribe();
+const PRINT_JOB_STATUSES: &[&str] = &["queued", "claimed", "printed", "failed"];
 
-    loop {
-        tokio::select! {
-            msg = socket.recv() => {
-                match msg {
-                    Some(Ok(Message::Text(t))) => {
-                        let _ = socket.send(Message::Text(format!("echo: {}", t))).await;
-                    }
-                    Some(Ok(Message::Ping(data))) => {
-                        let _ = socket.send(Message::Pong(data)).await;
-                    }
-                    Some(Ok(Message::Close(_))) | None => {
-                        let _ = socket.close().await;
-                        break;
-                    }
-                    Some(Err(e)) => {
-                        eprintln!("WebSocket error: {}", e);
-                        break;
-                    }
-                    _ => {}
-                }
-            }
-            res = rx.recv() => {
-                match res {
-                    Ok(text) => {
-                        if socket.send(Message::Text(text)).await.is_err() {
-                            let _ = socket.close().await;
-                            break;
-                        }
-                    }
-                    Err(_) => break,
-                }
-            }
+pub async fn list_print_jobs(
+    State(state): State<AppState>,
+    Query(query): Query<PrintJobQuery>
+) -> Result<Json<Vec<PrintJob>>, (StatusCode, String)> {
+    if let Some(status) = query.status.as_deref() {
+        if !PRINT_JOB_STATUSES.contains(&status) {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid status. Allowed: {}", PRINT_JOB_STATUSES.join(", "))));
         }
     }
+
+    let jobs = sqlx::query_as::<_, PrintJob>(
+        r#"
+        SELECT * FROM print_jobs
+        WHERE ($1::text IS NULL OR status = $1)
+          AND ($2::text IS NULL OR printer = $2)
+          AND ($3::int IS NULL OR order_id = $3)
+        ORDER BY id DESC
+        LIMIT $4
+        "#
+    )
+    .bind(&query.status)
+    .bind(&query.printer)
+    .bind(query.order_id)
+    .bind(query.limit.unwrap_or(100).clamp(1, 500))
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(jobs))
 }
 
-#[derive(serde::Deserialize)]
-pub struct SinceQuery {
-    pub since: Option<String>,
+/// Hands the oldest waiting job for one of the till's printers to that till, or answers
+/// 204 when there is nothing to print. Tills call this when they hear `print.queued`
+/// and on a timer, in case they missed one.
+pub async fn claim_print_job(
+    State(state): State<AppState>,
+    Json(payload): Json<ClaimPrintJob>
+) -> Result<Response, (StatusCode, String)> {
+    let client = payload.client.trim();
+    if client.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Client name is required".to_string()));
+    }
+    if payload.printers.is_empty() || !payload.printers.iter().all(|p| valid_printer_name(p)) {
+        return Err((StatusCode::BAD_REQUEST, "Name at least one printer; names use lowercase letters, digits, '-' and '_'".to_string()));
+    }
+
+    // Two tills driving the same printer never get the same job.
+    let job = sqlx::query_as::<_, PrintJob>(
+        r#"
+        UPDATE print_jobs
+        SET status = 'claimed', claimed_by = $2, claimed_at = now(), attempts = attempts + 1
+        WHERE id = (
+            SELECT id FROM print_jobs
+            WHERE status = 'queued' AND printer = ANY($1) AND available_at <= now()
+            ORDER BY id
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING *
+        "#
+    )
+    .bind(&payload.printers)
+    .bind(client)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(match job {
+        Some(job) => Json(job).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    })
 }
 
-pub async fn sync_menu(
+/// Records how a claimed job went. A failed attempt goes back in the queue after a
+/// delay that grows with each attempt, until the job runs out of attempts.
+pub async fn report_print_job(
     State(state): State<AppState>,
-    Query(q): Query<SinceQuery>
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let menu_items = if let Some(since) = q.since {
-        if let Ok(parsed) = DateTime::parse_from_rfc3339(&since) {
-            let dt_utc = parsed.with_timezone(&Utc);
-            sqlx::query_as::<_, MenuItem>(
-                "SELECT * FROM menu_items WHERE updated_at > $1 ORDER BY id ASC"
-            )
-            .bind(dt_utc)
-            .fetch_all(&state.pool)
+    Path(id): Path<i32>,
+    Json(payload): Json<PrintJobResult>
+) -> Result<Json<PrintJob>, (StatusCode, String)> {
+    let error = payload.error.as_deref().map(str::trim).filter(|e| !e.is_empty()).unwrap_or("Print failed");
+
+    let job = sqlx::query_as::<_, PrintJob>(
+        r#"
+        UPDATE print_jobs
+        SET status = CASE WHEN $3 THEN 'printed' WHEN attempts < max_attempts THEN 'queued' ELSE 'failed' END,
+            printed_at = CASE WHEN $3 THEN now() END,
+            last_error = CASE WHEN $3 THEN last_error ELSE $4 END,
+            available_at = CASE WHEN $3 THEN available_at ELSE now() + make_interval(secs => $5 * attempts) END,
+            claimed_by = NULL,
+            claimed_at = NULL
+        WHERE id = $1 AND status = 'claimed' AND claimed_by = $2
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(payload.client.trim())
+    .bind(payload.ok)
+    .bind(error)
+    .bind(state.printing.retry_delay_secs as f64)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let Some(job) = job else {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM print_jobs WHERE id = $1)")
+            .bind(id)
+            .fetch_one(&state.pool)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        return Err(if exists {
+            (StatusCode::CONFLICT, "This client no longer holds the job; its claim may have expired".to_string())
         } else {
-            return Err((StatusCode::BAD_REQUEST, "Invalid date format. Use RFC3339".to_string()));
-        }
-    } else {
-        sqlx::query_as::<_, MenuItem>("SELECT * FROM menu_items ORDER BY id ASC")
-            .fetch_all(&state.pool)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+            (StatusCode::NOT_FOUND, "Print job not found".to_string())
+        });
     };
 
-    Ok(Json(json!({ "menu": menu_items })))
+    publish_print_job(&state, &job);
+    Ok(Json(job))
 }
 
-pub async fn sync_orders(
+/// Gives a failed job a fresh set of attempts.
+pub async fn retry_print_job(
     State(state): State<AppState>,
-    Query(q): Query<SinceQuery>
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let orders = if let Some(since) = q.since {
-        if let Ok(parsed) = DateTime::parse_from_rfc3339(&since) {
-            let dt_utc = parsed.with_timezone(&Utc);
-            sqlx::query_as::<_, Order>(
-                "SELECT * FROM orders WHERE updated_at > $1 ORDER BY updated_at DESC"
-            )
-            .bind(dt_utc)
-            .fetch_all(&state.pool)
+    Path(id): Path<i32>
+) -> Result<Json<PrintJob>, (StatusCode, String)> {
+    let job = sqlx::query_as::<_, PrintJob>(
+        "UPDATE print_jobs SET status = 'queued', attempts = 0, available_at = now() WHERE id = $1 AND status = 'failed' RETURNING *"
+    )
+    .bind(id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    let Some(job) = job else {
+        let status: Option<String> = sqlx::query_scalar("SELECT status FROM print_jobs WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&state.pool)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
-        } else {
-            return Err((StatusCode::BAD_REQUEST, "Invalid date format. Use RFC3339".to_string()));
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        return Err(match status {
+            Some(status) => (StatusCode::CONFLICT, format!("Only failed jobs can be retried; this one is {}", status)),
+            None => (StatusCode::NOT_FOUND, "Print job not found".to_string()),
+        });
+    };
+
+    publish_print_job(&state, &job);
+    Ok(Json(job))
+}
+
+/// Treats claims not reported back within `PRINT_CLAIM_TIMEOUT_SECS` as failed attempts,
+/// so jobs held by a till that went away get printed elsewhere. Runs on a timer from `main`.
+pub async fn release_stale_print_jobs(state: &AppState) {
+    let released = sqlx::query_as::<_, PrintJob>(
+        r#"
+        UPDATE print_jobs
+        SET status = CASE WHEN attempts < max_attempts THEN 'queued' ELSE 'failed' END,
+            last_error = 'Claim by ' || claimed_by || ' expired',
+            available_at = now(),
+            claimed_by = NULL,
+            claimed_at = NULL
+        WHERE status = 'claimed' AND claimed_at < now() - make_interval(secs => $1)
+        RETURNING *
+        "#
+    )
+    .bind(state.printing.claim_timeout_secs as f64)
+    .fetch_all(&state.pool)
+    .await;
+
+    match released {
+        Ok(jobs) => {
+            for job in jobs.iter() {
+                publish_print_job(state, job);
+            }
         }
-    } else {
-        sqlx::query_as::<_, Order>("SELECT * FROM orders ORDER BY updated_at DESC")
-            .fetch_all(&state.pool)
+        Err(e) => eprintln!("Failed to release stale print jobs: {}", e),
+    }
+}
+const DRAWER_REASONS: &[&str] = &["sale", "refund", "no_sale", "float", "count"];
+
+/// Records a cash drawer opening. The till kicks the drawer itself and reports each
+/// opening here for the cash audit.
+pub async fn open_drawer(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Json(payload): Json<OpenDrawer>
+) -> Result<(StatusCode, Json<DrawerOpening>), (StatusCode, String)> {
+    let reason = payload.reason.trim().to_lowercase();
+    if !DRAWER_REASONS.contains(&reason.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid reason. Allowed: {}", DRAWER_REASONS.join(", "))));
+    }
+    let note = payload.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    if reason == "no_sale" && note.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "Opening the drawer for no sale needs a note".to_string()));
+    }
+    if let Some(order_id) = payload.order_id {
+        let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM orders WHERE id = $1)")
+            .bind(order_id)
+            .fetch_one(&state.pool)
             .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
-    };
-    
-    Ok(Json(json!({ "orders": orders })))
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+        if !exists {
+            return Err((StatusCode::NOT_FOUND, "Order not found".to_string()));
+        }
+    }
+
+    let opening = sqlx::query_as::<_, DrawerOpening>(
+        r#"
+        WITH opened AS (
+            INSERT INTO drawer_openings (staff_id, reason, order_id, note)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+        )
+        SELECT opened.*, s.name AS staff_name
+        FROM opened JOIN staff s ON s.id = opened.staff_id
+        "#
+    )
+    .bind(staff.id)
+    .bind(&reason)
+    .bind(payload.order_id)
+    .bind(note)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(opening)))
 }
 
-pub async fn print_receipt(Path(order_id): Path<i32>) -> Json<serde_json::Value> {
-    Json(json!({"order_id": order_id, "status": "print job queued"}))
+/// The till couldn't kick the drawer after recording an opening. Only the staff member
+/// who recorded it can say so, and only before the day is closed.
+pub async fn report_drawer_kick_failure(
+    State(state): State<AppState>,
+    staff: AuthStaff,
+    Path(id): Path<i32>,
+    Json(payload): Json<DrawerKickFailed>
+) -> Result<Json<DrawerOpening>, (StatusCode, String)> {
+    let error = payload.error.as_deref().map(str::trim).filter(|e| !e.is_empty()).unwrap_or("Drawer kick failed");
+
+    let opening = sqlx::query_as::<_, DrawerOpening>(
+        r#"
+        WITH failed AS (
+            UPDATE drawer_openings
+            SET error = $3
+            WHERE id = $1 AND staff_id = $2 AND error IS NULL AND z_report_id IS NULL
+            RETURNING *
+        )
+        SELECT failed.*, s.name AS staff_name
+        FROM failed JOIN staff s ON s.id = failed.staff_id
+        "#
+    )
+    .bind(id)
+    .bind(staff.id)
+    .bind(error)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "No open drawer opening of yours with that id".to_string()))?;
+
+    Ok(Json(opening))
 }
-pub async fn open_drawer() -> Json<serde_json::Value> {
-    Json(json!({"status": "ok", "note": "client should trigger cash drawer"}))
+
+pub async fn list_drawer_openings(
+    State(state): State<AppState>,
+    Query(query): Query<DrawerOpeningQuery>
+) -> Result<Json<Vec<DrawerOpening>>, (StatusCode, String)> {
+    let openings = sqlx::query_as::<_, DrawerOpening>(
+        r#"
+        SELECT d.*, s.name AS staff_name
+        FROM drawer_openings d
+        JOIN staff s ON s.id = d.staff_id
+        WHERE d.z_report_id IS NOT DISTINCT FROM $1
+          AND ($2::int IS NULL OR d.staff_id = $2)
+        ORDER BY d.opened_at, d.id
+        "#
+    )
+    .bind(query.z_report_id)
+    .bind(query.staff_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
+
+    Ok(Json(openings))
 }
 
 pub async fn latest_update() -> Json<serde_json::Value> {
@@ -422,21 +4240,38 @@ pub async fn get_order_detailed(
         .await
         .map_err(|_| (StatusCode::NOT_FOUND, "Order not found".to_string()))?;
 
+    let items = load_order_items_detailed(&state, order.id).await?;
+
+    Ok(Json(OrderDetailed { order, items }))
+}
+
+/// Order lines with the prices captured when the order was placed, never the current menu price.
+async fn load_order_items_detailed(
+    state: &AppState,
+    order_id: i32,
+) -> Result<Vec<OrderItemDetailed>, (StatusCode, String)> {
     let items = sqlx::query!(
         r#"
         SELECT oi.id, oi.order_id, oi.menu_item_id, oi.quantity,
-               mi.name as menu_name, mi.price as "menu_price!", mi.image_url as menu_image
+               mi.name as menu_name, oi.base_price as "menu_price: Money", oi.unit_price as "unit_price: Money",
+               oi.line_total as "line_total: Money", oi.tax_rate, oi.tax_inclusive,
+               oi.tax_amount as "tax_amount: Money", mi.image_url as menu_image,
+               oi.station_id, oi.status, oi.note
         FROM order_items oi
         JOIN menu_items mi ON mi.id = oi.menu_item_id
         WHERE oi.order_id = $1
+        ORDER BY oi.id ASC
         "#,
-        order.id
+        order_id
     )
     .fetch_all(&state.pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)))?;
 
-    let items_detailed: Vec<OrderItemDetailed> = items.into_iter().map(|r| {
+    let item_ids: Vec<i32> = items.iter().map(|r| r.id).collect();
+    let mut modifiers = load_order_item_modifiers(state, &item_ids).await?;
+
+    Ok(items.into_iter().map(|r| {
         OrderItemDetailed {
             id: r.id,
             order_id: r.order_id,
@@ -444,9 +4279,263 @@ pub async fn get_order_detailed(
             quantity: r.quantity,
             menu_name: r.menu_name,
             menu_price: r.menu_price,
+            unit_price: r.unit_price,
+            line_total: r.line_total,
+            tax_rate: r.tax_rate,
+            tax_inclusive: r.tax_inclusive,
+            tax_amount: r.tax_amount,
             menu_image: r.menu_image,
+            station_id: r.station_id,
+            status: r.status,
+            note: r.note,
+            modifiers: modifiers.remove(&r.id).unwrap_or_default(),
         }
-    }).collect();
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(id: i32, name: &str, min_select: i32, max_select: i32, options: &[(i32, &str, bool)]) -> ModifierGroupDetailed {
+        let now = Utc::now();
+        ModifierGroupDetailed {
+            group: ModifierGroup {
+                id,
+                menu_item_id: 1,
+                name: name.to_string(),
+                min_select,
+                max_select,
+                display_order: 0,
+                created_at: now,
+                updated_at: now,
+            },
+            options: options.iter().map(|&(option_id, option_name, available)| ModifierOption {
+                id: option_id,
+                group_id: id,
+                name: option_name.to_string(),
+                price_delta: Money::zero(),
+                available,
+                display_order: 0,
+                created_at: now,
+                updated_at: now,
+            }).collect(),
+        }
+    }
+
+    /// A burger: pick exactly one size, and up to two extras.
+    fn burger() -> Vec<ModifierGroupDetailed> {
+        vec![
+            group(1, "Size", 1, 1, &[(10, "Regular", true), (11, "Large", true)]),
+            group(2, "Extras", 0, 2, &[(20, "Cheese", true), (21, "Bacon", true), (22, "Egg", true), (23, "Truffle", false)]),
+        ]
+    }
+
+    fn chosen_ids(groups: &[ModifierGroupDetailed], selected: &[i32]) -> Result<Vec<i32>, String> {
+        resolve_modifiers(groups, selected).map(|chosen| chosen.iter().map(|(_, o)| o.id).collect())
+    }
+
+    #[test]
+    fn resolves_options_in_the_order_chosen() {
+        assert_eq!(chosen_ids(&burger(), &[21, 11, 20]), Ok(vec![21, 11, 20]));
+        assert_eq!(chosen_ids(&[], &[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn enforces_group_limits() {
+        assert_eq!(chosen_ids(&burger(), &[20]), Err("choose exactly 1 option(s) for 'Size'".to_string()));
+        assert_eq!(chosen_ids(&burger(), &[10, 11]), Err("choose exactly 1 option(s) for 'Size'".to_string()));
+        assert_eq!(
+            chosen_ids(&burger(), &[10, 20, 21, 22]),
+            Err("choose between 0 and 2 option(s) for 'Extras'".to_string()),
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_repeated_and_unavailable_options() {
+        assert_eq!(chosen_ids(&burger(), &[10, 99]), Err("modifier option 99 does not belong to this item".to_string()));
+        assert_eq!(chosen_ids(&burger(), &[10, 20, 20]), Err("modifier option 20 was selected more than once".to_string()));
+        assert_eq!(chosen_ids(&burger(), &[10, 23]), Err("'Truffle' is currently unavailable".to_string()));
+    }
+
+    #[test]
+    fn orders_move_forward_or_get_cancelled() {
+        assert_eq!(allowed_transitions("pending"), ["preparing", "ready", "cancelled"]);
+        assert_eq!(allowed_transitions("preparing"), ["ready", "cancelled"]);
+        assert_eq!(allowed_transitions("ready"), ["completed", "cancelled"]);
+        assert_eq!(allowed_transitions("completed"), ["refunded"]);
+    }
+
+    #[test]
+    fn terminal_and_unknown_statuses_go_nowhere() {
+        for status in ["cancelled", "refunded", "shipped", ""] {
+            assert!(allowed_transitions(status).is_empty(), "{} has transitions", status);
+        }
+    }
+
+    #[test]
+    fn every_target_is_a_known_status() {
+        for from in ALLOWED_STATUSES {
+            for to in allowed_transitions(from) {
+                assert!(ALLOWED_STATUSES.contains(to), "{} -> {}", from, to);
+                assert!(!allowed_transitions(to).contains(from), "{} <-> {} is a loop", from, to);
+            }
+        }
+    }
+
+    fn money(amount: &str) -> Money {
+        amount.parse().unwrap()
+    }
+
+    fn settled(amount: &str, tendered: &str, change_given: &str) -> Result<Settlement, String> {
+        Ok(Settlement { amount: money(amount), tendered: money(tendered), change_given: money(change_given) })
+    }
+
+    #[test]
+    fn cash_beyond_the_balance_comes_back_as_change() {
+        let due = money("12.40");
+        assert_eq!(settle("cash", None, Some(money("20")), &due), settled("12.40", "20.00", "7.60"));
+        assert_eq!(settle("cash", None, Some(money("12.40")), &due), settled("12.40", "12.40", "0.00"));
+        // A split: five towards the bill out of a ten handed over.
+        assert_eq!(settle("cash", Some(money("5")), Some(money("10")), &due), settled("5.00", "10.00", "5.00"));
+    }
+
+    #[test]
+    fn without_an_amount_the_payment_covers_what_was_tendered_or_the_balance() {
+        let due = money("12.40");
+        assert_eq!(settle("cash", None, Some(money("10")), &due), settled("10.00", "10.00", "0.00"));
+        assert_eq!(settle("card", None, Some(money("4.40")), &due), settled("4.40", "4.40", "0.00"));
+        assert_eq!(settle("card", None, None, &due), settled("12.40", "12.40", "0.00"));
+    }
+
+    #[test]
+    fn rejects_overpayment_short_tenders_and_change_on_cards() {
+        let due = money("12.40");
+        assert_eq!(
+            settle("card", Some(money("15")), None, &due),
+            Err("Payment of 15.00 exceeds the balance due of 12.40".to_string()),
+        );
+        assert_eq!(
+            settle("card", None, Some(money("15")), &due),
+            Err("Payment of 15.00 exceeds the balance due of 12.40".to_string()),
+        );
+        assert_eq!(
+            settle("cash", Some(money("10")), Some(money("5")), &due),
+            Err("Tendered amount is less than the payment amount".to_string()),
+        );
+        assert_eq!(
+            settle("card", Some(money("10")), Some(money("12")), &due),
+            Err("Only cash payments can give change".to_string()),
+        );
+    }
+
+    fn order(client_order_id: Option<&str>) -> CreateOrder {
+        CreateOrder {
+            source: "pos".to_string(),
+            items: vec![],
+            client_order_id: client_order_id.map(str::to_string),
+            print_receipt: false,
+        }
+    }
+
+    fn key(header: Option<&str>, client_order_id: Option<&str>) -> Result<Option<String>, String> {
+        let mut headers = HeaderMap::new();
+        if let Some(header) = header {
+            headers.insert("idempotency-key", header.parse().unwrap());
+        }
+        idempotency_key(&headers, &order(client_order_id)).map_err(|(_, e)| e)
+    }
+
+    const ID: &str = "6f1c2f3e-8a4b-4c5d-9e6f-7a8b9c0d1e2f";
+
+    #[test]
+    fn the_key_comes_from_the_header_or_the_body() {
+        assert_eq!(key(None, None), Ok(None));
+        assert_eq!(key(Some(" till-3/0042 "), None), Ok(Some("till-3/0042".to_string())));
+        assert_eq!(key(None, Some(ID)), Ok(Some(ID.to_string())));
+        assert_eq!(key(Some(ID), Some(ID)), Ok(Some(ID.to_string())));
+    }
+
+    #[test]
+    fn uuid_keys_are_canonical() {
+        let shouted = ID.to_uppercase();
+        let bare = ID.replace('-', "");
+        assert_eq!(key(Some(&shouted), None), Ok(Some(ID.to_string())));
+        assert_eq!(key(Some(&bare), Some(&shouted)), Ok(Some(ID.to_string())));
+    }
+
+    #[test]
+    fn rejects_bad_and_conflicting_keys() {
+        assert_eq!(key(Some(""), None), Err("Idempotency-Key must be 1 to 255 characters".to_string()));
+        assert_eq!(key(Some(&"k".repeat(256)), None), Err("Idempotency-Key must be 1 to 255 characters".to_string()));
+        assert_eq!(key(None, Some("order-1")), Err("client_order_id must be a UUID".to_string()));
+        assert_eq!(
+            key(Some("till-3/0042"), Some(ID)),
+            Err("Idempotency-Key and client_order_id don't match".to_string()),
+        );
+    }
 
-    Ok(Json(OrderDetailed { order, items: items_detailed }))
+    fn page(cursor: Option<i64>, limit: Option<i64>) -> Result<(i64, i64), String> {
+        SyncQuery { cursor, limit }.page()
+    }
+
+    #[test]
+    fn sync_pages_start_at_the_beginning_and_stay_within_bounds() {
+        assert_eq!(page(None, None), Ok((0, SYNC_PAGE_SIZE)));
+        assert_eq!(page(Some(42), Some(10)), Ok((42, 10)));
+        assert_eq!(page(Some(42), Some(0)), Ok((42, 1)));
+        assert_eq!(page(Some(42), Some(1_000_000)), Ok((42, SYNC_MAX_PAGE_SIZE)));
+        assert_eq!(page(Some(-1), None), Err("cursor must not be negative".to_string()));
+    }
+
+    fn change(change_id: i64, entity: &str, id: i32, op: &str) -> SyncChange {
+        SyncChange { change_id, entity: entity.to_string(), id, op: op.to_string(), data: None }
+    }
+
+    #[test]
+    fn upserts_carry_the_current_row_and_vanished_rows_become_deletes() {
+        let mut changes = vec![
+            change(1, "menu_item", 7, "upsert"),
+            change(2, "menu_item", 8, "upsert"),
+            change(3, "menu_item", 9, "delete"),
+            change(4, "tax_rate", 7, "upsert"),
+        ];
+        let rows = HashMap::from([(7, json!({ "id": 7, "name": "Fries" }))]);
+        attach_rows(&mut changes, "menu_item", rows);
+
+        let ops: Vec<(&str, bool)> = changes.iter().map(|c| (c.op.as_str(), c.data.is_some())).collect();
+        assert_eq!(ops, [("upsert", true), ("delete", false), ("delete", false), ("upsert", false)]);
+        assert_eq!(changes[0].data, Some(json!({ "id": 7, "name": "Fries" })));
+    }
+
+    #[test]
+    fn ready_tokens_are_called_and_called_ones_recalled() {
+        assert_eq!(can_announce("K-012", "ready", false), Ok(()));
+        assert_eq!(can_announce("K-012", "called", true), Ok(()));
+    }
+
+    #[test]
+    fn calls_out_of_turn_are_refused() {
+        assert_eq!(can_announce("K-012", "waiting", false), Err("Order K-012 is not ready yet".to_string()));
+        assert_eq!(can_announce("K-012", "waiting", true), Err("Order K-012 is not ready yet".to_string()));
+        assert_eq!(
+            can_announce("K-012", "called", false),
+            Err("Token K-012 was already called; recall it instead".to_string()),
+        );
+        assert_eq!(can_announce("K-012", "ready", true), Err("Token K-012 hasn't been called yet".to_string()));
+        assert_eq!(can_announce("K-012", "no_show", true), Err("Token K-012 is no_show".to_string()));
+    }
+
+    #[test]
+    fn sockets_without_a_session_only_keep_public_topics() {
+        let topics = || -> HashSet<String> { ["queue", "order", "print", "station:grill"].map(String::from).into() };
+
+        let mut anonymous = topics();
+        assert_eq!(withhold_private(&mut anonymous, false), ["order", "print", "station:grill"]);
+        assert_eq!(sorted(&anonymous), ["queue"]);
+
+        let mut staff = topics();
+        assert!(withhold_private(&mut staff, true).is_empty());
+        assert_eq!(staff, topics());
+    }
 }
@@ -0,0 +1,282 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::FromRow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Recent orders used to check prep times against how long the kitchen really takes.
+const CALIBRATION_DAYS: i32 = 14;
+const CALIBRATION_SAMPLES: i64 = 100;
+/// Below this many measured orders the menu prep times are used as they are.
+const MIN_SAMPLES: usize = 5;
+/// Keeps one bad shift from making the estimates absurd.
+const MIN_FACTOR: f64 = 0.5;
+const MAX_FACTOR: f64 = 3.0;
+
+/// An order line still to be made.
+#[derive(Debug, Clone, FromRow)]
+struct OpenLine {
+    order_id: i32,
+    station_id: Option<i32>,
+    prep_seconds: i32,
+    /// Set once the line is cooking.
+    started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, FromRow)]
+struct MeasuredOrder {
+    /// Seconds from `preparing` to `ready`.
+    actual: f64,
+    /// Longest menu prep time among its items.
+    nominal: Option<f64>,
+}
+
+/// The last calibration factor, so estimating the queue doesn't rerun the history
+/// aggregate on every request. Starts at 1.0 until the first refresh.
+pub struct Calibration(AtomicU64);
+
+impl Calibration {
+    pub fn new() -> Self {
+        Calibration(AtomicU64::new(1.0f64.to_bits()))
+    }
+
+    pub fn factor(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Measures the factor again; on failure the previous one is kept.
+    pub async fn refresh(&self, pool: &sqlx::PgPool) {
+        let factor = match pool.acquire().await {
+            Ok(mut conn) => calibration_factor(&mut conn).await,
+            Err(e) => Err(e),
+        };
+        match factor {
+            Ok(factor) => self.0.store(factor.to_bits(), Ordering::Relaxed),
+            Err(e) => eprintln!("Failed to calibrate wait estimates: {}", e),
+        }
+    }
+}
+
+/// How much longer (or shorter) than the menu prep times the kitchen has been taking,
+/// measured from `preparing` to `ready` in the order status history.
+async fn calibration_factor(conn: &mut sqlx::PgConnection) -> Result<f64, sqlx::Error> {
+    let measured = sqlx::query_as::<_, MeasuredOrder>(
+        r#"
+        WITH timed AS (
+            SELECT order_id,
+                   MIN(changed_at) FILTER (WHERE to_status = 'preparing') AS started,
+                   MIN(changed_at) FILTER (WHERE to_status = 'ready') AS ready
+            FROM order_status_history
+            WHERE changed_at > now() - make_interval(days => $1)
+            GROUP BY order_id
+        ), recent AS (
+            SELECT order_id, EXTRACT(EPOCH FROM ready - started)::float8 AS actual
+            FROM timed
+            WHERE started IS NOT NULL AND ready > started
+            ORDER BY ready DESC
+            LIMIT $2
+        )
+        SELECT r.actual,
+               (SELECT MAX(mi.prep_seconds)::float8
+                FROM order_items oi JOIN menu_items mi ON mi.id = oi.menu_item_id
+                WHERE oi.order_id = r.order_id) AS nominal
+        FROM recent r
+        "#
+    )
+    .bind(CALIBRATION_DAYS)
+    .bind(CALIBRATION_SAMPLES)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(calibrate(measured))
+}
+
+fn calibrate(measured: Vec<MeasuredOrder>) -> f64 {
+    let samples: Vec<(f64, f64)> = measured
+        .into_iter()
+        .filter_map(|m| m.nominal.filter(|n| *n > 0.0).map(|n| (m.actual, n)))
+        .collect();
+    if samples.len() < MIN_SAMPLES {
+        return 1.0;
+    }
+    let actual: f64 = samples.iter().map(|(a, _)| a).sum();
+    let nominal: f64 = samples.iter().map(|(_, n)| n).sum();
+    (actual / nominal).clamp(MIN_FACTOR, MAX_FACTOR)
+}
+
+/// When each order still in the kitchen should be ready.
+///
+/// Orders go through each station first come, first served, with `capacity` orders on a
+/// station at a time. An order's share of a station takes as long as its slowest line
+/// there, scaled by the calibration factor (see `Calibration`); lines already cooking
+/// only count what is left. Lines with no station are made alongside everything else.
+/// The order is ready when its last station finishes.
+pub async fn estimate_ready_times(
+    conn: &mut sqlx::PgConnection,
+    factor: f64,
+) -> Result<HashMap<i32, DateTime<Utc>>, sqlx::Error> {
+    let capacities: HashMap<i32, i32> = sqlx::query_as::<_, (i32, i32)>("SELECT id, capacity FROM kitchen_stations")
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .collect();
+
+    let lines = sqlx::query_as::<_, OpenLine>(
+        r#"
+        SELECT o.id AS order_id, oi.station_id, mi.prep_seconds,
+               CASE WHEN oi.status = 'cooking' THEN oi.started_at END AS started_at
+        FROM orders o
+        JOIN order_items oi ON oi.order_id = o.id
+        JOIN menu_items mi ON mi.id = oi.menu_item_id
+        WHERE o.status IN ('pending', 'preparing')
+          AND oi.status <> 'done'
+          AND oi.quantity > COALESCE((
+              SELECT SUM(ai.quantity) FROM order_adjustment_items ai WHERE ai.order_item_id = oi.id
+          ), 0)
+        ORDER BY o.created_at, o.id, oi.id
+        "#
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let open_orders: Vec<i32> = sqlx::query_scalar(
+        "SELECT id FROM orders WHERE status IN ('pending', 'preparing') ORDER BY created_at, id"
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(schedule(&open_orders, &lines, &capacities, factor, Utc::now()))
+}
+
+fn schedule(
+    orders: &[i32],
+    lines: &[OpenLine],
+    capacities: &HashMap<i32, i32>,
+    factor: f64,
+    now: DateTime<Utc>,
+) -> HashMap<i32, DateTime<Utc>> {
+    // When each slot of each station frees up.
+    let mut slots: HashMap<i32, Vec<DateTime<Utc>>> = HashMap::new();
+    let mut ready = HashMap::new();
+
+    let mut lines_by_order: HashMap<i32, Vec<&OpenLine>> = HashMap::new();
+    for line in lines {
+        lines_by_order.entry(line.order_id).or_default().push(line);
+    }
+
+    for order_id in orders {
+        // Remaining work per station for this order.
+        let mut work: Vec<(Option<i32>, Duration)> = Vec::new();
+        for line in lines_by_order.get(order_id).into_iter().flatten() {
+            let total = Duration::seconds((line.prep_seconds as f64 * factor).round() as i64);
+            let left = match line.started_at {
+                Some(started) => (total - (now - started)).max(Duration::zero()),
+                None => total,
+            };
+            match work.iter_mut().find(|(station, _)| *station == line.station_id) {
+                Some((_, longest)) => *longest = (*longest).max(left),
+                None => work.push((line.station_id, left)),
+            }
+        }
+
+        let mut ready_at = now;
+        for (station, duration) in work {
+            let finish = match station {
+                Some(station_id) => {
+                    let capacity = capacities.get(&station_id).copied().unwrap_or(1).max(1) as usize;
+                    let station_slots = slots.entry(station_id).or_insert_with(|| vec![now; capacity]);
+                    let earliest = station_slots
+                        .iter_mut()
+                        .min()
+                        .expect("stations have at least one slot");
+                    *earliest += duration;
+                    *earliest
+                }
+                None => now + duration,
+            };
+            ready_at = ready_at.max(finish);
+        }
+        ready.insert(*order_id, ready_at);
+    }
+
+    ready
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(order_id: i32, station_id: Option<i32>, prep_seconds: i32) -> OpenLine {
+        OpenLine { order_id, station_id, prep_seconds, started_at: None }
+    }
+
+    /// Seconds from now until each order is ready.
+    fn waits(orders: &[i32], lines: &[OpenLine], capacities: &[(i32, i32)], factor: f64, now: DateTime<Utc>) -> Vec<i64> {
+        let capacities = capacities.iter().copied().collect();
+        let ready = schedule(orders, lines, &capacities, factor, now);
+        orders.iter().map(|id| (ready[id] - now).num_seconds()).collect()
+    }
+
+    #[test]
+    fn orders_queue_for_a_station_one_at_a_time() {
+        let now = Utc::now();
+        let lines = [line(1, Some(1), 300), line(2, Some(1), 120), line(3, Some(1), 60)];
+        assert_eq!(waits(&[1, 2, 3], &lines, &[(1, 1)], 1.0, now), [300, 420, 480]);
+    }
+
+    #[test]
+    fn a_station_takes_as_many_orders_at_once_as_its_capacity() {
+        let now = Utc::now();
+        let lines = [line(1, Some(1), 300), line(2, Some(1), 120), line(3, Some(1), 60)];
+        // The third order takes whichever slot frees up first.
+        assert_eq!(waits(&[1, 2, 3], &lines, &[(1, 2)], 1.0, now), [300, 120, 180]);
+    }
+
+    #[test]
+    fn an_order_is_ready_when_its_slowest_station_is() {
+        let now = Utc::now();
+        let lines = [line(1, Some(1), 300), line(1, Some(1), 200), line(1, Some(2), 400), line(2, Some(1), 100)];
+        assert_eq!(waits(&[1, 2], &lines, &[(1, 1), (2, 1)], 1.0, now), [400, 400]);
+    }
+
+    #[test]
+    fn lines_already_cooking_only_count_what_is_left() {
+        let now = Utc::now();
+        let cooking = OpenLine { started_at: Some(now - Duration::seconds(200)), ..line(1, Some(1), 300) };
+        let overdue = OpenLine { started_at: Some(now - Duration::seconds(900)), ..line(2, Some(2), 300) };
+        assert_eq!(waits(&[1, 2], &[cooking, overdue], &[(1, 1), (2, 1)], 1.0, now), [100, 0]);
+    }
+
+    #[test]
+    fn lines_without_a_station_do_not_queue() {
+        let now = Utc::now();
+        let lines = [line(1, None, 300), line(2, None, 200), line(3, Some(1), 60)];
+        assert_eq!(waits(&[1, 2, 3], &lines, &[], 1.0, now), [300, 200, 60]);
+    }
+
+    #[test]
+    fn prep_times_are_scaled_and_orders_without_open_lines_are_ready_now() {
+        let now = Utc::now();
+        let lines = [line(1, Some(1), 300)];
+        assert_eq!(waits(&[1, 2], &lines, &[(1, 1)], 1.5, now), [450, 0]);
+    }
+
+    fn measured(samples: &[(f64, Option<f64>)]) -> Vec<MeasuredOrder> {
+        samples.iter().map(|&(actual, nominal)| MeasuredOrder { actual, nominal }).collect()
+    }
+
+    #[test]
+    fn calibration_compares_actual_and_menu_prep_times() {
+        assert_eq!(calibrate(measured(&[(360.0, Some(300.0)); 5])), 1.2);
+        assert_eq!(calibrate(measured(&[(360.0, Some(300.0)); 4])), 1.0);
+        // Orders with no prep time on the menu don't count towards the sample.
+        let mut samples = measured(&[(360.0, Some(300.0)); 4]);
+        samples.push(MeasuredOrder { actual: 600.0, nominal: None });
+        assert_eq!(calibrate(samples), 1.0);
+    }
+
+    #[test]
+    fn calibration_stays_within_bounds() {
+        assert_eq!(calibrate(measured(&[(3000.0, Some(300.0)); 5])), MAX_FACTOR);
+        assert_eq!(calibrate(measured(&[(30.0, Some(300.0)); 5])), MIN_FACTOR);
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+use crate::kitchen::{valid_station_code, STATION_TOPIC_PREFIX};
+
+/// Bumped whenever the envelope or an event payload changes incompatibly.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Topics a WebSocket client can subscribe to. An event's topic is the part of its
+/// type before the first dot, so `order.created` is delivered on `order`.
+pub const TOPICS: &[&str] = &["queue", "order", "menu", "print"];
+
+/// Topics kiosks and customer displays may follow without a staff session. Orders,
+/// print jobs and station feeds carry payments and receipts, so they need one.
+pub const PUBLIC_TOPICS: &[&str] = &["queue", "menu"];
+
+/// Envelope for everything pushed over `/ws`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub v: u32,
+    pub seq: u64,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub topic: String,
+    pub at: DateTime<Utc>,
+    pub payload: serde_json::Value,
+}
+
+pub fn topic_of(kind: &str) -> &str {
+    kind.split('.').next().unwrap_or(kind)
+}
+
+/// One of `TOPICS`, or a kitchen station feed (`station:grill`).
+pub fn is_known_topic(topic: &str) -> bool {
+    TOPICS.contains(&topic)
+        || topic.strip_prefix(STATION_TOPIC_PREFIX).is_some_and(valid_station_code)
+}
+
+pub fn is_public_topic(topic: &str) -> bool {
+    PUBLIC_TOPICS.contains(&topic)
+}
+
+/// What a reconnecting client gets back for its last seen sequence number.
+pub enum Replay {
+    /// Every buffered event after the client's sequence, oldest first.
+    Events(Vec<Arc<Event>>),
+    /// The gap can't be filled from the buffer; the client needs a full snapshot.
+    Resync(&'static str),
+}
+
+struct Log {
+    seq: u64,
+    recent: VecDeque<Arc<Event>>,
+}
+
+/// Fan-out of server events to every connected socket.
+///
+/// Sequence numbers increase by one per event and restart with the process, so each
+/// run gets a fresh `epoch`. The most recent `replay_capacity` events are kept so a
+/// client that drops off briefly can catch up instead of reloading everything.
+pub struct EventBus {
+    tx: broadcast::Sender<Arc<Event>>,
+    epoch: String,
+    replay_capacity: usize,
+    // Held while sending so sequence numbers go out in order and the replay buffer
+    // never misses an event a live receiver saw.
+    log: Mutex<Log>,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize, replay_capacity: usize) -> Self {
+        let (tx, _rx) = broadcast::channel(capacity);
+        EventBus {
+            tx,
+            epoch: Uuid::new_v4().to_string(),
+            replay_capacity,
+            log: Mutex::new(Log { seq: 0, recent: VecDeque::with_capacity(replay_capacity) }),
+        }
+    }
+
+    pub fn epoch(&self) -> &str {
+        &self.epoch
+    }
+
+    pub fn current_seq(&self) -> u64 {
+        self.lock().seq
+    }
+
+    pub fn publish(&self, kind: &str, payload: serde_json::Value) -> u64 {
+        self.publish_to(topic_of(kind), kind, payload)
+    }
+
+    /// Publishes on a topic other than the one named by the event type, e.g. a station feed.
+    pub fn publish_to(&self, topic: &str, kind: &str, payload: serde_json::Value) -> u64 {
+        let mut log = self.lock();
+        log.seq += 1;
+        let event = Arc::new(Event {
+            v: PROTOCOL_VERSION,
+            seq: log.seq,
+            kind: kind.to_string(),
+            topic: topic.to_string(),
+            at: Utc::now(),
+            payload,
+        });
+
+        // Always keep the latest event so a capacity of 0 can't leave the buffer unbounded.
+        while log.recent.len() >= self.replay_capacity.max(1) {
+            log.recent.pop_front();
+        }
+        log.recent.push_back(event.clone());
+
+        // No receivers just means no one is connected.
+        let _ = self.tx.send(event);
+        log.seq
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Event>> {
+        self.tx.subscribe()
+    }
+
+    /// Events published after `last_seq` in the run identified by `epoch`.
+    pub fn replay_since(&self, epoch: &str, last_seq: u64) -> Replay {
+        let log = self.lock();
+        if epoch != self.epoch {
+            return Replay::Resync("server restarted");
+        }
+        if last_seq > log.seq {
+            return Replay::Resync("sequence is ahead of the server");
+        }
+        let oldest = log.recent.front().map(|e| e.seq).unwrap_or(log.seq + 1);
+        if last_seq + 1 < oldest {
+            return Replay::Resync("gap is older than the replay buffer");
+        }
+        Replay::Events(log.recent.iter().filter(|e| e.seq > last_seq).cloned().collect())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Messages a client may send over `/ws`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe { topics: Vec<String> },
+    Unsubscribe { topics: Vec<String> },
+    /// Sent after reconnecting with the epoch from `hello` and the last `seq` seen.
+    Resume { epoch: String, last_seq: u64 },
+    /// Signs the socket in with a staff session token, for the topics that need one.
+    Auth { token: String },
+    Ping,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Sequence numbers a replay would send, or why the client has to resync.
+    fn replay(bus: &EventBus, epoch: &str, last_seq: u64) -> Result<Vec<u64>, &'static str> {
+        match bus.replay_since(epoch, last_seq) {
+            Replay::Events(events) => Ok(events.iter().map(|e| e.seq).collect()),
+            Replay::Resync(reason) => Err(reason),
+        }
+    }
+
+    fn bus_with(replay_capacity: usize, events: usize) -> EventBus {
+        let bus = EventBus::new(16, replay_capacity);
+        for _ in 0..events {
+            bus.publish("order.created", json!({}));
+        }
+        bus
+    }
+
+    #[test]
+    fn replays_everything_after_the_last_seen_event() {
+        let bus = bus_with(8, 5);
+        let epoch = bus.epoch().to_string();
+        assert_eq!(replay(&bus, &epoch, 2), Ok(vec![3, 4, 5]));
+        assert_eq!(replay(&bus, &epoch, 5), Ok(vec![]));
+        assert_eq!(replay(&bus, &epoch, 0), Ok(vec![1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn another_epoch_means_the_server_restarted() {
+        let bus = bus_with(8, 3);
+        assert_eq!(replay(&bus, "some-earlier-run", 1), Err("server restarted"));
+    }
+
+    #[test]
+    fn a_gap_older_than_the_buffer_needs_a_snapshot() {
+        let bus = bus_with(3, 6);
+        let epoch = bus.epoch().to_string();
+        assert_eq!(replay(&bus, &epoch, 3), Ok(vec![4, 5, 6]));
+        assert_eq!(replay(&bus, &epoch, 2), Err("gap is older than the replay buffer"));
+    }
+
+    #[test]
+    fn a_sequence_ahead_of_the_server_needs_a_snapshot() {
+        let bus = bus_with(8, 2);
+        let epoch = bus.epoch().to_string();
+        assert_eq!(replay(&bus, &epoch, 3), Err("sequence is ahead of the server"));
+    }
+
+    #[test]
+    fn a_zero_capacity_buffer_keeps_only_the_latest_event() {
+        let bus = bus_with(0, 4);
+        let epoch = bus.epoch().to_string();
+        assert_eq!(replay(&bus, &epoch, 3), Ok(vec![4]));
+        assert_eq!(replay(&bus, &epoch, 2), Err("gap is older than the replay buffer"));
+    }
+
+    #[test]
+    fn events_are_published_on_the_topic_before_the_dot() {
+        assert_eq!(topic_of("order.created"), "order");
+        assert_eq!(topic_of("queue"), "queue");
+        assert!(is_known_topic("station:grill"));
+        assert!(!is_known_topic("station:"));
+    }
+
+    #[test]
+    fn only_the_queue_and_menu_are_public() {
+        assert!(is_public_topic("queue"));
+        assert!(is_public_topic("menu"));
+        assert!(!is_public_topic("order"));
+        assert!(!is_public_topic("print"));
+        assert!(!is_public_topic("sync"));
+        assert!(!is_public_topic("station:grill"));
+    }
+}
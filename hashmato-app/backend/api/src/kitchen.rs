@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// Prep status of a single order line.
+pub const ITEM_STATUSES: &[&str] = &["queued", "cooking", "done"];
+
+/// Station feeds are WebSocket topics named `station:<code>`.
+pub const STATION_TOPIC_PREFIX: &str = "station:";
+
+pub fn station_topic(code: &str) -> String {
+    format!("{}{}", STATION_TOPIC_PREFIX, code)
+}
+
+/// Station codes end up in topic names, so they stay plain.
+pub fn valid_station_code(code: &str) -> bool {
+    !code.is_empty() && code.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// One line on a station display. `quantity` is what is left after voids and refunds.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TicketLine {
+    pub id: i32,
+    pub menu_item_id: i32,
+    pub name: String,
+    pub quantity: i32,
+    /// Chosen modifier options, e.g. "No onions".
+    pub modifiers: Vec<String>,
+    /// Cooking instructions, e.g. "well done".
+    pub note: Option<String>,
+    pub status: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub done_at: Option<DateTime<Utc>>,
+}
+
+/// The part of one order a station has to make.
+#[derive(Debug, Clone, Serialize)]
+pub struct StationTicket {
+    pub station_id: i32,
+    pub station_code: String,
+    pub station_name: String,
+    pub order_id: i32,
+    pub token_label: Option<String>,
+    pub source: String,
+    /// Displays drop the ticket once the order leaves `pending`/`preparing`.
+    pub order_status: String,
+    pub placed_at: DateTime<Utc>,
+    pub items: Vec<TicketLine>,
+}
+
+#[derive(Debug, FromRow)]
+struct TicketRow {
+    station_id: i32,
+    station_code: String,
+    station_name: String,
+    order_id: i32,
+    token_label: Option<String>,
+    source: String,
+    order_status: String,
+    placed_at: DateTime<Utc>,
+    #[sqlx(flatten)]
+    line: TicketLine,
+}
+
+/// Tickets for one station (or all of them), either for a single order whatever its
+/// status, or for every order still in the kitchen. Oldest order first.
+///
+/// A ticket whose lines were all voided comes back with no items, so displays know to
+/// drop it.
+pub async fn load_tickets(
+    conn: &mut sqlx::PgConnection,
+    station_code: Option<&str>,
+    order_id: Option<i32>,
+) -> Result<Vec<StationTicket>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, TicketRow>(
+        r#"
+        SELECT s.id AS station_id, s.code AS station_code, s.name AS station_name,
+               o.id AS order_id, o.token_label, o.source, o.status AS order_status, o.created_at AS placed_at,
+               oi.id, oi.menu_item_id, mi.name,
+               (oi.quantity - COALESCE(r.quantity, 0))::int AS quantity,
+               ARRAY(
+                   SELECT m.option_name FROM order_item_modifiers m
+                   WHERE m.order_item_id = oi.id ORDER BY m.id
+               ) AS modifiers,
+               oi.note, oi.status, oi.started_at, oi.done_at
+        FROM order_items oi
+        JOIN kitchen_stations s ON s.id = oi.station_id
+        JOIN orders o ON o.id = oi.order_id
+        JOIN menu_items mi ON mi.id = oi.menu_item_id
+        LEFT JOIN (
+            SELECT order_item_id, SUM(quantity) AS quantity
+            FROM order_adjustment_items
+            GROUP BY order_item_id
+        ) r ON r.order_item_id = oi.id
+        WHERE ($1::text IS NULL OR s.code = $1)
+          AND (o.id = $2 OR ($2 IS NULL AND o.status IN ('pending', 'preparing')))
+        ORDER BY o.created_at, o.id, s.display_order, s.id, oi.id
+        "#
+    )
+    .bind(station_code)
+    .bind(order_id)
+    .fetch_all(conn)
+    .await?;
+
+    // A fully voided ticket only matters as a removal for one order.
+    Ok(group_tickets(rows, order_id.is_some()))
+}
+
+/// Collects rows, which come grouped by order and then station, into tickets. Lines with
+/// nothing left to make are dropped, and so are the tickets they leave empty unless
+/// `keep_empty` is set.
+fn group_tickets(rows: Vec<TicketRow>, keep_empty: bool) -> Vec<StationTicket> {
+    let mut tickets: Vec<StationTicket> = Vec::new();
+    for row in rows {
+        let same_ticket = tickets.last().is_some_and(|t| t.order_id == row.order_id && t.station_id == row.station_id);
+        if !same_ticket {
+            tickets.push(StationTicket {
+                station_id: row.station_id,
+                station_code: row.station_code,
+                station_name: row.station_name,
+                order_id: row.order_id,
+                token_label: row.token_label,
+                source: row.source,
+                order_status: row.order_status,
+                placed_at: row.placed_at,
+                items: Vec::new(),
+            });
+        }
+        if row.line.quantity > 0 {
+            if let Some(ticket) = tickets.last_mut() {
+                ticket.items.push(row.line);
+            }
+        }
+    }
+
+    if !keep_empty {
+        tickets.retain(|t| !t.items.is_empty());
+    }
+    tickets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A line of `quantity` left on order `order_id` at station `station_id`.
+    fn row(order_id: i32, station_id: i32, line_id: i32, quantity: i32) -> TicketRow {
+        TicketRow {
+            station_id,
+            station_code: format!("s{}", station_id),
+            station_name: format!("Station {}", station_id),
+            order_id,
+            token_label: Some(format!("K-{:03}", order_id)),
+            source: "kiosk".to_string(),
+            order_status: "pending".to_string(),
+            placed_at: Utc::now(),
+            line: TicketLine {
+                id: line_id,
+                menu_item_id: 1,
+                name: "Burger".to_string(),
+                quantity,
+                modifiers: vec![],
+                note: None,
+                status: "queued".to_string(),
+                started_at: None,
+                done_at: None,
+            },
+        }
+    }
+
+    fn shape(tickets: &[StationTicket]) -> Vec<(i32, i32, Vec<i32>)> {
+        tickets.iter().map(|t| (t.order_id, t.station_id, t.items.iter().map(|l| l.id).collect())).collect()
+    }
+
+    #[test]
+    fn groups_lines_by_order_and_station() {
+        let rows = vec![row(1, 1, 10, 1), row(1, 1, 11, 2), row(1, 2, 12, 1), row(2, 1, 13, 1)];
+        let tickets = group_tickets(rows, false);
+        assert_eq!(shape(&tickets), [(1, 1, vec![10, 11]), (1, 2, vec![12]), (2, 1, vec![13])]);
+        assert_eq!(tickets[0].station_code, "s1");
+        assert_eq!(tickets[2].token_label.as_deref(), Some("K-002"));
+    }
+
+    #[test]
+    fn voided_lines_and_the_tickets_they_empty_are_dropped() {
+        let rows = vec![row(1, 1, 10, 0), row(1, 1, 11, 1), row(1, 2, 12, 0), row(2, 1, 13, 0)];
+        assert_eq!(shape(&group_tickets(rows, false)), [(1, 1, vec![11])]);
+    }
+
+    #[test]
+    fn empty_tickets_stay_when_asked_for_so_displays_can_remove_them() {
+        let rows = vec![row(1, 1, 10, 1), row(1, 2, 12, 0)];
+        assert_eq!(shape(&group_tickets(rows, true)), [(1, 1, vec![10]), (1, 2, vec![])]);
+    }
+
+    #[test]
+    fn station_codes_are_plain() {
+        assert!(valid_station_code("grill"));
+        assert!(valid_station_code("cold-bar_2"));
+        assert!(!valid_station_code(""));
+        assert!(!valid_station_code("Grill"));
+        assert!(!valid_station_code("bar 2"));
+        assert!(!valid_station_code("a.b"));
+        assert_eq!(station_topic("grill"), "station:grill");
+    }
+}
@@ -2,6 +2,17 @@ mod db;
 mod models;
 mod controllers;
 mod routes;
+mod pricing;
+mod tokens;
+mod events;
+mod auth;
+mod permissions;
+mod adjustments;
+mod reports;
+mod analytics;
+mod kitchen;
+mod estimates;
+mod printing;
 
 use axum::Router;
 use dotenvy::dotenv;
@@ -10,13 +21,20 @@ use tower_http::cors::{CorsLayer, Any};
 use tower_http::services::ServeDir;
 use std::sync::Arc;
 use tokio::net::TcpListener;
-use tokio::sync::broadcast;
 use sqlx::PgPool;
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
-    pub bcast: Arc<broadcast::Sender<String>>,
+    pub bcast: Arc<events::EventBus>,
+    pub tokens: Arc<tokens::TokenSettings>,
+    pub auth: Arc<auth::AuthConfig>,
+    pub adjustments: Arc<adjustments::AdjustmentPolicy>,
+    pub queue: Arc<tokens::QueueSettings>,
+    pub printing: Arc<printing::PrintSettings>,
+    /// ISO 4217 code every amount is in; see `Money`.
+    pub currency: Arc<str>,
+    pub calibration: Arc<estimates::Calibration>,
 }
 
 #[tokio::main]
@@ -30,9 +48,36 @@ async fn main() {
         .run(&pool)
         .await
         .expect("Failed to run migrations");
-    let (bcast_tx, _rx) = broadcast::channel::<String>(128);
-    let bcast = Arc::new(bcast_tx);
-    let state = AppState { pool, bcast };
+    let replay_capacity = std::env::var("WS_REPLAY_BUFFER")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(1024);
+    let bcast = Arc::new(events::EventBus::new(128, replay_capacity));
+    let tokens = Arc::new(tokens::TokenSettings::from_env());
+    tokens.check_timezone(&pool).await;
+    let auth = Arc::new(auth::AuthConfig::from_env());
+    auth::bootstrap_admin(&pool).await;
+    let adjustments = Arc::new(adjustments::AdjustmentPolicy::from_env());
+    let queue = Arc::new(tokens::QueueSettings::from_env());
+    let printing = Arc::new(printing::PrintSettings::from_env());
+    let currency = Arc::from(models::Money::currency_from_env());
+    let calibration = Arc::new(estimates::Calibration::new());
+    let state = AppState { pool, bcast, tokens, auth, adjustments, queue, printing, currency, calibration };
+
+    // Ready tokens nobody collects are marked as no-shows, print jobs held by a till
+    // that went quiet go back in the queue, and wait estimates are recalibrated.
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            let mut tick = tokio::time::interval(std::time::Duration::from_secs(30));
+            loop {
+                tick.tick().await;
+                controllers::mark_no_shows(&state).await;
+                controllers::release_stale_print_jobs(&state).await;
+                state.calibration.refresh(&state.pool).await;
+            }
+        }
+    });
 
     let cors = CorsLayer::new()
         .allow_origin(Any)
@@ -47,7 +92,7 @@ async fn main() {
 
     let app = Router::new()
         .nest_service("/uploads", serve_uploads)
-        .merge(routes::routes())
+        .merge(routes::routes(state.clone()))
         .layer(cors)
         .with_state(state);
 
@@ -1,26 +1,429 @@
-use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
-use chrono::{DateTime, Utc};
-use bigdecimal::BigDecimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{FromRow, Postgres};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
+use chrono::{DateTime, NaiveDate, Utc};
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+/// An amount in the store currency.
+///
+/// The store sells in a single currency, so amounts don't carry one. It is read from
+/// `CURRENCY` once at startup into `AppState::currency`; each order records the currency
+/// it was priced in, and responses with totals name it.
+/// Amounts are always held at `Money::SCALE` decimal places, rounding half away from
+/// zero, and travel through JSON as strings (`"12.50"`) so clients never see a float.
+/// Numbers are still accepted on input for older clients.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(BigDecimal);
+
+impl Money {
+    pub const SCALE: i64 = 2;
+
+    pub fn new(amount: BigDecimal) -> Self {
+        Money(amount.round(Self::SCALE).with_scale(Self::SCALE))
+    }
+
+    pub fn zero() -> Self {
+        Money::new(BigDecimal::zero())
+    }
+
+    pub fn amount(&self) -> &BigDecimal {
+        &self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < BigDecimal::zero()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn times(&self, quantity: i32) -> Money {
+        Money::new(&self.0 * BigDecimal::from(quantity))
+    }
+
+    /// `rate` percent of this amount, e.g. exclusive tax on a net price.
+    pub fn percent(&self, rate: &BigDecimal) -> Money {
+        Money::new(&self.0 * rate / BigDecimal::from(100))
+    }
+
+    /// The share of this amount that is `rate` percent tax already included in it.
+    pub fn included_percent(&self, rate: &BigDecimal) -> Money {
+        Money::new(&self.0 * rate / (BigDecimal::from(100) + rate))
+    }
+
+    /// ISO 4217 code of the store currency, from `CURRENCY` (defaults to USD).
+    pub fn currency_from_env() -> String {
+        std::env::var("CURRENCY")
+            .map(|c| c.trim().to_uppercase())
+            .ok()
+            .filter(|c| !c.is_empty())
+            .unwrap_or_else(|| "USD".to_string())
+    }
+}
+
+impl Default for Money {
+    fn default() -> Self {
+        Money::zero()
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Money {
+    type Err = bigdecimal::ParseBigDecimalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BigDecimal::from_str(s.trim()).map(Money::new)
+    }
+}
+
+impl From<BigDecimal> for Money {
+    fn from(amount: BigDecimal) -> Self {
+        Money::new(amount)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl<'a> Add<&'a Money> for &'a Money {
+    type Output = Money;
+    fn add(self, rhs: &'a Money) -> Money {
+        Money(&self.0 + &rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl<'a> Sub<&'a Money> for &'a Money {
+    type Output = Money;
+    fn sub(self, rhs: &'a Money) -> Money {
+        Money(&self.0 - &rhs.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl AddAssign<&Money> for Money {
+    fn add_assign(&mut self, rhs: &Money) {
+        self.0 += &rhs.0;
+    }
+}
+
+impl SubAssign<&Money> for Money {
+    fn sub_assign(&mut self, rhs: &Money) {
+        self.0 -= &rhs.0;
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.fold(Money::zero(), |acc, m| &acc + m)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl serde::de::Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a decimal amount such as \"12.50\"")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Money, E> {
+                Money::from_str(v).map_err(|_| E::custom(format!("invalid amount: {}", v)))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Money, E> {
+                Ok(Money::new(BigDecimal::from(v)))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Money, E> {
+                Ok(Money::new(BigDecimal::from(v)))
+            }
+
+            // Goes through the shortest decimal representation, so 0.1 stays 0.10.
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<Money, E> {
+                if !v.is_finite() {
+                    return Err(E::custom("amount must be finite"));
+                }
+                self.visit_str(&v.to_string())
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+impl sqlx::Type<Postgres> for Money {
+    fn type_info() -> PgTypeInfo {
+        <BigDecimal as sqlx::Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <BigDecimal as sqlx::Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl sqlx::Encode<'_, Postgres> for Money {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <BigDecimal as sqlx::Encode<Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for Money {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(Money::new(<BigDecimal as sqlx::Decode<Postgres>>::decode(value)?))
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct MenuItem {
     pub id: i32,
     pub name: String,
-    pub price: BigDecimal, 
+    pub price: Money,
     pub available: bool,
     pub image_url: Option<String>,
+    pub category_id: Option<i32>,
+    pub tax_rate_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Kitchen station that makes it; unrouted items never show on a station display.
+    pub station_id: Option<i32>,
+    /// How long one takes to make, for queue wait estimates.
+    pub prep_seconds: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateMenuItem {
+    pub name: String,
+    pub price: Money,
+    pub image_url: Option<String>,
+    pub category_id: Option<i32>,
+    pub tax_rate_id: Option<i32>,
+    pub station_id: Option<i32>,
+    /// Left unchanged when omitted.
+    pub prep_seconds: Option<i32>,
+    /// Left unchanged when omitted; see `PATCH /menu/:id/availability`.
+    pub available: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAvailability {
+    pub available: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MenuCategory {
+    pub id: i32,
+    pub name: String,
+    pub parent_id: Option<i32>,
+    pub display_order: i32,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MenuCategoryPayload {
+    pub name: String,
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub display_order: i32,
+    #[serde(default = "default_true")]
+    pub active: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct KitchenStation {
+    pub id: i32,
+    pub code: String,
+    pub name: String,
+    pub display_order: i32,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Orders the station works on at once.
+    pub capacity: i32,
+    /// Ticket printer, by the name the tills use for it.
+    pub printer: Option<String>,
+    /// Print a ticket for every new order with lines here.
+    pub auto_print: bool,
+    /// Roll width of the ticket printer in mm, 58 or 80.
+    pub paper_width: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct KitchenStationPayload {
+    /// Lowercase letters, digits, `-` and `_`; stations are followed on `/ws` as `station:<code>`.
+    pub code: String,
+    pub name: String,
+    #[serde(default)]
+    pub display_order: i32,
+    #[serde(default = "default_true")]
+    pub active: bool,
+    #[serde(default = "default_capacity")]
+    pub capacity: i32,
+    pub printer: Option<String>,
+    /// Needs a `printer`.
+    #[serde(default)]
+    pub auto_print: bool,
+    #[serde(default = "default_paper_width")]
+    pub paper_width: i32,
+}
+
+fn default_capacity() -> i32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateItemStatus {
+    pub status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MenuCategoryNode {
+    #[serde(flatten)]
+    pub category: MenuCategory,
+    pub items: Vec<MenuItem>,
+    pub children: Vec<MenuCategoryNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MenuTree {
+    pub categories: Vec<MenuCategoryNode>,
+    pub uncategorized: Vec<MenuItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ModifierGroup {
+    pub id: i32,
+    pub menu_item_id: i32,
+    pub name: String,
+    pub min_select: i32,
+    pub max_select: i32,
+    pub display_order: i32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ModifierOption {
+    pub id: i32,
+    pub group_id: i32,
+    pub name: String,
+    pub price_delta: Money,
+    pub available: bool,
+    pub display_order: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModifierGroupDetailed {
+    #[serde(flatten)]
+    pub group: ModifierGroup,
+    pub options: Vec<ModifierOption>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModifierGroupPayload {
+    pub name: String,
+    #[serde(default)]
+    pub min_select: i32,
+    #[serde(default = "default_max_select")]
+    pub max_select: i32,
+    #[serde(default)]
+    pub display_order: i32,
+    /// Only used on create; options are managed individually afterwards.
+    #[serde(default)]
+    pub options: Vec<ModifierOptionPayload>,
+}
+
+fn default_max_select() -> i32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModifierOptionPayload {
+    pub name: String,
+    #[serde(default)]
+    pub price_delta: Money,
+    #[serde(default = "default_true")]
+    pub available: bool,
+    #[serde(default)]
+    pub display_order: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OrderItemModifier {
+    pub id: i32,
+    pub order_item_id: i32,
+    pub modifier_option_id: Option<i32>,
+    pub group_name: String,
+    pub option_name: String,
+    pub price_delta: Money,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Order {
     pub id: i32,
     pub source: String,
     pub status: String,
+    pub subtotal: Money,
+    pub tax_total: Money,
+    pub total: Money,
+    pub currency: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub amount_paid: Money,
+    /// `unpaid`, `partially_paid` or `paid`; separate from the kitchen-facing `status`.
+    pub payment_status: String,
+    pub idempotency_key: Option<String>,
+    #[serde(skip_serializing)]
+    pub request_hash: Option<String>,
+    pub token_number: Option<i32>,
+    pub token_label: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -29,18 +432,67 @@ pub struct OrderItem {
     pub order_id: i32,
     pub menu_item_id: i32,
     pub quantity: i32,
+    pub base_price: Money,
+    pub unit_price: Money,
+    pub line_total: Money,
+    pub tax_rate: BigDecimal,
+    pub tax_inclusive: bool,
+    pub tax_amount: Money,
+    pub station_id: Option<i32>,
+    /// Prep status: queued, cooking or done.
+    pub status: String,
+    pub started_at: Option<DateTime<Utc>>,
+    pub done_at: Option<DateTime<Utc>>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TaxRate {
+    pub id: i32,
+    pub name: String,
+    pub rate: BigDecimal,
+    pub inclusive: bool,
+    pub is_default: bool,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct TaxRatePayload {
+    pub name: String,
+    pub rate: BigDecimal,
+    #[serde(default)]
+    pub inclusive: bool,
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(default = "default_true")]
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateOrderItem {
     pub menu_item_id: i32,
     pub quantity: i32,
+    /// Ids of the chosen `modifier_options`.
+    #[serde(default)]
+    pub modifiers: Vec<i32>,
+    /// Cooking instructions for the kitchen, e.g. "well done".
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateOrder {
     pub source: String,
     pub items: Vec<CreateOrderItem>,
+    /// Client-generated UUID; retries with the same id return the original order.
+    /// An `Idempotency-Key` header works the same way.
+    #[serde(default, skip_serializing)]
+    pub client_order_id: Option<String>,
+    /// Kiosks set this to have a receipt printed on the counter printer.
+    #[serde(default)]
+    pub print_receipt: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -48,8 +500,118 @@ pub struct QueueToken {
     pub id: i32,
     pub order_id: i32,
     pub token_number: i32,
+    /// What the customer sees, e.g. `K-012`.
+    pub token_label: String,
+    pub business_date: NaiveDate,
+    /// waiting, ready, called; then collected, no_show or cancelled once archived.
     pub status: String,
     pub created_at: DateTime<Utc>,
+    pub ready_at: Option<DateTime<Utc>>,
+    /// Times the token was announced, recalls included.
+    pub call_count: i32,
+    pub last_called_at: Option<DateTime<Utc>>,
+    pub collected_at: Option<DateTime<Utc>>,
+    pub no_show_at: Option<DateTime<Utc>>,
+    /// Archived tokens are off the live queue.
+    pub archived_at: Option<DateTime<Utc>>,
+    /// Filled in for tokens still waiting; see `estimates::estimate_ready_times`.
+    #[sqlx(default)]
+    pub estimated_ready_at: Option<DateTime<Utc>>,
+    #[sqlx(default)]
+    pub estimated_wait_seconds: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Payment {
+    pub id: i32,
+    pub order_id: i32,
+    pub tender: String,
+    /// What counts towards the order total.
+    pub amount: Money,
+    /// What the customer handed over; more than `amount` only for cash.
+    pub tendered: Money,
+    pub change_given: Money,
+    pub reference: Option<String>,
+    pub staff_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    /// For refunds (negative amounts), the payment being refunded.
+    pub refund_of: Option<i32>,
+    pub adjustment_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePayment {
+    pub tender: String,
+    /// Defaults to the outstanding balance (or, for cash, as much of it as `tendered` covers).
+    pub amount: Option<Money>,
+    pub tendered: Option<Money>,
+    /// Card terminal or voucher reference.
+    pub reference: Option<String>,
+}
+
+/// A void or refund. Amounts are negative.
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderAdjustment {
+    pub id: i32,
+    pub order_id: i32,
+    pub kind: String,
+    pub reason_code: String,
+    pub note: Option<String>,
+    pub subtotal: Money,
+    pub tax_total: Money,
+    pub total: Money,
+    pub staff_id: Option<i32>,
+    pub approved_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderAdjustmentItem {
+    pub id: i32,
+    pub adjustment_id: i32,
+    pub order_item_id: i32,
+    pub quantity: i32,
+    pub line_total: Money,
+    pub tax_amount: Money,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderAdjustmentDetailed {
+    #[serde(flatten)]
+    pub adjustment: OrderAdjustment,
+    pub items: Vec<OrderAdjustmentItem>,
+    /// Money paid back, one negative payment per original tender.
+    pub refunds: Vec<Payment>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdjustmentLine {
+    pub order_item_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefundTender {
+    pub payment_id: i32,
+    pub amount: Money,
+}
+
+/// A manager's username and PIN, for actions the signed-in staff member can't approve.
+#[derive(Debug, Deserialize)]
+pub struct ManagerApproval {
+    pub username: String,
+    pub pin: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAdjustment {
+    pub reason_code: String,
+    pub note: Option<String>,
+    /// Lines and quantities to take back; every remaining line when omitted.
+    pub items: Option<Vec<AdjustmentLine>>,
+    /// Refunds only: which payments to pay back through. Most recent first when omitted.
+    pub tenders: Option<Vec<RefundTender>>,
+    pub approval: Option<ManagerApproval>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -57,6 +619,19 @@ pub struct UpdateOrderStatus {
     pub status: String,
 }
 
+#[derive(Debug, Serialize, FromRow)]
+pub struct OrderStatusChange {
+    pub id: i32,
+    pub order_id: i32,
+    pub from_status: Option<String>,
+    pub to_status: String,
+    pub actor: Option<String>,
+    pub staff_id: Option<i32>,
+    pub changed_at: DateTime<Utc>,
+    /// Time spent in `to_status`; `None` while the order is still in it.
+    pub duration_seconds: Option<f64>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct OrderDetailed {
     pub order: Order,
@@ -70,6 +645,371 @@ pub struct OrderItemDetailed {
     pub menu_item_id: i32,
     pub quantity: i32,
     pub menu_name: String,
-    pub menu_price: BigDecimal,
+    /// Menu price when the order was placed, before modifiers.
+    pub menu_price: Money,
+    pub unit_price: Money,
+    pub line_total: Money,
+    pub tax_rate: BigDecimal,
+    pub tax_inclusive: bool,
+    pub tax_amount: Money,
     pub menu_image: Option<String>,
-}
\ No newline at end of file
+    pub station_id: Option<i32>,
+    pub status: String,
+    pub note: Option<String>,
+    pub modifiers: Vec<OrderItemModifier>,
+}
+
+/// A staff account as returned by the API; credential hashes never leave the database.
+#[derive(Debug, Serialize, FromRow)]
+pub struct Staff {
+    pub id: i32,
+    pub name: String,
+    pub username: String,
+    pub active: bool,
+    pub has_pin: bool,
+    pub has_password: bool,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateStaff {
+    pub name: String,
+    pub username: String,
+    pub pin: Option<String>,
+    pub password: Option<String>,
+    /// Defaults to `cashier` when empty.
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// Partial update; omitted fields are left as they are.
+#[derive(Debug, Deserialize)]
+pub struct UpdateStaff {
+    pub name: Option<String>,
+    pub pin: Option<String>,
+    pub password: Option<String>,
+    pub active: Option<bool>,
+}
+
+/// Replaces every role the staff member holds.
+#[derive(Debug, Deserialize)]
+pub struct UpdateStaffRoles {
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub pin: Option<String>,
+    pub password: Option<String>,
+}
+
+/// One entry of the sync change log. Deletions (including soft deletes) carry no data.
+#[derive(Debug, Serialize)]
+pub struct SyncChange {
+    pub change_id: i64,
+    pub entity: String,
+    pub id: i32,
+    pub op: String,
+    pub data: Option<serde_json::Value>,
+}
+
+/// A change made on a client while it was offline, sent through `POST /sync/push`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum SyncMutation {
+    /// Needs `client_order_id`, so a resent push can't create the order twice.
+    #[serde(rename = "order.create")]
+    CreateOrder(CreateOrder),
+    #[serde(rename = "order.status")]
+    OrderStatus { order_id: i32, status: String },
+    #[serde(rename = "menu_item.availability")]
+    MenuItemAvailability {
+        id: i32,
+        available: bool,
+        /// The client's cursor when it made the change; newer server changes win.
+        base_change_id: Option<i64>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncPush {
+    pub mutations: Vec<SyncMutation>,
+}
+
+/// A closed business day. `report` is the `SalesReport` as it stood at closing.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ZReport {
+    pub id: i32,
+    pub business_date: NaiveDate,
+    pub period_start: Option<DateTime<Utc>>,
+    pub period_end: DateTime<Utc>,
+    pub opening_float: Money,
+    pub counted_cash: Option<Money>,
+    pub float_left: Money,
+    pub closed_by: Option<i32>,
+    pub report: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CloseDay {
+    /// Cash counted in the drawer at close.
+    pub counted_cash: Option<Money>,
+    /// Cash left in the drawer as tomorrow's opening float; defaults to none.
+    pub float_left: Option<Money>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DrawerOpening {
+    pub id: i32,
+    pub staff_id: i32,
+    pub staff_name: String,
+    /// sale, refund, no_sale, float or count.
+    pub reason: String,
+    pub order_id: Option<i32>,
+    pub note: Option<String>,
+    pub opened_at: DateTime<Utc>,
+    /// The Z report the opening was closed into; `None` for the open day.
+    pub z_report_id: Option<i32>,
+    /// Why the drawer didn't open after all, e.g. the printer was offline.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenDrawer {
+    pub reason: String,
+    pub order_id: Option<i32>,
+    /// Required for `no_sale`.
+    pub note: Option<String>,
+}
+
+/// Body of `POST /drawer/openings/:id/failed`.
+#[derive(Debug, Deserialize)]
+pub struct DrawerKickFailed {
+    pub error: Option<String>,
+}
+
+/// `?z_report_id=3&staff_id=2`; without `z_report_id`, openings since the last close.
+#[derive(Debug, Deserialize)]
+pub struct DrawerOpeningQuery {
+    pub z_report_id: Option<i32>,
+    pub staff_id: Option<i32>,
+}
+
+/// How receipts are laid out; see the `receipt_templates` migration.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReceiptTemplate {
+    pub id: i32,
+    pub name: String,
+    pub is_default: bool,
+    /// 58 or 80 (mm).
+    pub paper_width: i32,
+    pub store_name: String,
+    pub store_address: Option<String>,
+    pub tax_id: Option<String>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub currency_symbol: String,
+    pub locale: String,
+    pub qr_code: Option<String>,
+    pub logo_width: Option<i32>,
+    pub logo_height: Option<i32>,
+    /// Base64 of the 1-bit logo raster.
+    pub logo_data: Option<String>,
+    pub labels: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptTemplatePayload {
+    pub name: String,
+    #[serde(default)]
+    pub is_default: bool,
+    #[serde(default = "default_paper_width")]
+    pub paper_width: i32,
+    #[serde(default)]
+    pub store_name: String,
+    pub store_address: Option<String>,
+    pub tax_id: Option<String>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    #[serde(default = "default_currency_symbol")]
+    pub currency_symbol: String,
+    #[serde(default = "default_locale")]
+    pub locale: String,
+    pub qr_code: Option<String>,
+    pub logo_width: Option<i32>,
+    pub logo_height: Option<i32>,
+    pub logo_data: Option<String>,
+    /// Wording overrides keyed by `order`, `token`, `tax_id`, `subtotal`, `tax`, `total`.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+}
+
+fn default_paper_width() -> i32 {
+    80
+}
+
+fn default_currency_symbol() -> String {
+    "$".to_string()
+}
+
+fn default_locale() -> String {
+    "en-US".to_string()
+}
+
+/// A job for one of the tills' printers; see the `print_jobs` migration.
+#[derive(Debug, Serialize, FromRow)]
+pub struct PrintJob {
+    pub id: i32,
+    pub printer: String,
+    pub kind: String,
+    pub order_id: Option<i32>,
+    pub payload: serde_json::Value,
+    /// queued, claimed, printed or failed.
+    pub status: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// When a queued job may next be claimed.
+    pub available_at: DateTime<Utc>,
+    pub claimed_by: Option<String>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub requested_by: Option<i32>,
+    pub printed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The station a kitchen ticket is for.
+    pub station_id: Option<i32>,
+}
+
+/// Body of `POST /orders/:id/kitchen-tickets`; every station with a printer unless one is named.
+#[derive(Debug, Default, Deserialize)]
+pub struct ReprintKitchenTickets {
+    pub station_id: Option<i32>,
+}
+
+/// Body of `POST /print/:order_id`; prints on `receipt` unless another printer is named.
+#[derive(Debug, Default, Deserialize)]
+pub struct QueuePrint {
+    pub printer: Option<String>,
+}
+
+/// `?status=failed&printer=kitchen&order_id=12`; newest first.
+#[derive(Debug, Deserialize)]
+pub struct PrintJobQuery {
+    pub status: Option<String>,
+    pub printer: Option<String>,
+    pub order_id: Option<i32>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClaimPrintJob {
+    /// Printers the till drives.
+    pub printers: Vec<String>,
+    /// Stable name for the till, reported back with the result.
+    pub client: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrintJobResult {
+    pub client: String,
+    pub ok: bool,
+    /// Why the attempt failed, when `ok` is false.
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Money {
+        amount.parse().unwrap()
+    }
+
+    #[test]
+    fn holds_two_decimal_places() {
+        assert_eq!(money("12.5").to_string(), "12.50");
+        assert_eq!(money("3").to_string(), "3.00");
+        assert_eq!(Money::zero().to_string(), "0.00");
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(money("0.125").to_string(), "0.13");
+        assert_eq!(money("0.135").to_string(), "0.14");
+        assert_eq!(money("-0.125").to_string(), "-0.13");
+        assert_eq!(money("2.3449").to_string(), "2.34");
+    }
+
+    #[test]
+    fn percentages_round_once() {
+        let rate = BigDecimal::from_str("8.25").unwrap();
+        assert_eq!(money("10.30").percent(&rate), money("0.85"));
+        assert_eq!(money("10.825").included_percent(&BigDecimal::from(10)), money("0.98"));
+        assert_eq!(money("1.99").times(3), money("5.97"));
+    }
+
+    #[test]
+    fn serializes_as_a_string() {
+        assert_eq!(serde_json::to_string(&money("1234.5")).unwrap(), r#""1234.50""#);
+        assert_eq!(serde_json::to_string(&-money("0.1")).unwrap(), r#""-0.10""#);
+    }
+
+    #[test]
+    fn deserializes_strings_and_numbers() {
+        let parse = |json: &str| serde_json::from_str::<Money>(json).map(|m| m.to_string());
+        assert_eq!(parse(r#""12.50""#).unwrap(), "12.50");
+        assert_eq!(parse(r#"" 7.1 ""#).unwrap(), "7.10");
+        assert_eq!(parse("5").unwrap(), "5.00");
+        assert_eq!(parse("0.1").unwrap(), "0.10");
+        assert_eq!(parse("2.675").unwrap(), "2.68");
+        assert!(parse(r#""twelve""#).is_err());
+        assert!(parse("null").is_err());
+    }
+
+    #[test]
+    fn menu_item_updates_leave_availability_alone_unless_given() {
+        let update: UpdateMenuItem = serde_json::from_str(r#"{"name": "Fries", "price": "3.5"}"#).unwrap();
+        assert_eq!(update.price, money("3.50"));
+        assert_eq!(update.available, None);
+        assert_eq!(update.tax_rate_id, None);
+
+        let update: UpdateMenuItem =
+            serde_json::from_str(r#"{"name": "Fries", "price": 3, "available": false, "tax_rate_id": 2}"#).unwrap();
+        assert_eq!(update.available, Some(false));
+        assert_eq!(update.tax_rate_id, Some(2));
+
+        assert!(serde_json::from_str::<UpdateMenuItem>(r#"{"name": "Fries"}"#).is_err());
+    }
+
+    #[test]
+    fn sync_mutations_are_tagged_by_type() {
+        let push: SyncPush = serde_json::from_str(r#"{"mutations": [
+            {"type": "order.create", "source": "pos", "items": [{"menu_item_id": 1, "quantity": 2}], "client_order_id": "c1"},
+            {"type": "order.status", "order_id": 5, "status": "ready"},
+            {"type": "menu_item.availability", "id": 3, "available": false}
+        ]}"#).unwrap();
+
+        match &push.mutations[..] {
+            [
+                SyncMutation::CreateOrder(order),
+                SyncMutation::OrderStatus { order_id: 5, status },
+                SyncMutation::MenuItemAvailability { id: 3, available: false, base_change_id: None },
+            ] => {
+                assert_eq!(order.client_order_id.as_deref(), Some("c1"));
+                assert_eq!(order.items[0].quantity, 2);
+                assert_eq!(status, "ready");
+            }
+            other => panic!("unexpected mutations: {:?}", other),
+        }
+
+        assert!(serde_json::from_str::<SyncMutation>(r#"{"type": "order.delete", "order_id": 5}"#).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use crate::auth::AuthStaff;
+
+/// Something a staff member may be allowed to do. Routes are guarded with
+/// `require_permission`; finer checks (like which status an order may move to)
+/// happen in the handler with `AuthStaff::require`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Permission {
+    #[serde(rename = "orders.view")]
+    OrdersView,
+    /// Move orders to `preparing` or `ready`.
+    #[serde(rename = "orders.prepare")]
+    OrdersPrepare,
+    /// Hand orders over (`completed`).
+    #[serde(rename = "orders.complete")]
+    OrdersComplete,
+    /// Cancel an order before it is completed.
+    #[serde(rename = "orders.void")]
+    OrdersVoid,
+    #[serde(rename = "orders.refund")]
+    OrdersRefund,
+    /// Take cash, card and other tenders against an order.
+    #[serde(rename = "payments.take")]
+    PaymentsTake,
+    #[serde(rename = "queue.manage")]
+    QueueManage,
+    #[serde(rename = "menu.edit")]
+    MenuEdit,
+    #[serde(rename = "tax.manage")]
+    TaxManage,
+    /// Receipt printer and cash drawer.
+    #[serde(rename = "peripherals.use")]
+    PeripheralsUse,
+    /// X reports and closing the day (Z reports).
+    #[serde(rename = "reports.view")]
+    ReportsView,
+    /// Receipt templates: store details, logo and wording on printed receipts.
+    #[serde(rename = "receipts.manage")]
+    ReceiptsManage,
+    #[serde(rename = "staff.manage")]
+    StaffManage,
+}
+
+impl Permission {
+    pub const ALL: &'static [Permission] = &[
+        Permission::OrdersView,
+        Permission::OrdersPrepare,
+        Permission::OrdersComplete,
+        Permission::OrdersVoid,
+        Permission::OrdersRefund,
+        Permission::PaymentsTake,
+        Permission::QueueManage,
+        Permission::MenuEdit,
+        Permission::TaxManage,
+        Permission::PeripheralsUse,
+        Permission::ReportsView,
+        Permission::ReceiptsManage,
+        Permission::StaffManage,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Permission::OrdersView => "orders.view",
+            Permission::OrdersPrepare => "orders.prepare",
+            Permission::OrdersComplete => "orders.complete",
+            Permission::OrdersVoid => "orders.void",
+            Permission::OrdersRefund => "orders.refund",
+            Permission::PaymentsTake => "payments.take",
+            Permission::QueueManage => "queue.manage",
+            Permission::MenuEdit => "menu.edit",
+            Permission::TaxManage => "tax.manage",
+            Permission::PeripheralsUse => "peripherals.use",
+            Permission::ReportsView => "reports.view",
+            Permission::ReceiptsManage => "receipts.manage",
+            Permission::StaffManage => "staff.manage",
+        }
+    }
+}
+
+pub const ROLES: &[&str] = &["cashier", "kitchen", "manager", "admin"];
+
+/// What each role grants. A staff member holding several roles gets the union.
+pub fn role_permissions(role: &str) -> &'static [Permission] {
+    use Permission::*;
+    match role {
+        "kitchen" => &[OrdersView, OrdersPrepare, QueueManage],
+        "cashier" => &[OrdersView, OrdersPrepare, OrdersComplete, PaymentsTake, QueueManage, PeripheralsUse],
+        "manager" => &[
+            OrdersView, OrdersPrepare, OrdersComplete, OrdersVoid, OrdersRefund,
+            PaymentsTake, QueueManage, MenuEdit, TaxManage, PeripheralsUse, ReportsView, ReceiptsManage,
+        ],
+        "admin" => Permission::ALL,
+        _ => &[],
+    }
+}
+
+/// The permission needed to move an order into `status`.
+pub fn status_permission(status: &str) -> Permission {
+    match status {
+        "preparing" | "ready" => Permission::OrdersPrepare,
+        "completed" => Permission::OrdersComplete,
+        _ => Permission::OrdersVoid,
+    }
+}
+
+/// A 403 that tells the client which permission was missing.
+#[derive(Debug)]
+pub struct Forbidden {
+    pub permission: Permission,
+    pub roles: Vec<String>,
+    /// The action can go ahead if a manager approves it with their PIN.
+    pub approval_required: bool,
+}
+
+impl Forbidden {
+    /// The 403 body, also used for mutations `/sync/push` refuses.
+    pub fn body(&self) -> serde_json::Value {
+        let message = if self.approval_required {
+            format!("This action needs approval from someone with the '{}' permission", self.permission.as_str())
+        } else {
+            format!("This action requires the '{}' permission", self.permission.as_str())
+        };
+        json!({
+            "error": "forbidden",
+            "message": message,
+            "required_permission": self.permission,
+            "roles": self.roles,
+            "approval_required": self.approval_required,
+        })
+    }
+}
+
+impl IntoResponse for Forbidden {
+    fn into_response(self) -> Response {
+        (StatusCode::FORBIDDEN, Json(self.body())).into_response()
+    }
+}
+
+/// Route guard, used as `middleware::from_fn_with_state(Permission::MenuEdit, require_permission)`.
+/// Must sit inside `auth::require_staff`.
+pub async fn require_permission(
+    State(permission): State<Permission>,
+    staff: AuthStaff,
+    req: Request,
+    next: Next,
+) -> Result<Response, Forbidden> {
+    staff.require(permission)?;
+    Ok(next.run(req).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Permission::*;
+
+    fn grants(role: &str, permission: Permission) -> bool {
+        role_permissions(role).contains(&permission)
+    }
+
+    #[test]
+    fn admins_can_do_everything() {
+        assert_eq!(role_permissions("admin"), Permission::ALL);
+    }
+
+    #[test]
+    fn each_role_can_do_everything_the_one_below_can() {
+        for pair in ["kitchen", "cashier", "manager", "admin"].windows(2) {
+            let (lower, higher) = (pair[0], pair[1]);
+            for permission in role_permissions(lower) {
+                assert!(grants(higher, *permission), "{} lacks {} that {} has", higher, permission.as_str(), lower);
+            }
+        }
+    }
+
+    #[test]
+    fn only_managers_and_admins_void_refund_or_report() {
+        for permission in [OrdersVoid, OrdersRefund, ReportsView, MenuEdit] {
+            assert!(!grants("kitchen", permission));
+            assert!(!grants("cashier", permission));
+            assert!(grants("manager", permission));
+        }
+        assert!(!grants("manager", StaffManage));
+        assert!(!grants("kitchen", PaymentsTake));
+    }
+
+    #[test]
+    fn unknown_roles_grant_nothing() {
+        assert!(role_permissions("owner").is_empty());
+        assert!(role_permissions("").is_empty());
+    }
+
+    #[test]
+    fn permission_names_match_their_serde_names() {
+        for permission in Permission::ALL {
+            assert_eq!(serde_json::to_value(permission).unwrap(), permission.as_str());
+        }
+    }
+
+    #[test]
+    fn status_changes_need_the_matching_permission() {
+        assert_eq!(status_permission("preparing"), OrdersPrepare);
+        assert_eq!(status_permission("ready"), OrdersPrepare);
+        assert_eq!(status_permission("completed"), OrdersComplete);
+        assert_eq!(status_permission("cancelled"), OrdersVoid);
+    }
+}
@@ -0,0 +1,117 @@
+use bigdecimal::BigDecimal;
+use crate::models::Money;
+
+/// Tax settings applied to an order line.
+#[derive(Debug, Clone)]
+pub struct LineTax {
+    /// Percentage, e.g. `8.25`.
+    pub rate: BigDecimal,
+    pub inclusive: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct LineTotals {
+    pub unit_price: Money,
+    pub line_total: Money,
+    pub tax_amount: Money,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OrderTotals {
+    pub subtotal: Money,
+    pub tax_total: Money,
+    pub total: Money,
+}
+
+/// Prices one order line. The unit price is the base price plus every modifier delta;
+/// inclusive tax is backed out of the line total, exclusive tax is computed on top of it.
+pub fn price_line<'a>(
+    base_price: &Money,
+    modifier_deltas: impl IntoIterator<Item = &'a Money>,
+    quantity: i32,
+    tax: Option<&LineTax>,
+) -> LineTotals {
+    let unit_price = modifier_deltas.into_iter().fold(base_price.clone(), |acc, d| &acc + d);
+    let line_total = unit_price.times(quantity);
+
+    let tax_amount = match tax {
+        Some(t) if t.inclusive => line_total.included_percent(&t.rate),
+        Some(t) => line_total.percent(&t.rate),
+        None => Money::zero(),
+    };
+
+    LineTotals { unit_price, line_total, tax_amount }
+}
+
+impl OrderTotals {
+    /// Adds a priced line. Only exclusive tax increases the amount due.
+    pub fn add_line(&mut self, line: &LineTotals, inclusive: bool) {
+        self.subtotal += &line.line_total;
+        self.tax_total += &line.tax_amount;
+        self.total += &line.line_total;
+        if !inclusive {
+            self.total += &line.tax_amount;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn money(amount: &str) -> Money {
+        amount.parse().unwrap()
+    }
+
+    fn tax(rate: &str, inclusive: bool) -> LineTax {
+        LineTax { rate: BigDecimal::from_str(rate).unwrap(), inclusive }
+    }
+
+    #[test]
+    fn unit_price_adds_modifier_deltas() {
+        let deltas = [money("1.50"), money("-0.25")];
+        let line = price_line(&money("4.00"), &deltas, 3, None);
+        assert_eq!(line.unit_price, money("5.25"));
+        assert_eq!(line.line_total, money("15.75"));
+        assert_eq!(line.tax_amount, money("0.00"));
+    }
+
+    #[test]
+    fn exclusive_tax_rounds_half_away_from_zero() {
+        // 8.25% of 10.30 is 0.84975
+        let line = price_line(&money("10.30"), [], 1, Some(&tax("8.25", false)));
+        assert_eq!(line.tax_amount, money("0.85"));
+        // 5% of 0.10 is exactly half a cent
+        let line = price_line(&money("0.10"), [], 1, Some(&tax("5", false)));
+        assert_eq!(line.tax_amount, money("0.01"));
+    }
+
+    #[test]
+    fn inclusive_tax_is_backed_out_of_the_line_total() {
+        // 19% included in 11.90 is 1.90
+        let line = price_line(&money("5.95"), [], 2, Some(&tax("19", true)));
+        assert_eq!(line.line_total, money("11.90"));
+        assert_eq!(line.tax_amount, money("1.90"));
+    }
+
+    #[test]
+    fn tax_is_rounded_per_line_not_per_unit() {
+        // 3 x 0.35 at 10% is 0.105 of tax, not 3 x 0.04
+        let line = price_line(&money("0.35"), [], 3, Some(&tax("10", false)));
+        assert_eq!(line.tax_amount, money("0.11"));
+    }
+
+    #[test]
+    fn only_exclusive_tax_adds_to_the_total() {
+        let mut totals = OrderTotals::default();
+        let exclusive = price_line(&money("10.00"), [], 1, Some(&tax("10", false)));
+        let inclusive = price_line(&money("11.00"), [], 1, Some(&tax("10", true)));
+        totals.add_line(&exclusive, false);
+        totals.add_line(&inclusive, true);
+
+        assert_eq!(totals.subtotal, money("21.00"));
+        assert_eq!(totals.tax_total, money("2.00"));
+        assert_eq!(totals.total, money("22.00"));
+    }
+}
@@ -0,0 +1,195 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::FromRow;
+use crate::kitchen::{self, StationTicket};
+use crate::models::{Money, PrintJob};
+
+/// Printer names are configured on the tills and end up in job queries, so they stay plain.
+pub fn valid_printer_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+/// How print jobs are retried, read once at startup.
+#[derive(Debug, Clone)]
+pub struct PrintSettings {
+    /// Attempts a job gets before it is marked failed.
+    pub max_attempts: i32,
+    /// A claim not reported back within this many seconds counts as a failed attempt,
+    /// so a till that crashes mid-job doesn't hold it forever.
+    pub claim_timeout_secs: i32,
+    /// Seconds a failed job waits before its next attempt, times the attempts so far.
+    pub retry_delay_secs: i32,
+    /// Where receipts asked for at the kiosk come out.
+    pub kiosk_printer: String,
+}
+
+impl PrintSettings {
+    /// Reads `PRINT_MAX_ATTEMPTS` (default 3), `PRINT_CLAIM_TIMEOUT_SECS` (default 60),
+    /// `PRINT_RETRY_DELAY_SECS` (default 10) and `KIOSK_PRINTER` (default `receipt`, the
+    /// counter till's receipt printer).
+    pub fn from_env() -> Self {
+        let max_attempts = std::env::var("PRINT_MAX_ATTEMPTS")
+            .ok()
+            .map(|v| v.parse::<i32>().expect("PRINT_MAX_ATTEMPTS must be a number"))
+            .unwrap_or(3);
+        assert!(max_attempts > 0, "PRINT_MAX_ATTEMPTS must be at least 1");
+
+        let claim_timeout_secs = std::env::var("PRINT_CLAIM_TIMEOUT_SECS")
+            .ok()
+            .map(|v| v.parse::<i32>().expect("PRINT_CLAIM_TIMEOUT_SECS must be a number of seconds"))
+            .unwrap_or(60);
+
+        let retry_delay_secs = std::env::var("PRINT_RETRY_DELAY_SECS")
+            .ok()
+            .map(|v| v.parse::<i32>().expect("PRINT_RETRY_DELAY_SECS must be a number of seconds"))
+            .unwrap_or(10);
+
+        let kiosk_printer = std::env::var("KIOSK_PRINTER").unwrap_or_else(|_| "receipt".to_string());
+        assert!(valid_printer_name(&kiosk_printer), "KIOSK_PRINTER must be a printer name like 'receipt'");
+
+        PrintSettings { max_attempts, claim_timeout_secs, retry_delay_secs, kiosk_printer }
+    }
+}
+
+/// One line on a receipt, as sold.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReceiptLine {
+    pub name: String,
+    pub qty: i32,
+    /// Line total, not the unit price.
+    pub price: Money,
+}
+
+/// Payload of a `receipt` job. Tills format `placed_at` for the store's locale.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ReceiptJob {
+    pub order_id: i32,
+    pub token_label: Option<String>,
+    pub placed_at: DateTime<Utc>,
+    #[sqlx(skip)]
+    pub items: Vec<ReceiptLine>,
+    pub subtotal: Money,
+    pub tax_total: Money,
+    pub total: Money,
+}
+
+/// The receipt for an order as it stands, or `None` if there is no such order.
+pub async fn receipt_job(conn: &mut sqlx::PgConnection, order_id: i32) -> Result<Option<ReceiptJob>, sqlx::Error> {
+    let Some(mut receipt) = sqlx::query_as::<_, ReceiptJob>(
+        "SELECT id AS order_id, token_label, created_at AS placed_at, subtotal, tax_total, total FROM orders WHERE id = $1"
+    )
+    .bind(order_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    receipt.items = sqlx::query_as::<_, ReceiptLine>(
+        r#"
+        SELECT mi.name, oi.quantity AS qty, oi.line_total AS price
+        FROM order_items oi
+        JOIN menu_items mi ON mi.id = oi.menu_item_id
+        WHERE oi.order_id = $1
+        ORDER BY oi.id
+        "#
+    )
+    .bind(order_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(Some(receipt))
+}
+
+/// Payload of a `kitchen_ticket` job: one station's part of an order.
+#[derive(Debug, Serialize)]
+pub struct KitchenTicketJob {
+    #[serde(flatten)]
+    pub ticket: StationTicket,
+    /// Printed again on request rather than when the order came in.
+    pub reprint: bool,
+    /// The station printer's roll width in mm.
+    pub paper_width: i32,
+}
+
+/// Queues kitchen tickets for an order: one per station with lines on it and a printer.
+/// New orders only print at stations with `auto_print` on; reprints go to any station
+/// with a printer, or just `station_id`.
+pub async fn queue_kitchen_tickets(
+    conn: &mut sqlx::PgConnection,
+    settings: &PrintSettings,
+    order_id: i32,
+    station_id: Option<i32>,
+    reprint: bool,
+    requested_by: Option<i32>,
+) -> Result<Vec<PrintJob>, sqlx::Error> {
+    let printers: Vec<(i32, String, i32)> = sqlx::query_as(
+        r#"
+        SELECT id, printer, paper_width FROM kitchen_stations
+        WHERE printer IS NOT NULL AND active AND (auto_print OR $1) AND ($2::int IS NULL OR id = $2)
+        "#
+    )
+    .bind(reprint)
+    .bind(station_id)
+    .fetch_all(&mut *conn)
+    .await?;
+    if printers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let tickets = kitchen::load_tickets(&mut *conn, None, Some(order_id)).await?;
+    let mut jobs = Vec::new();
+    for ticket in tickets {
+        let Some((station_id, printer, paper_width)) = printers.iter().find(|(id, _, _)| *id == ticket.station_id) else {
+            continue;
+        };
+        if ticket.items.is_empty() {
+            continue;
+        }
+        let payload = serde_json::json!(KitchenTicketJob { ticket, reprint, paper_width: *paper_width });
+        let job = NewPrintJob {
+            printer,
+            kind: "kitchen_ticket",
+            order_id: Some(order_id),
+            station_id: Some(*station_id),
+            payload,
+            requested_by,
+        };
+        jobs.push(enqueue(&mut *conn, settings, job).await?);
+    }
+    Ok(jobs)
+}
+
+pub struct NewPrintJob<'a> {
+    pub printer: &'a str,
+    pub kind: &'a str,
+    pub order_id: Option<i32>,
+    pub station_id: Option<i32>,
+    pub payload: serde_json::Value,
+    /// `None` for jobs a kiosk or the server asked for.
+    pub requested_by: Option<i32>,
+}
+
+/// Queues a job. Callers announce it with `print.queued` once their transaction commits.
+pub async fn enqueue(
+    conn: &mut sqlx::PgConnection,
+    settings: &PrintSettings,
+    job: NewPrintJob<'_>,
+) -> Result<PrintJob, sqlx::Error> {
+    sqlx::query_as::<_, PrintJob>(
+        r#"
+        INSERT INTO print_jobs (printer, kind, order_id, station_id, payload, max_attempts, requested_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#
+    )
+    .bind(job.printer)
+    .bind(job.kind)
+    .bind(job.order_id)
+    .bind(job.station_id)
+    .bind(job.payload)
+    .bind(settings.max_attempts)
+    .bind(job.requested_by)
+    .fetch_one(conn)
+    .await
+}
@@ -0,0 +1,316 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use crate::models::Money;
+
+/// Takings for one trading period: the open shift for an X report, a closed business day
+/// for a Z report.
+///
+/// Sales figures exclude tax. Voids and refunds are negative, so `net_sales` is simply
+/// their sum with `gross_sales`. Which period a row belongs to is decided by the Z report
+/// it was stamped with when the day was closed, not by its timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SalesReport {
+    pub period_start: Option<DateTime<Utc>>,
+    pub period_end: DateTime<Utc>,
+    pub currency: String,
+    pub order_count: i64,
+    pub gross_sales: Money,
+    /// There are no discounts yet; reported so the layout doesn't change when there are.
+    pub discounts: Money,
+    pub voids: Money,
+    pub void_count: i64,
+    pub refunds: Money,
+    pub refund_count: i64,
+    pub net_sales: Money,
+    pub tax: Money,
+    /// Net sales plus tax.
+    pub total: Money,
+    pub tenders: Vec<TenderTotals>,
+    pub sources: Vec<SourceTotals>,
+    pub cash: CashSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TenderTotals {
+    pub tender: String,
+    pub payment_count: i64,
+    pub taken: Money,
+    /// Negative.
+    pub refunded: Money,
+    pub net: Money,
+}
+
+/// Sales by where the order came from (kiosk, pos, online).
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SourceTotals {
+    pub source: String,
+    pub order_count: i64,
+    pub gross_sales: Money,
+    pub net_sales: Money,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CashSummary {
+    /// Float left in the drawer when the previous day was closed.
+    pub opening_float: Money,
+    pub cash_taken: Money,
+    /// Negative.
+    pub cash_refunded: Money,
+    pub expected_in_drawer: Money,
+    pub counted: Option<Money>,
+    /// Counted minus expected; negative when the drawer is short.
+    pub variance: Option<Money>,
+    /// Times the drawer was opened, and how many of those were for no sale.
+    #[serde(default)]
+    pub drawer_openings: i64,
+    #[serde(default)]
+    pub no_sale_openings: i64,
+}
+
+#[derive(Debug, FromRow)]
+struct EntryTotals {
+    entry_type: String,
+    entry_count: i64,
+    net: Money,
+    tax: Money,
+}
+
+/// Builds the report for rows stamped with `z_report_id`, or for the open period when it
+/// is `None`. Run inside the closing transaction so the figures match what was stamped.
+pub async fn build_report(
+    conn: &mut sqlx::PgConnection,
+    currency: &str,
+    z_report_id: Option<i32>,
+    period_start: Option<DateTime<Utc>>,
+    period_end: DateTime<Utc>,
+    opening_float: Money,
+) -> Result<SalesReport, sqlx::Error> {
+    let entries = sqlx::query_as::<_, EntryTotals>(
+        r#"
+        SELECT entry_type,
+               COUNT(*) AS entry_count,
+               COALESCE(SUM(total - tax_total), 0) AS net,
+               COALESCE(SUM(tax_total), 0) AS tax
+        FROM sales_entries
+        WHERE z_report_id IS NOT DISTINCT FROM $1
+        GROUP BY entry_type
+        "#
+    )
+    .bind(z_report_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let sources = sqlx::query_as::<_, SourceTotals>(
+        r#"
+        SELECT o.source,
+               COUNT(*) FILTER (WHERE e.entry_type = 'sale') AS order_count,
+               COALESCE(SUM(e.total - e.tax_total) FILTER (WHERE e.entry_type = 'sale'), 0) AS gross_sales,
+               COALESCE(SUM(e.total - e.tax_total), 0) AS net_sales
+        FROM sales_entries e
+        JOIN orders o ON o.id = e.order_id
+        WHERE e.z_report_id IS NOT DISTINCT FROM $1
+        GROUP BY o.source
+        ORDER BY o.source
+        "#
+    )
+    .bind(z_report_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let tenders = sqlx::query_as::<_, TenderTotals>(
+        r#"
+        SELECT tender,
+               COUNT(*) FILTER (WHERE refund_of IS NULL) AS payment_count,
+               COALESCE(SUM(amount) FILTER (WHERE refund_of IS NULL), 0) AS taken,
+               COALESCE(SUM(amount) FILTER (WHERE refund_of IS NOT NULL), 0) AS refunded,
+               COALESCE(SUM(amount), 0) AS net
+        FROM payments
+        WHERE z_report_id IS NOT DISTINCT FROM $1
+        GROUP BY tender
+        ORDER BY tender
+        "#
+    )
+    .bind(z_report_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let (drawer_openings, no_sale_openings): (i64, i64) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*), COUNT(*) FILTER (WHERE reason = 'no_sale')
+        FROM drawer_openings
+        WHERE z_report_id IS NOT DISTINCT FROM $1 AND error IS NULL
+        "#
+    )
+    .bind(z_report_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut report = summarize(currency, period_start, period_end, opening_float, entries, tenders, sources);
+    report.cash.drawer_openings = drawer_openings;
+    report.cash.no_sale_openings = no_sale_openings;
+    Ok(report)
+}
+
+/// Puts the report together from the per-entry-type and per-tender totals.
+fn summarize(
+    currency: &str,
+    period_start: Option<DateTime<Utc>>,
+    period_end: DateTime<Utc>,
+    opening_float: Money,
+    entries: Vec<EntryTotals>,
+    tenders: Vec<TenderTotals>,
+    sources: Vec<SourceTotals>,
+) -> SalesReport {
+    let mut report = SalesReport {
+        period_start,
+        period_end,
+        currency: currency.to_string(),
+        order_count: 0,
+        gross_sales: Money::zero(),
+        discounts: Money::zero(),
+        voids: Money::zero(),
+        void_count: 0,
+        refunds: Money::zero(),
+        refund_count: 0,
+        net_sales: Money::zero(),
+        tax: Money::zero(),
+        total: Money::zero(),
+        tenders,
+        sources,
+        cash: CashSummary {
+            opening_float: Money::zero(),
+            cash_taken: Money::zero(),
+            cash_refunded: Money::zero(),
+            expected_in_drawer: Money::zero(),
+            counted: None,
+            variance: None,
+            drawer_openings: 0,
+            no_sale_openings: 0,
+        },
+    };
+
+    for entry in entries {
+        match entry.entry_type.as_str() {
+            "sale" => {
+                report.order_count = entry.entry_count;
+                report.gross_sales = entry.net;
+            }
+            "void" => {
+                report.void_count = entry.entry_count;
+                report.voids = entry.net;
+            }
+            "refund" => {
+                report.refund_count = entry.entry_count;
+                report.refunds = entry.net;
+            }
+            _ => continue,
+        }
+        report.tax += &entry.tax;
+    }
+    report.net_sales = &(&report.gross_sales + &report.voids) + &report.refunds;
+    report.total = &report.net_sales + &report.tax;
+
+    if let Some(cash) = report.tenders.iter().find(|t| t.tender == "cash") {
+        report.cash.cash_taken = cash.taken.clone();
+        report.cash.cash_refunded = cash.refunded.clone();
+    }
+    report.cash.expected_in_drawer = &(&opening_float + &report.cash.cash_taken) + &report.cash.cash_refunded;
+    report.cash.opening_float = opening_float;
+
+    report
+}
+
+impl SalesReport {
+    /// Records the drawer count taken when closing the day.
+    pub fn count_drawer(&mut self, counted: Money) {
+        self.cash.variance = Some(&counted - &self.cash.expected_in_drawer);
+        self.cash.counted = Some(counted);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Money {
+        amount.parse().unwrap()
+    }
+
+    fn entries(rows: &[(&str, i64, &str, &str)]) -> Vec<EntryTotals> {
+        rows.iter()
+            .map(|&(entry_type, entry_count, net, tax)| EntryTotals {
+                entry_type: entry_type.to_string(),
+                entry_count,
+                net: money(net),
+                tax: money(tax),
+            })
+            .collect()
+    }
+
+    fn tender(tender: &str, taken: &str, refunded: &str) -> TenderTotals {
+        TenderTotals {
+            tender: tender.to_string(),
+            payment_count: 1,
+            taken: money(taken),
+            refunded: money(refunded),
+            net: &money(taken) + &money(refunded),
+        }
+    }
+
+    /// 120.00 of sales, a 10.00 void and a 5.00 refund, with 40.00 of cash in and 5.50 back out.
+    fn day() -> SalesReport {
+        summarize(
+            "USD",
+            None,
+            Utc::now(),
+            money("100"),
+            entries(&[
+                ("sale", 12, "120.00", "12.00"),
+                ("void", 1, "-10.00", "-1.00"),
+                ("refund", 1, "-5.00", "-0.50"),
+            ]),
+            vec![tender("card", "92.00", "0"), tender("cash", "40.00", "-5.50")],
+            vec![],
+        )
+    }
+
+    #[test]
+    fn voids_and_refunds_come_off_gross_sales() {
+        let report = day();
+        assert_eq!(report.order_count, 12);
+        assert_eq!((report.void_count, report.refund_count), (1, 1));
+        assert_eq!(report.gross_sales, money("120.00"));
+        assert_eq!(report.net_sales, money("105.00"));
+        assert_eq!(report.tax, money("10.50"));
+        assert_eq!(report.total, money("115.50"));
+    }
+
+    #[test]
+    fn the_drawer_should_hold_the_float_plus_net_cash() {
+        let cash = day().cash;
+        assert_eq!(cash.opening_float, money("100.00"));
+        assert_eq!((cash.cash_taken, cash.cash_refunded), (money("40.00"), money("-5.50")));
+        assert_eq!(cash.expected_in_drawer, money("134.50"));
+        assert_eq!((cash.counted, cash.variance), (None, None));
+    }
+
+    #[test]
+    fn an_empty_period_reports_zeroes() {
+        let report = summarize("USD", None, Utc::now(), money("50"), vec![], vec![], vec![]);
+        assert_eq!(report.order_count, 0);
+        assert_eq!(report.total, Money::zero());
+        assert_eq!(report.cash.expected_in_drawer, money("50.00"));
+    }
+
+    #[test]
+    fn counting_the_drawer_records_the_variance() {
+        let mut report = day();
+        report.count_drawer(money("130.00"));
+        assert_eq!(report.cash.counted, Some(money("130.00")));
+        assert_eq!(report.cash.variance, Some(money("-4.50")));
+
+        report.count_drawer(money("135"));
+        assert_eq!(report.cash.variance, Some(money("0.50")));
+    }
+}
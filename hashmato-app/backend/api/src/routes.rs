@@ -1,33 +1,151 @@
 use axum::{
-    routing::{get, post, put},
+    middleware,
+    routing::{get, post, put, patch, delete},
     Router,
 };
-use crate::{controllers, AppState};
+use crate::permissions::{require_permission, Permission};
+use crate::{auth, controllers, AppState};
 
-pub fn routes() -> Router<AppState> {
-    Router::new()
+pub fn routes(state: AppState) -> Router<AppState> {
+    // Open to kiosks and customer-facing displays.
+    let public = Router::new()
         .route("/", get(controllers::root))
-     
+
+        .route("/auth/login", post(controllers::login))
+
         .route("/menu", get(controllers::list_menu))
-        .route("/menu", post(controllers::create_menu_item))
         .route("/menu/sync", get(controllers::sync_menu))
-        
-        .route("/orders", get(controllers::list_orders))
+        .route("/menu/tree", get(controllers::menu_tree))
+        .route("/menu/categories", get(controllers::list_categories))
+        .route("/menu/:id/modifiers", get(controllers::list_item_modifiers))
+
         .route("/orders", post(controllers::create_order))
+
+        .route("/queue", get(controllers::list_queue))
+
+        .route("/ws", get(controllers::ws_handler))
+
+        .route("/version", get(controllers::latest_update));
+
+    // Everything else needs a signed-in staff member holding the group's permission.
+    let account = Router::new()
+        .route("/auth/me", get(controllers::me));
+
+    let staff = Router::new()
+        .route("/roles", get(controllers::list_roles))
+        .route("/staff", get(controllers::list_staff))
+        .route("/staff", post(controllers::create_staff))
+        .route("/staff/:id", put(controllers::update_staff))
+        .route("/staff/:id/roles", put(controllers::set_staff_roles))
+        .route_layer(middleware::from_fn_with_state(Permission::StaffManage, require_permission));
+
+    let menu = Router::new()
+        .route("/menu", post(controllers::create_menu_item))
+        .route("/menu/:id", put(controllers::update_menu_item))
+        .route("/menu/:id", delete(controllers::delete_menu_item))
+        .route("/menu/:id/availability", patch(controllers::set_menu_item_availability))
+        .route("/menu/categories", post(controllers::create_category))
+        .route("/menu/categories/:id", put(controllers::update_category))
+        .route("/menu/categories/:id", delete(controllers::delete_category))
+        .route("/menu/:id/modifiers", post(controllers::create_modifier_group))
+        .route("/modifiers/groups/:id", put(controllers::update_modifier_group))
+        .route("/modifiers/groups/:id", delete(controllers::delete_modifier_group))
+        .route("/modifiers/groups/:id/options", post(controllers::create_modifier_option))
+        .route("/modifiers/options/:id", put(controllers::update_modifier_option))
+        .route("/modifiers/options/:id", delete(controllers::delete_modifier_option))
+        .route("/upload", post(controllers::upload_image))
+        .route("/stations", post(controllers::create_station))
+        .route("/stations/:id", put(controllers::update_station))
+        .route("/stations/:id", delete(controllers::delete_station))
+        .route_layer(middleware::from_fn_with_state(Permission::MenuEdit, require_permission));
+
+    let tax = Router::new()
+        .route("/tax-rates", get(controllers::list_tax_rates))
+        .route("/tax-rates", post(controllers::create_tax_rate))
+        .route("/tax-rates/:id", put(controllers::update_tax_rate))
+        .route("/tax-rates/:id", delete(controllers::delete_tax_rate))
+        .route_layer(middleware::from_fn_with_state(Permission::TaxManage, require_permission));
+
+    // The target status, and who may void or refund, are checked in the handlers.
+    let orders = Router::new()
+        .route("/orders", get(controllers::list_orders))
         .route("/orders/detailed", get(controllers::list_orders_detailed))
         .route("/orders/:id/detailed", get(controllers::get_order_detailed))
         .route("/orders/:id/status", put(controllers::update_order_status))
+        .route("/orders/:id/history", get(controllers::get_order_history))
         .route("/orders/sync", get(controllers::sync_orders))
+        .route("/orders/:id/void", post(controllers::void_order_items))
+        .route("/orders/:id/refunds", post(controllers::refund_order_items))
+        .route("/orders/:id/adjustments", get(controllers::list_order_adjustments))
+        .route("/adjustments/reasons", get(controllers::list_adjustment_reasons))
+        .route("/stations", get(controllers::list_stations))
+        .route("/stations/:id/tickets", get(controllers::list_station_tickets))
+        .route_layer(middleware::from_fn_with_state(Permission::OrdersView, require_permission));
 
-        .route("/queue", get(controllers::list_queue))
-        .route("/queue/broadcast", post(controllers::broadcast_queue))
+    let kitchen = Router::new()
+        .route("/order-items/:id/status", put(controllers::update_order_item_status))
+        .route("/orders/:id/kitchen-tickets", post(controllers::reprint_kitchen_tickets))
+        .route_layer(middleware::from_fn_with_state(Permission::OrdersPrepare, require_permission));
 
-        .route("/upload", post(controllers::upload_image))
+    let payments = Router::new()
+        .route("/orders/:id/payments", get(controllers::list_payments))
+        .route("/orders/:id/payments", post(controllers::record_payment))
+        .route_layer(middleware::from_fn_with_state(Permission::PaymentsTake, require_permission));
 
-        .route("/ws", get(controllers::ws_handler))
-  
+    // Individual mutations check their own permissions.
+    let sync = Router::new()
+        .route("/sync/push", post(controllers::sync_push));
+
+    let queue = Router::new()
+        .route("/queue/broadcast", post(controllers::broadcast_queue))
+        .route("/queue/:token/call", post(controllers::call_token))
+        .route("/queue/:token/recall", post(controllers::recall_token))
+        .route_layer(middleware::from_fn_with_state(Permission::QueueManage, require_permission));
+
+    let peripherals = Router::new()
         .route("/print/:order_id", post(controllers::print_receipt))
+        .route("/print-jobs", get(controllers::list_print_jobs))
+        .route("/print-jobs/claim", post(controllers::claim_print_job))
+        .route("/print-jobs/:id/result", post(controllers::report_print_job))
+        .route("/print-jobs/:id/retry", post(controllers::retry_print_job))
         .route("/drawer/open", post(controllers::open_drawer))
-  
-        .route("/version", get(controllers::latest_update))
-}
\ No newline at end of file
+        .route("/drawer/openings/:id/failed", post(controllers::report_drawer_kick_failure))
+        .route("/receipt-templates/default", get(controllers::get_default_receipt_template))
+        .route_layer(middleware::from_fn_with_state(Permission::PeripheralsUse, require_permission));
+
+    let receipts = Router::new()
+        .route("/receipt-templates", get(controllers::list_receipt_templates))
+        .route("/receipt-templates", post(controllers::create_receipt_template))
+        .route("/receipt-templates/:id", put(controllers::update_receipt_template))
+        .route("/receipt-templates/:id", delete(controllers::delete_receipt_template))
+        .route_layer(middleware::from_fn_with_state(Permission::ReceiptsManage, require_permission));
+
+    let reports = Router::new()
+        .route("/reports/x", get(controllers::x_report))
+        .route("/reports/z", get(controllers::list_z_reports))
+        .route("/reports/z", post(controllers::close_day))
+        .route("/reports/z/:id", get(controllers::get_z_report))
+        .route("/drawer/openings", get(controllers::list_drawer_openings))
+        .route("/reports/items", get(controllers::sales_by_item))
+        .route("/reports/hours", get(controllers::sales_by_hour))
+        .route("/reports/weekdays", get(controllers::sales_by_weekday))
+        .route("/reports/heatmap", get(controllers::sales_heatmap))
+        .route("/reports/sources", get(controllers::sales_by_source))
+        .route_layer(middleware::from_fn_with_state(Permission::ReportsView, require_permission));
+
+    let protected = account
+        .merge(staff)
+        .merge(menu)
+        .merge(tax)
+        .merge(orders)
+        .merge(kitchen)
+        .merge(payments)
+        .merge(sync)
+        .merge(queue)
+        .merge(peripherals)
+        .merge(receipts)
+        .merge(reports)
+        .route_layer(middleware::from_fn_with_state(state, auth::require_staff));
+
+    public.merge(protected)
+}
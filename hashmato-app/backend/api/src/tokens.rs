@@ -0,0 +1,167 @@
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// How customer-facing queue tokens are numbered.
+///
+/// Numbers restart every business day. A business day starts at `cutoff_hour` in
+/// `timezone`, so orders taken after midnight on a late shift still count towards
+/// the previous day. Each order source draws from its own counter and gets its own
+/// prefix, e.g. `K-012` for the kiosk and `P-045` for the POS.
+#[derive(Debug, Clone)]
+pub struct TokenSettings {
+    pub cutoff_hour: u32,
+    pub timezone: String,
+    prefixes: HashMap<String, String>,
+}
+
+impl TokenSettings {
+    /// Reads `BUSINESS_DAY_CUTOFF_HOUR` (default 4), `STORE_TIMEZONE` (an IANA name,
+    /// default UTC) and `TOKEN_PREFIXES` (e.g. `kiosk=K,pos=P,online=O`).
+    pub fn from_env() -> Self {
+        let cutoff_hour = std::env::var("BUSINESS_DAY_CUTOFF_HOUR")
+            .ok()
+            .map(|v| v.parse::<u32>().expect("BUSINESS_DAY_CUTOFF_HOUR must be an hour between 0 and 23"))
+            .unwrap_or(4);
+        assert!(cutoff_hour < 24, "BUSINESS_DAY_CUTOFF_HOUR must be an hour between 0 and 23");
+
+        let timezone = std::env::var("STORE_TIMEZONE").unwrap_or_else(|_| "UTC".to_string());
+
+        let prefixes = parse_prefixes(
+            &std::env::var("TOKEN_PREFIXES").unwrap_or_else(|_| "kiosk=K,pos=P,online=O".to_string()),
+        );
+
+        TokenSettings { cutoff_hour, timezone, prefixes }
+    }
+
+    /// Refuses to start with a `STORE_TIMEZONE` Postgres doesn't know, which would
+    /// otherwise only show up as a failed order.
+    pub async fn check_timezone(&self, pool: &sqlx::PgPool) {
+        let known: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+            .bind(&self.timezone)
+            .fetch_one(pool)
+            .await
+            .expect("Failed to check STORE_TIMEZONE");
+        assert!(known, "STORE_TIMEZONE must be an IANA time zone name like 'Europe/London', not '{}'", self.timezone);
+    }
+
+    /// Prefix for an order source; unknown sources use their first letter.
+    pub fn prefix_for(&self, source: &str) -> String {
+        let source = source.trim().to_lowercase();
+        self.prefixes.get(&source).cloned().unwrap_or_else(|| {
+            source.chars().next().map(|c| c.to_uppercase().to_string()).unwrap_or_default()
+        })
+    }
+}
+
+/// `kiosk=K,pos=P`: prefixes by order source. Prefixes are uppercased like every token
+/// label, so tokens can be called however the prefix was written.
+fn parse_prefixes(list: &str) -> HashMap<String, String> {
+    list.split(',')
+        .filter_map(|pair| {
+            let (source, prefix) = pair.split_once('=')?;
+            Some((source.trim().to_lowercase(), prefix.trim().to_uppercase()))
+        })
+        .collect()
+}
+
+/// When tokens nobody collects leave the queue.
+#[derive(Debug, Clone)]
+pub struct QueueSettings {
+    /// A ready token not collected this many minutes after its last call becomes a
+    /// no-show. 0 turns this off.
+    pub no_show_minutes: u32,
+}
+
+impl QueueSettings {
+    /// Reads `QUEUE_NO_SHOW_MINUTES` (default 15).
+    pub fn from_env() -> Self {
+        let no_show_minutes = std::env::var("QUEUE_NO_SHOW_MINUTES")
+            .ok()
+            .map(|v| v.parse::<u32>().expect("QUEUE_NO_SHOW_MINUTES must be a number of minutes"))
+            .unwrap_or(15);
+
+        QueueSettings { no_show_minutes }
+    }
+}
+
+pub fn format_token(prefix: &str, number: i32) -> String {
+    if prefix.is_empty() {
+        format!("{:03}", number)
+    } else {
+        format!("{}-{:03}", prefix, number)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AllocatedToken {
+    pub business_date: NaiveDate,
+    pub number: i32,
+    pub label: String,
+}
+
+/// Draws the next token for `source`. Must run inside the order transaction so the
+/// counter row stays locked until the order commits.
+pub async fn allocate_token(
+    conn: &mut sqlx::PgConnection,
+    settings: &TokenSettings,
+    source: &str,
+) -> Result<AllocatedToken, sqlx::Error> {
+    let prefix = settings.prefix_for(source);
+
+    let (business_date, number): (NaiveDate, i32) = sqlx::query_as(
+        r#"
+        INSERT INTO token_sequences (business_date, prefix, last_number)
+        VALUES (((now() AT TIME ZONE $1) - make_interval(hours => $2))::date, $3, 1)
+        ON CONFLICT (business_date, prefix)
+        DO UPDATE SET last_number = token_sequences.last_number + 1
+        RETURNING business_date, last_number
+        "#
+    )
+    .bind(&settings.timezone)
+    .bind(settings.cutoff_hour as i32)
+    .bind(&prefix)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(AllocatedToken { business_date, number, label: format_token(&prefix, number) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(prefixes: &[(&str, &str)]) -> TokenSettings {
+        TokenSettings {
+            cutoff_hour: 4,
+            timezone: "UTC".to_string(),
+            prefixes: prefixes.iter().map(|(s, p)| (s.to_string(), p.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn formats_tokens_with_three_digits() {
+        assert_eq!(format_token("K", 12), "K-012");
+        assert_eq!(format_token("P", 7), "P-007");
+        assert_eq!(format_token("K", 1234), "K-1234");
+        assert_eq!(format_token("", 45), "045");
+    }
+
+    #[test]
+    fn prefixes_come_from_the_source() {
+        let settings = settings(&[("kiosk", "K"), ("pos", "P"), ("online", "")]);
+        assert_eq!(settings.prefix_for("kiosk"), "K");
+        assert_eq!(settings.prefix_for(" POS "), "P");
+        assert_eq!(settings.prefix_for("online"), "");
+        assert_eq!(settings.prefix_for("delivery"), "D");
+        assert_eq!(settings.prefix_for(""), "");
+    }
+
+    #[test]
+    fn configured_prefixes_are_uppercased() {
+        let prefixes = parse_prefixes("Kiosk=k, pos = p2,online=,broken");
+        assert_eq!(prefixes.len(), 3);
+        assert_eq!(prefixes["kiosk"], "K");
+        assert_eq!(prefixes["pos"], "P2");
+        assert_eq!(prefixes["online"], "");
+    }
+}
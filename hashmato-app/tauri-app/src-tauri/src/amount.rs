@@ -0,0 +1,146 @@
+//! Money amounts as the backend sends them.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+
+/// An amount in the store currency, held as whole cents so it prints exactly.
+///
+/// The backend sends amounts as decimal strings (`"12.50"`); they are parsed digit by
+/// digit and never pass through a float. Extra decimal places round half away from
+/// zero, as on the backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Amount {
+    cents: i128,
+}
+
+impl Amount {
+    pub fn is_negative(&self) -> bool {
+        self.cents < 0
+    }
+
+    /// Whole units and cents, without the sign.
+    pub fn parts(&self) -> (u128, u8) {
+        let abs = self.cents.unsigned_abs();
+        (abs / 100, (abs % 100) as u8)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAmountError(String);
+
+impl fmt::Display for ParseAmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' is not a decimal amount like \"12.50\"", self.0)
+    }
+}
+
+impl std::error::Error for ParseAmountError {}
+
+impl FromStr for Amount {
+    type Err = ParseAmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseAmountError(s.to_string());
+        let text = s.trim();
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !digits(whole) || !digits(fraction) {
+            return Err(err());
+        }
+
+        let mut cents: i128 = 0;
+        for digit in whole.chars().chain(fraction.chars().chain("00".chars()).take(2)) {
+            cents = cents
+                .checked_mul(10)
+                .and_then(|c| c.checked_add(i128::from(digit as u8 - b'0')))
+                .ok_or_else(err)?;
+        }
+        if fraction.chars().nth(2).is_some_and(|c| c >= '5') {
+            cents = cents.checked_add(1).ok_or_else(err)?;
+        }
+        Ok(Amount { cents: if negative { -cents } else { cents } })
+    }
+}
+
+impl From<i64> for Amount {
+    fn from(units: i64) -> Self {
+        Amount { cents: i128::from(units) * 100 }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AmountVisitor;
+
+        impl Visitor<'_> for AmountVisitor {
+            type Value = Amount;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a decimal string like \"12.50\"")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Amount, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Amount, E> {
+                Ok(Amount::from(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Amount, E> {
+                i64::try_from(v).map(Amount::from).map_err(|_| E::custom("amount is too large"))
+            }
+        }
+
+        deserializer.deserialize_any(AmountVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cents(s: &str) -> i128 {
+        s.parse::<Amount>().unwrap().cents
+    }
+
+    #[test]
+    fn parses_decimal_strings_exactly() {
+        assert_eq!(cents("12.50"), 1250);
+        assert_eq!(cents("12.5"), 1250);
+        assert_eq!(cents("12"), 1200);
+        assert_eq!(cents(".5"), 50);
+        assert_eq!(cents("-3.00"), -300);
+        assert_eq!(cents(" +0.10 "), 10);
+        assert_eq!(cents("1026.47"), 102647);
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(cents("0.005"), 1);
+        assert_eq!(cents("0.00499"), 0);
+        assert_eq!(cents("-0.005"), -1);
+        assert_eq!(cents("999.999"), 100000);
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for bad in ["", "-", ".", "1e3", "1,50", "12.5.0", "abc", "--1"] {
+            assert!(bad.parse::<Amount>().is_err(), "{:?} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn deserializes_strings_and_whole_numbers_but_not_floats() {
+        assert_eq!(serde_json::from_str::<Amount>("\"4.20\"").unwrap().cents, 420);
+        assert_eq!(serde_json::from_str::<Amount>("7").unwrap().cents, 700);
+        assert!(serde_json::from_str::<Amount>("4.2").is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! A printout laid out once and rendered either as an ESC/POS job or as plain text for
+//! on-screen previews.
+
+use serde::Deserialize;
+
+use crate::escpos::{Align, EscPos};
+
+/// Supported paper rolls. Read from the roll width in mm, 58 or 80; any other width is
+/// an error rather than a guess that would print off the edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u16")]
+pub enum Paper {
+    Mm58,
+    Mm80,
+}
+
+impl TryFrom<u16> for Paper {
+    type Error = String;
+
+    fn try_from(mm: u16) -> Result<Self, Self::Error> {
+        match mm {
+            58 => Ok(Paper::Mm58),
+            80 => Ok(Paper::Mm80),
+            other => Err(format!("Unsupported paper width {}mm; use 58 or 80", other)),
+        }
+    }
+}
+
+impl Paper {
+
+    /// Characters per line in the standard font.
+    pub fn chars(self) -> usize {
+        match self {
+            Paper::Mm58 => 32,
+            Paper::Mm80 => 48,
+        }
+    }
+
+    /// Printable width in dots, for images.
+    pub fn dots(self) -> u16 {
+        match self {
+            Paper::Mm58 => 384,
+            Paper::Mm80 => 576,
+        }
+    }
+
+    fn qr_module(self) -> u8 {
+        match self {
+            Paper::Mm58 => 4,
+            Paper::Mm80 => 6,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub align: Align,
+    pub bold: bool,
+    /// Double height.
+    pub tall: bool,
+    /// Double width: only half of `Document::width()` characters fit on a line.
+    pub wide: bool,
+}
+
+impl Style {
+    pub const NORMAL: Style = Style { align: Align::Left, bold: false, tall: false, wide: false };
+    pub const CENTERED: Style = Style { align: Align::Center, bold: false, tall: false, wide: false };
+    pub const HEADING: Style = Style { align: Align::Center, bold: true, tall: true, wide: false };
+    pub const EMPHASIS: Style = Style { align: Align::Left, bold: true, tall: true, wide: false };
+    /// Readable from across a kitchen.
+    pub const LARGE: Style = Style { align: Align::Left, bold: true, tall: true, wide: true };
+    pub const BANNER: Style = Style { align: Align::Center, bold: true, tall: true, wide: true };
+}
+
+/// A 1-bit image; see `EscPos::raster` for the layout of `data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Raster {
+    pub width: u16,
+    pub height: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Block {
+    Line(String, Style),
+    Rule,
+    Image(Raster),
+    Qr(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Document {
+    pub paper: Paper,
+    blocks: Vec<Block>,
+}
+
+impl Document {
+    pub fn new(paper: Paper) -> Self {
+        Self { paper, blocks: Vec::new() }
+    }
+
+    pub fn width(&self) -> usize {
+        self.paper.chars()
+    }
+
+    /// Characters per line in a `wide` style.
+    pub fn wide_width(&self) -> usize {
+        self.paper.chars() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// One line; callers wrap or truncate to `width()` first.
+    pub fn line(&mut self, text: impl Into<String>, style: Style) -> &mut Self {
+        self.blocks.push(Block::Line(text.into(), style));
+        self
+    }
+
+    pub fn rule(&mut self) -> &mut Self {
+        self.blocks.push(Block::Rule);
+        self
+    }
+
+    /// Printed centred. Images wider than the paper are left out.
+    pub fn image(&mut self, image: Raster) -> &mut Self {
+        if image.width <= self.paper.dots() {
+            self.blocks.push(Block::Image(image));
+        }
+        self
+    }
+
+    /// Printed centred.
+    pub fn qr(&mut self, data: impl Into<String>) -> &mut Self {
+        self.blocks.push(Block::Qr(data.into()));
+        self
+    }
+
+    /// The job for the printer, ending with a feed and a cut.
+    pub fn to_escpos(&self) -> Vec<u8> {
+        let mut job = EscPos::new();
+        // Only send style changes, starting from the printer's reset state.
+        let mut current = Style::NORMAL;
+        let mut apply = |job: &mut EscPos, style: Style| {
+            if style.align != current.align {
+                job.align(style.align);
+            }
+            if style.bold != current.bold {
+                job.bold(style.bold);
+            }
+            if (style.wide, style.tall) != (current.wide, current.tall) {
+                job.char_size(style.wide, style.tall);
+            }
+            current = style;
+        };
+
+        for block in &self.blocks {
+            match block {
+                Block::Line(text, style) => {
+                    apply(&mut job, *style);
+                    job.line(text);
+                }
+                Block::Rule => {
+                    apply(&mut job, Style::NORMAL);
+                    job.line(&"-".repeat(self.width()));
+                }
+                Block::Image(image) => {
+                    apply(&mut job, Style::CENTERED);
+                    job.raster(image.width, image.height, &image.data);
+                }
+                Block::Qr(data) => {
+                    apply(&mut job, Style::CENTERED);
+                    job.qr(data, self.paper.qr_module());
+                }
+            }
+        }
+        apply(&mut job, Style::NORMAL);
+        job.feed(3).cut();
+        job.into_bytes()
+    }
+
+    /// What the printout says, with placeholders for images and QR codes.
+    pub fn to_text(&self) -> String {
+        let width = self.width();
+        let centre = |text: &str| format!("{:^width$}", text, width = width).trim_end().to_string();
+
+        let mut out = String::new();
+        for block in &self.blocks {
+            let line = match block {
+                Block::Line(text, style) if style.align == Align::Center => centre(text),
+                Block::Line(text, _) => text.clone(),
+                Block::Rule => "-".repeat(width),
+                Block::Image(image) => centre(&format!("[logo {}x{}]", image.width, image.height)),
+                Block::Qr(data) => centre(&format!("[QR: {}]", data)),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
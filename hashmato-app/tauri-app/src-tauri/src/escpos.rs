@@ -0,0 +1,257 @@
+//! Byte streams for ESC/POS receipt printers.
+
+const ESC: u8 = 0x1b;
+const GS: u8 = 0x1d;
+const LF: u8 = 0x0a;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+}
+
+/// Drawer connector pin the kick pulse goes out on. Most drawers are wired to pin 2; a
+/// second drawer, if any, to pin 5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawerPin {
+    Pin2,
+    Pin5,
+}
+
+/// The most bytes a QR code holds at medium error correction (version 40, byte mode).
+pub const QR_MAX_BYTES: usize = 2331;
+
+/// Code page 19 on ESC/POS printers: PC858, which is Latin-1 plus the euro sign.
+const CODE_PAGE_PC858: u8 = 19;
+
+/// PC858 bytes 0x80-0xff.
+const PC858: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', '€', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{ad}', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{a0}',
+];
+
+/// Builds one print job. Starts by resetting the printer, so settings left over from a
+/// previous job don't carry into this one, and selecting the PC858 code page.
+#[derive(Debug)]
+pub struct EscPos {
+    buf: Vec<u8>,
+}
+
+impl EscPos {
+    pub fn new() -> Self {
+        Self { buf: vec![ESC, b'@', ESC, b't', CODE_PAGE_PC858] }
+    }
+
+    pub fn align(&mut self, align: Align) -> &mut Self {
+        let n = match align {
+            Align::Left => 0,
+            Align::Center => 1,
+        };
+        self.buf.extend([ESC, b'a', n]);
+        self
+    }
+
+    pub fn bold(&mut self, on: bool) -> &mut Self {
+        self.buf.extend([ESC, b'E', on as u8]);
+        self
+    }
+
+    /// Double-width characters halve the characters per line; double-height ones keep
+    /// their normal width, so column layouts still line up.
+    pub fn char_size(&mut self, wide: bool, tall: bool) -> &mut Self {
+        self.buf.extend([GS, b'!', (wide as u8) << 4 | tall as u8]);
+        self
+    }
+
+    pub fn text(&mut self, text: &str) -> &mut Self {
+        self.buf.extend(encode(text));
+        self
+    }
+
+    pub fn line(&mut self, text: &str) -> &mut Self {
+        self.text(text);
+        self.buf.push(LF);
+        self
+    }
+
+    pub fn feed(&mut self, lines: u8) -> &mut Self {
+        self.buf.extend([ESC, b'd', lines]);
+        self
+    }
+
+    /// Prints a 1-bit image: `data` holds `height` rows of `width.div_ceil(8)` bytes,
+    /// most significant bit leftmost, set bits black.
+    pub fn raster(&mut self, width: u16, height: u16, data: &[u8]) -> &mut Self {
+        let row_bytes = width.div_ceil(8);
+        let [xl, xh] = row_bytes.to_le_bytes();
+        let [yl, yh] = height.to_le_bytes();
+        self.buf.extend([GS, b'v', b'0', 0, xl, xh, yl, yh]);
+        self.buf.extend(data);
+        self.buf.push(LF);
+        self
+    }
+
+    /// Prints `data` as a QR code with modules `module` dots wide (1-16), medium error
+    /// correction. Data longer than `QR_MAX_BYTES` prints nothing: a cut-short code
+    /// would scan as something else.
+    pub fn qr(&mut self, data: &str, module: u8) -> &mut Self {
+        if data.is_empty() || data.len() > QR_MAX_BYTES {
+            return self;
+        }
+        // Store, then print; pL/pH count the data plus the three function bytes.
+        let [pl, ph] = (data.len() as u16 + 3).to_le_bytes();
+        self.buf.extend([GS, b'(', b'k', 4, 0, 49, 65, 50, 0]);
+        self.buf.extend([GS, b'(', b'k', 3, 0, 49, 67, module.clamp(1, 16)]);
+        self.buf.extend([GS, b'(', b'k', 3, 0, 49, 69, 49]);
+        self.buf.extend([GS, b'(', b'k', pl, ph, 49, 80, 48]);
+        self.buf.extend(data.as_bytes());
+        self.buf.extend([GS, b'(', b'k', 3, 0, 49, 81, 48]);
+        self.buf.push(LF);
+        self
+    }
+
+    /// Pulses the drawer kick connector: on for `on_ms`, then off for `off_ms`.
+    /// Printers count in 2ms steps, up to 510ms.
+    pub fn kick_drawer(&mut self, pin: DrawerPin, on_ms: u16, off_ms: u16) -> &mut Self {
+        let m = match pin {
+            DrawerPin::Pin2 => 0,
+            DrawerPin::Pin5 => 1,
+        };
+        let steps = |ms: u16| (ms / 2).clamp(1, 255) as u8;
+        self.buf.extend([ESC, b'p', m, steps(on_ms), steps(off_ms)]);
+        self
+    }
+
+    /// Feeds the paper up to the cutter and cuts it.
+    pub fn cut(&mut self) -> &mut Self {
+        self.buf.extend([GS, b'V', 0x41, 0x03]);
+        self
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl Default for EscPos {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Text in the PC858 code page; characters it doesn't have become `?`. Control
+/// characters are dropped, so text can never turn into a printer command.
+fn encode(text: &str) -> impl Iterator<Item = u8> + '_ {
+    text.chars().filter(|c| !c.is_control()).map(|c| {
+        if c.is_ascii() {
+            c as u8
+        } else {
+            PC858.iter().position(|p| *p == c).map_or(b'?', |i| 0x80 + i as u8)
+        }
+    })
+}
+
+/// `text` without the control characters `encode` would drop, tabs and line breaks
+/// becoming spaces. Layout works on this so padding counts only what prints.
+pub fn printable(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c {
+            c if c.is_control() && c.is_whitespace() => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// `left` and `right` on one line of `width` characters, `left` cut short if the two
+/// don't fit.
+pub fn columns(left: &str, right: &str, width: usize) -> String {
+    let (left, right) = (printable(left), printable(right));
+    let room = width.saturating_sub(right.chars().count() + 1);
+    let left: String = left.chars().take(room).collect();
+    format!("{:<room$} {}", left, right, room = room)
+}
+
+/// Splits the `printable` part of `text` into lines of at most `width` characters,
+/// breaking between words where it can.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in printable(text).split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        // Words longer than a whole line are split wherever they hit the edge.
+        while word.len() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        if word.is_empty() {
+            continue;
+        }
+        let used = current.chars().count();
+        if used > 0 && used + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.extend(word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drawer_kick_pulse() {
+        let mut job = EscPos::new();
+        job.kick_drawer(DrawerPin::Pin2, 50, 500);
+        assert_eq!(job.into_bytes(), [0x1b, b'@', 0x1b, b't', 19, 0x1b, b'p', 0, 25, 250]);
+
+        let mut job = EscPos::new();
+        job.kick_drawer(DrawerPin::Pin5, 0, 2000);
+        assert_eq!(job.into_bytes(), [0x1b, b'@', 0x1b, b't', 19, 0x1b, b'p', 1, 1, 255]);
+    }
+
+    #[test]
+    fn qr_code_stores_then_prints() {
+        let mut job = EscPos::new();
+        job.qr("hi", 6);
+        let bytes = job.into_bytes();
+        assert!(bytes.windows(10).any(|w| w == [0x1d, b'(', b'k', 5, 0, 49, 80, 48, b'h', b'i']));
+    }
+
+    #[test]
+    fn qr_code_too_long_to_fit_is_left_out() {
+        let mut job = EscPos::new();
+        job.qr(&"x".repeat(QR_MAX_BYTES + 1), 6).qr("", 6);
+        assert_eq!(job.into_bytes(), EscPos::new().into_bytes());
+    }
+
+    #[test]
+    fn layout_helpers_only_count_printable_characters() {
+        assert_eq!(printable("a\tb\u{1b}@\u{0}c"), "a b@c");
+        assert_eq!(wrap("latte\u{1b}\u{1d}V more", 6), ["latteV", "more"]);
+        assert_eq!(columns("Tea\u{7}", "1.00", 10), "Tea   1.00");
+    }
+
+    #[test]
+    fn encodes_pc858() {
+        let bytes: Vec<u8> = encode("€5 £3 ¥2 é ☃").collect();
+        assert_eq!(bytes, [0xd5, b'5', b' ', 0x9c, b'3', b' ', 0xbe, b'2', b' ', 0x82, b' ', b'?']);
+    }
+}
@@ -0,0 +1,31 @@
+//! Golden-file checks for print layouts.
+
+use std::path::PathBuf;
+
+/// Golden files hold the job with every byte outside printable ASCII written as
+/// `\xNN`, and a line break after each LF, so changes show up in a normal diff.
+/// Run with `UPDATE_GOLDEN=1` to rewrite them after an intended layout change.
+pub fn assert_golden(name: &str, bytes: &[u8]) {
+    let mut dump = String::new();
+    for &b in bytes {
+        match b {
+            b'\n' => dump.push_str("\\x0a\n"),
+            b'\\' => dump.push_str("\\\\"),
+            0x20..=0x7e => dump.push(b as char),
+            _ => dump.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    assert_matches_file(name, &dump);
+}
+
+pub fn assert_matches_file(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1 to create it)", path.display(), e));
+    assert_eq!(actual, expected, "{} differs", path.display());
+}
@@ -0,0 +1,129 @@
+use serde::Deserialize;
+
+use crate::document::{Document, Paper, Style};
+use crate::escpos::wrap;
+
+/// Modifiers and notes sit under the item name, past its quantity.
+const INDENT: usize = 3;
+const NOTE: Style = Style { bold: true, ..Style::NORMAL };
+
+#[derive(Debug, Deserialize)]
+pub struct TicketItem {
+    pub name: String,
+    pub quantity: u32,
+    /// Chosen modifier options, e.g. "No onions".
+    #[serde(default)]
+    pub modifiers: Vec<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// One station's part of an order, as queued by the backend in a `kitchen_ticket` job.
+#[derive(Debug, Deserialize)]
+pub struct KitchenTicket {
+    pub order_id: i32,
+    pub station_name: String,
+    /// Queue token, e.g. `K-012`.
+    #[serde(default)]
+    pub token_label: Option<String>,
+    /// kiosk, pos or online.
+    pub source: String,
+    /// Already formatted for the store's locale by the caller.
+    #[serde(default)]
+    pub placed_at: Option<String>,
+    pub items: Vec<TicketItem>,
+    #[serde(default)]
+    pub reprint: bool,
+}
+
+/// Lays out a ticket for the station's printer. Station, token and item names print
+/// double size so they can be read from across the kitchen.
+pub fn layout(ticket: &KitchenTicket, paper: Paper) -> Document {
+    let mut doc = Document::new(paper);
+    let (width, wide) = (doc.width(), doc.wide_width());
+
+    for line in wrap(&ticket.station_name.to_uppercase(), wide) {
+        doc.line(line, Style::BANNER);
+    }
+    if let Some(token) = &ticket.token_label {
+        doc.line(token.clone(), Style::BANNER);
+    }
+    doc.line(format!("Order #{} - {}", ticket.order_id, capitalize(&ticket.source)), Style::CENTERED);
+    if let Some(placed_at) = &ticket.placed_at {
+        doc.line(placed_at.clone(), Style::CENTERED);
+    }
+    if ticket.reprint {
+        doc.line("** REPRINT **", Style::HEADING);
+    }
+    doc.rule();
+
+    for item in &ticket.items {
+        let qty = format!("{}x ", item.quantity);
+        for (i, name) in wrap(&item.name, wide.saturating_sub(qty.len())).into_iter().enumerate() {
+            let lead = if i == 0 { qty.clone() } else { " ".repeat(qty.len()) };
+            doc.line(format!("{}{}", lead, name), Style::LARGE);
+        }
+        for modifier in &item.modifiers {
+            indented(&mut doc, &format!("+ {}", modifier), width, Style::NORMAL);
+        }
+        if let Some(note) = item.note.as_deref().filter(|n| !n.trim().is_empty()) {
+            indented(&mut doc, &format!("NOTE: {}", note.trim()), width, NOTE);
+        }
+    }
+    doc.rule();
+
+    doc
+}
+
+fn indented(doc: &mut Document, text: &str, width: usize, style: Style) {
+    for line in wrap(text, width.saturating_sub(INDENT)) {
+        doc.line(format!("{:indent$}{}", "", line, indent = INDENT), style);
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{assert_golden, assert_matches_file};
+
+    fn ticket() -> KitchenTicket {
+        KitchenTicket {
+            order_id: 1042,
+            station_name: "Grill".into(),
+            token_label: Some("K-012".into()),
+            source: "kiosk".into(),
+            placed_at: Some("18/10/2026, 12:30".into()),
+            items: vec![
+                TicketItem {
+                    name: "Cheeseburger".into(),
+                    quantity: 2,
+                    modifiers: vec!["No onions".into(), "Extra cheese".into()],
+                    note: Some("well done, cut in half please".into()),
+                },
+                TicketItem { name: "Double smash burger with bacon".into(), quantity: 12, modifiers: vec![], note: None },
+            ],
+            reprint: false,
+        }
+    }
+
+    #[test]
+    fn kitchen_ticket() {
+        assert_golden("kitchen_ticket.txt", &layout(&ticket(), Paper::Mm80).to_escpos());
+    }
+
+    #[test]
+    fn reprint_on_58mm() {
+        let ticket = KitchenTicket { reprint: true, token_label: None, ..ticket() };
+        assert_golden("kitchen_ticket_reprint_58.txt", &layout(&ticket, Paper::Mm58).to_escpos());
+    }
+
+    #[test]
+    fn plain_text_preview() {
+        assert_matches_file("kitchen_ticket_preview.txt", &layout(&ticket(), Paper::Mm80).to_text());
+    }
+}
@@ -1,41 +1,93 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::command;
-use serde::{Serialize, Deserialize};
+mod amount;
+mod document;
+mod escpos;
+#[cfg(test)]
+mod golden;
+mod kitchen;
+mod printer;
+mod receipt;
+mod template;
 
-/// Command: open cash drawer
+use tauri::{command, State};
+
+use document::Paper;
+use escpos::EscPos;
+use kitchen::KitchenTicket;
+use printer::{PrinterSettings, PrinterTarget};
+use receipt::ReceiptPayload;
+use template::ReceiptTemplate;
+
+/// Command: open the cash drawer with a kick pulse through the receipt printer
 #[command]
-fn open_drawer() -> Result<String, String> {
-    // TODO: send signal to actual cash drawer (USB/serial/ESC/POS)
-    println!("💵 Open drawer called!");
+async fn open_drawer(settings: State<'_, PrinterSettings>) -> Result<String, String> {
+    let target = settings.receipt_printer()?.clone();
+    let drawer = settings.drawer;
+    let mut job = EscPos::new();
+    job.kick_drawer(drawer.pin, drawer.on_ms, drawer.off_ms);
+
+    send_job(target, job.into_bytes()).await?;
     Ok("Drawer opened".into())
 }
 
-/// Command: print receipt
-#[derive(Debug, Deserialize)]
-struct ReceiptItem {
-    name: String,
-    qty: u32,
-    price: f64,
+/// Command: print receipt on the printer set by `RECEIPT_PRINTER`, or another named
+/// printer, laid out with the store's receipt template (or a plain default one)
+#[command]
+async fn print_receipt(
+    settings: State<'_, PrinterSettings>,
+    payload: ReceiptPayload,
+    template: Option<ReceiptTemplate>,
+    printer: Option<String>,
+) -> Result<String, String> {
+    let target = settings.printer(printer.as_deref().unwrap_or("receipt"))?.clone();
+    let job = receipt::layout(&payload, &template_or_fallback(template, &settings)).to_escpos();
+
+    send_job(target, job).await?;
+    Ok(format!("Receipt for order {} printed", payload.order_id))
+}
+
+/// Command: print a kitchen ticket on the named station printer
+#[command]
+async fn print_kitchen_ticket(
+    settings: State<'_, PrinterSettings>,
+    ticket: KitchenTicket,
+    printer: String,
+    paper_width: Option<Paper>,
+) -> Result<String, String> {
+    let target = settings.printer(&printer)?.clone();
+    let job = kitchen::layout(&ticket, paper_width.unwrap_or(Paper::Mm80)).to_escpos();
+
+    send_job(target, job).await?;
+    Ok(format!("Ticket for order {} printed on {}", ticket.order_id, printer))
 }
 
-#[derive(Debug, Deserialize)]
-struct ReceiptPayload {
-    order_id: i32,
-    items: Vec<ReceiptItem>,
-    total: f64,
+/// Command: the receipt as plain text, for an on-screen preview
+#[command]
+fn preview_receipt(
+    settings: State<'_, PrinterSettings>,
+    payload: ReceiptPayload,
+    template: Option<ReceiptTemplate>,
+) -> String {
+    receipt::layout(&payload, &template_or_fallback(template, &settings)).to_text()
 }
 
+/// Without the store's template, the plain default layout on this till's `RECEIPT_WIDTH`.
+fn template_or_fallback(template: Option<ReceiptTemplate>, settings: &PrinterSettings) -> ReceiptTemplate {
+    template.unwrap_or_else(|| ReceiptTemplate { paper_width: settings.receipt_paper, ..ReceiptTemplate::default() })
+}
+
+/// Command: names of the printers this till drives, for claiming print jobs
 #[command]
-fn print_receipt(payload: ReceiptPayload) -> Result<String, String> {
-    println!("🖨 Printing receipt for order {}", payload.order_id);
-    for item in payload.items {
-        println!("- {} x{} = ${}", item.name, item.qty, item.price);
-    }
-    println!("TOTAL = ${}", payload.total);
-
-    // TODO: replace println! with actual ESC/POS print driver
-    Ok("Print job queued".into())
+fn list_printers(settings: State<'_, PrinterSettings>) -> Vec<String> {
+    settings.printers.keys().cloned().collect()
+}
+
+/// Printer I/O blocks for up to the connect timeout; keep it off the async workers.
+async fn send_job(target: PrinterTarget, job: Vec<u8>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || target.send(&job))
+        .await
+        .map_err(|e| e.to_string())?
 }
 
 /// Command: get current app version
@@ -53,9 +105,13 @@ fn latest_update() -> Result<String, String> {
 
 fn main() {
     tauri::Builder::default()
+        .manage(PrinterSettings::from_env())
         .invoke_handler(tauri::generate_handler![
             open_drawer,
             print_receipt,
+            preview_receipt,
+            print_kitchen_ticket,
+            list_printers,
             get_app_version,
             latest_update
         ])
@@ -0,0 +1,265 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::document::Paper;
+use crate::escpos::DrawerPin;
+
+/// Port network printers listen on for raw jobs.
+const RAW_PORT: u16 = 9100;
+const DEFAULT_BAUD: u32 = 9600;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Where print jobs are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrinterTarget {
+    /// A network printer taking raw jobs, as `host:port`.
+    Tcp(String),
+    Serial { path: String, baud: u32 },
+    /// A device node such as `/dev/usb/lp0` or a shared printer path. It has to exist
+    /// already, so an unplugged printer is an error rather than a new file.
+    Device(PathBuf),
+    /// A plain file capturing jobs, created if missing. Jobs are appended.
+    File(PathBuf),
+}
+
+impl PrinterTarget {
+    /// `tcp:192.168.1.50` (port 9100 unless given), `serial:/dev/ttyUSB0:19200` or
+    /// `serial:COM3` (9600 baud unless given), `file:jobs.bin` to capture jobs; anything
+    /// else is a device path.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        if let Some(addr) = spec.strip_prefix("tcp:") {
+            let has_port = addr.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+            if addr.is_empty() {
+                return Err("tcp: needs a host".into());
+            }
+            return Ok(if has_port { Self::Tcp(addr.into()) } else { Self::Tcp(format!("{}:{}", addr, RAW_PORT)) });
+        }
+        if let Some(device) = spec.strip_prefix("serial:") {
+            let (path, baud) = match device.rsplit_once(':') {
+                Some((path, baud)) if !path.is_empty() => {
+                    let baud = baud.parse().map_err(|_| format!("Invalid baud rate '{}'", baud))?;
+                    (path, baud)
+                }
+                _ => (device, DEFAULT_BAUD),
+            };
+            if path.is_empty() {
+                return Err("serial: needs a device".into());
+            }
+            return Ok(Self::Serial { path: path.into(), baud });
+        }
+        if let Some(path) = spec.strip_prefix("file:") {
+            if path.is_empty() {
+                return Err("file: needs a path".into());
+            }
+            return Ok(Self::File(path.into()));
+        }
+        if spec.is_empty() {
+            return Err("Printer path is empty".into());
+        }
+        Ok(Self::Device(spec.into()))
+    }
+
+    /// Sends one job and waits until it has been handed over.
+    pub fn send(&self, bytes: &[u8]) -> Result<(), String> {
+        match self {
+            Self::Tcp(addr) => {
+                let socket = addr
+                    .to_socket_addrs()
+                    .map_err(|e| format!("Cannot resolve printer {}: {}", addr, e))?
+                    .next()
+                    .ok_or_else(|| format!("Cannot resolve printer {}", addr))?;
+                let mut stream = TcpStream::connect_timeout(&socket, TIMEOUT)
+                    .map_err(|e| format!("Cannot reach printer {}: {}", addr, e))?;
+                stream.set_write_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+                stream.write_all(bytes).and_then(|_| stream.flush())
+                    .map_err(|e| format!("Printer {} write failed: {}", addr, e))
+            }
+            Self::Serial { path, baud } => {
+                let mut port = serialport::new(path.as_str(), *baud)
+                    .timeout(TIMEOUT)
+                    .open()
+                    .map_err(|e| format!("Cannot open serial printer {}: {}", path, e))?;
+                port.write_all(bytes).and_then(|_| port.flush())
+                    .map_err(|e| format!("Serial printer {} write failed: {}", path, e))
+            }
+            Self::Device(path) | Self::File(path) => {
+                let mut file = OpenOptions::new()
+                    .create(matches!(self, Self::File(_)))
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| format!("Cannot open printer {}: {}", path.display(), e))?;
+                file.write_all(bytes).and_then(|_| file.flush())
+                    .map_err(|e| format!("Printer {} write failed: {}", path.display(), e))
+            }
+        }
+    }
+}
+
+/// The cash drawer hangs off the receipt printer and opens on a pulse from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawerKick {
+    pub pin: DrawerPin,
+    pub on_ms: u16,
+    pub off_ms: u16,
+}
+
+/// `kitchen=tcp:192.168.1.60,bar=serial:COM3`: printer names and where they are.
+pub fn parse_printers(list: &str) -> Result<Vec<(String, PrinterTarget)>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, spec) = entry.split_once('=').ok_or_else(|| format!("'{}' needs to be name=printer", entry))?;
+            let name = name.trim();
+            let plain = !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+            if !plain {
+                return Err(format!("Printer name '{}' may only use lowercase letters, digits, '-' and '_'", name));
+            }
+            Ok((name.to_string(), PrinterTarget::parse(spec)?))
+        })
+        .collect()
+}
+
+/// `RECEIPT_WIDTH` in characters per line (48, or 32 for 58mm paper); the paper width in
+/// mm is accepted too.
+pub fn parse_receipt_width(width: &str) -> Result<Paper, String> {
+    match width.trim() {
+        "48" | "80" => Ok(Paper::Mm80),
+        "32" | "58" => Ok(Paper::Mm58),
+        other => Err(format!("'{}' is not a receipt width; use 48 (80mm) or 32 (58mm)", other)),
+    }
+}
+
+/// Printers this till drives, by the name print jobs use, read once at startup.
+#[derive(Debug, Clone)]
+pub struct PrinterSettings {
+    pub printers: BTreeMap<String, PrinterTarget>,
+    pub drawer: DrawerKick,
+    /// Receipt paper when no receipt template could be fetched.
+    pub receipt_paper: Paper,
+}
+
+impl PrinterSettings {
+    /// Reads `RECEIPT_PRINTER` (see `PrinterTarget::parse`) as the printer named
+    /// `receipt`, `PRINTERS` (see `parse_printers`) for any others, and `DRAWER_PIN` (2 or
+    /// 5, default 2), `DRAWER_ON_MS` (default 50) and `DRAWER_OFF_MS` (default 500) for
+    /// the drawer kick. Paper width comes from the receipt template, or `RECEIPT_WIDTH`
+    /// (see `parse_receipt_width`, default 80mm) when there is none.
+    pub fn from_env() -> Self {
+        let mut printers = BTreeMap::new();
+        if let Ok(list) = std::env::var("PRINTERS") {
+            match parse_printers(&list) {
+                Ok(named) => printers.extend(named),
+                Err(e) => eprintln!("Ignoring PRINTERS: {}", e),
+            }
+        }
+        if let Ok(spec) = std::env::var("RECEIPT_PRINTER") {
+            match PrinterTarget::parse(&spec) {
+                Ok(target) => {
+                    printers.insert("receipt".to_string(), target);
+                }
+                Err(e) => eprintln!("Ignoring RECEIPT_PRINTER: {}", e),
+            }
+        }
+        let pin = match std::env::var("DRAWER_PIN").as_deref() {
+            Ok("5") => DrawerPin::Pin5,
+            Ok("2") | Err(_) => DrawerPin::Pin2,
+            Ok(other) => {
+                eprintln!("Ignoring DRAWER_PIN '{}': use 2 or 5", other);
+                DrawerPin::Pin2
+            }
+        };
+        let ms = |key: &str, default: u16| std::env::var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        let drawer = DrawerKick { pin, on_ms: ms("DRAWER_ON_MS", 50), off_ms: ms("DRAWER_OFF_MS", 500) };
+        let receipt_paper = match std::env::var("RECEIPT_WIDTH") {
+            Ok(width) => parse_receipt_width(&width).unwrap_or_else(|e| {
+                eprintln!("Ignoring RECEIPT_WIDTH: {}", e);
+                Paper::Mm80
+            }),
+            Err(_) => Paper::Mm80,
+        };
+        Self { printers, drawer, receipt_paper }
+    }
+
+    pub fn printer(&self, name: &str) -> Result<&PrinterTarget, String> {
+        self.printers.get(name).ok_or_else(|| match name {
+            "receipt" => "No receipt printer configured (set RECEIPT_PRINTER)".to_string(),
+            _ => format!("No printer named '{}' configured (add it to PRINTERS)", name),
+        })
+    }
+
+    pub fn receipt_printer(&self) -> Result<&PrinterTarget, String> {
+        self.printer("receipt")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_targets() {
+        assert_eq!(PrinterTarget::parse("tcp:192.168.1.50"), Ok(PrinterTarget::Tcp("192.168.1.50:9100".into())));
+        assert_eq!(PrinterTarget::parse("tcp:printer.local:9101"), Ok(PrinterTarget::Tcp("printer.local:9101".into())));
+        assert_eq!(
+            PrinterTarget::parse("serial:/dev/ttyUSB0:19200"),
+            Ok(PrinterTarget::Serial { path: "/dev/ttyUSB0".into(), baud: 19200 })
+        );
+        assert_eq!(PrinterTarget::parse("serial:COM3"), Ok(PrinterTarget::Serial { path: "COM3".into(), baud: 9600 }));
+        assert_eq!(PrinterTarget::parse("/dev/usb/lp0"), Ok(PrinterTarget::Device("/dev/usb/lp0".into())));
+        assert_eq!(PrinterTarget::parse("file:jobs.bin"), Ok(PrinterTarget::File("jobs.bin".into())));
+        assert!(PrinterTarget::parse("serial:/dev/ttyS0:fast").is_err());
+        assert!(PrinterTarget::parse("tcp:").is_err());
+        assert!(PrinterTarget::parse("file:").is_err());
+    }
+
+    #[test]
+    fn receipt_width_is_characters_or_millimetres() {
+        assert_eq!(parse_receipt_width("32"), Ok(Paper::Mm58));
+        assert_eq!(parse_receipt_width(" 58 "), Ok(Paper::Mm58));
+        assert_eq!(parse_receipt_width("48"), Ok(Paper::Mm80));
+        assert_eq!(parse_receipt_width("80"), Ok(Paper::Mm80));
+        assert!(parse_receipt_width("42").is_err());
+    }
+
+    #[test]
+    fn parses_printer_lists() {
+        assert_eq!(
+            parse_printers("kitchen=tcp:10.0.0.5, bar=serial:COM3,"),
+            Ok(vec![
+                ("kitchen".into(), PrinterTarget::Tcp("10.0.0.5:9100".into())),
+                ("bar".into(), PrinterTarget::Serial { path: "COM3".into(), baud: 9600 }),
+            ])
+        );
+        assert_eq!(parse_printers(""), Ok(vec![]));
+        assert!(parse_printers("kitchen").is_err());
+        assert!(parse_printers("Kitchen 1=/dev/usb/lp0").is_err());
+        assert!(parse_printers("kitchen=serial:").is_err());
+    }
+
+    #[test]
+    fn file_target_appends_jobs() {
+        let path = std::env::temp_dir().join(format!("escpos-{}.bin", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let target = PrinterTarget::File(path.clone());
+        target.send(b"one").unwrap();
+        target.send(b"two").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"onetwo");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn missing_device_is_an_error_not_a_new_file() {
+        let path = std::env::temp_dir().join(format!("escpos-lp-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let err = PrinterTarget::Device(path.clone()).send(b"job").unwrap_err();
+        assert!(err.starts_with("Cannot open printer"), "{}", err);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,245 @@
+use serde::Deserialize;
+
+use crate::amount::Amount;
+use crate::document::{Document, Style};
+use crate::escpos::{columns, wrap};
+use crate::template::ReceiptTemplate;
+
+/// `"12x "`: room for up to three-digit quantities.
+const QTY_WIDTH: usize = 5;
+const AMOUNT_WIDTH: usize = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptItem {
+    pub name: String,
+    pub qty: u32,
+    /// Line total, not the unit price.
+    pub price: Amount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReceiptPayload {
+    pub order_id: i32,
+    /// Queue token, e.g. `K-012`.
+    #[serde(default)]
+    pub token_label: Option<String>,
+    /// Already formatted for the store's locale by the caller.
+    #[serde(default)]
+    pub placed_at: Option<String>,
+    pub items: Vec<ReceiptItem>,
+    #[serde(default)]
+    pub subtotal: Option<Amount>,
+    #[serde(default)]
+    pub tax_total: Option<Amount>,
+    pub total: Amount,
+}
+
+/// Lays the receipt out according to `template`.
+pub fn layout(payload: &ReceiptPayload, template: &ReceiptTemplate) -> Document {
+    let mut doc = Document::new(template.paper_width);
+    let width = doc.width();
+    let labels = &template.labels;
+    let centred = |doc: &mut Document, text: &str| {
+        for text_line in text.lines() {
+            for line in wrap(text_line, width) {
+                doc.line(line, Style::CENTERED);
+            }
+        }
+    };
+
+    if let Some(logo) = template.logo() {
+        doc.image(logo);
+    }
+    if !template.store_name.trim().is_empty() {
+        for line in wrap(&template.store_name, width) {
+            doc.line(line, Style::HEADING);
+        }
+    }
+    if let Some(address) = &template.store_address {
+        centred(&mut doc, address);
+    }
+    if let Some(tax_id) = &template.tax_id {
+        centred(&mut doc, &format!("{}: {}", labels.tax_id, tax_id));
+    }
+    if let Some(header) = &template.header {
+        centred(&mut doc, header);
+    }
+
+    if !doc.is_empty() {
+        doc.rule();
+    }
+    doc.line(format!("{} #{}", labels.order, payload.order_id), Style::HEADING);
+    if let Some(token) = &payload.token_label {
+        doc.line(format!("{} {}", labels.token, token), Style::HEADING);
+    }
+    if let Some(placed_at) = &payload.placed_at {
+        doc.line(placed_at.clone(), Style::CENTERED);
+    }
+    doc.rule();
+
+    for item in &payload.items {
+        for line in item_lines(item, template, width) {
+            doc.line(line, Style::NORMAL);
+        }
+    }
+    doc.rule();
+
+    if let Some(subtotal) = &payload.subtotal {
+        doc.line(columns(&labels.subtotal, &template.money(subtotal), width), Style::NORMAL);
+    }
+    if let Some(tax) = &payload.tax_total {
+        doc.line(columns(&labels.tax, &template.money(tax), width), Style::NORMAL);
+    }
+    doc.line(columns(&labels.total, &template.money(&payload.total), width), Style::EMPHASIS);
+
+    if let Some(footer) = &template.footer {
+        doc.rule();
+        centred(&mut doc, footer);
+    }
+    if let Some(qr) = &template.qr_code {
+        let content = qr
+            .replace("{order_id}", &payload.order_id.to_string())
+            .replace("{token}", payload.token_label.as_deref().unwrap_or(""));
+        doc.qr(content);
+    }
+
+    doc
+}
+
+/// Quantity, name and amount in columns. Long names wrap under the name column, with the
+/// amount on the first line.
+fn item_lines(item: &ReceiptItem, template: &ReceiptTemplate, width: usize) -> Vec<String> {
+    let name_width = width.saturating_sub(QTY_WIDTH + AMOUNT_WIDTH);
+    let qty = format!("{}x", item.qty);
+    let amount = template.number(&item.price);
+
+    wrap(&item.name, name_width)
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            if i == 0 {
+                format!("{:<qw$}{:<nw$}{:>aw$}", qty, name, amount, qw = QTY_WIDTH, nw = name_width, aw = AMOUNT_WIDTH)
+            } else {
+                format!("{:qw$}{}", "", name, qw = QTY_WIDTH)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Paper;
+    use crate::golden::{assert_golden, assert_matches_file};
+    use crate::template::Labels;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    fn item(name: &str, qty: u32, price: &str) -> ReceiptItem {
+        ReceiptItem { name: name.into(), qty, price: amount(price) }
+    }
+
+    fn payload(order_id: i32, items: Vec<ReceiptItem>, total: &str) -> ReceiptPayload {
+        ReceiptPayload { order_id, token_label: None, placed_at: None, items, subtotal: None, tax_total: None, total: amount(total) }
+    }
+
+    /// A store using every template feature, in German on 80mm paper.
+    fn branded() -> ReceiptTemplate {
+        ReceiptTemplate {
+            paper_width: Paper::Mm80,
+            store_name: "Hashmato Café".into(),
+            store_address: Some("Hauptstraße 1\n10115 Berlin".into()),
+            tax_id: Some("DE123456789".into()),
+            header: Some("Willkommen!".into()),
+            footer: Some("Danke für Ihren Besuch".into()),
+            currency_symbol: "€".into(),
+            locale: "de-DE".into(),
+            qr_code: Some("https://hashmato.example/o/{order_id}".into()),
+            logo_width: Some(16),
+            logo_height: Some(2),
+            logo_data: Some("/wAA/w==".into()),
+            labels: Labels {
+                order: "Bestellung".into(),
+                token: "Nummer".into(),
+                tax_id: "USt-IdNr".into(),
+                subtotal: "Zwischensumme".into(),
+                tax: "MwSt".into(),
+                total: "SUMME".into(),
+            },
+        }
+    }
+
+    fn branded_payload() -> ReceiptPayload {
+        ReceiptPayload {
+            order_id: 1042,
+            token_label: Some("K-012".into()),
+            placed_at: Some("18.10.2026 12:30".into()),
+            items: vec![item("Cheeseburger", 2, "17.00"), item("Pommes frites mit Trüffelmayonnaise", 1, "1203.50")],
+            subtotal: Some(amount("1026.47")),
+            tax_total: Some(amount("194.03")),
+            total: amount("1220.50"),
+        }
+    }
+
+    #[test]
+    fn basic_receipt() {
+        let payload = payload(42, vec![item("Cheeseburger", 2, "17.00"), item("Fries", 1, "3.50"), item("Cola", 3, "7.50")], "28.00");
+        assert_golden("receipt_basic.txt", &layout(&payload, &ReceiptTemplate::default()).to_escpos());
+    }
+
+    #[test]
+    fn long_names_wrap_on_narrow_paper() {
+        let payload = payload(
+            7,
+            vec![
+                item("Double smash burger with extra cheese and pickles", 1, "12.90"),
+                item("Supercalifragilisticexpialidocious shake", 12, "118.80"),
+            ],
+            "131.70",
+        );
+        let template = ReceiptTemplate { paper_width: Paper::Mm58, ..Default::default() };
+        assert_golden("receipt_narrow.txt", &layout(&payload, &template).to_escpos());
+    }
+
+    #[test]
+    fn text_cannot_inject_commands() {
+        let payload = payload(1, vec![item("Caf\u{e9} latte\u{1b}@\u{1d}V\u{0}", 1, "4.00")], "4.00");
+        assert_golden("receipt_sanitized.txt", &layout(&payload, &ReceiptTemplate::default()).to_escpos());
+    }
+
+    #[test]
+    fn branded_receipt() {
+        assert_golden("receipt_branded.txt", &layout(&branded_payload(), &branded()).to_escpos());
+    }
+
+    #[test]
+    fn branded_receipt_on_58mm() {
+        let template = ReceiptTemplate { paper_width: Paper::Mm58, ..branded() };
+        assert_golden("receipt_branded_58.txt", &layout(&branded_payload(), &template).to_escpos());
+    }
+
+    #[test]
+    fn plain_text_preview() {
+        assert_matches_file("receipt_branded_preview.txt", &layout(&branded_payload(), &branded()).to_text());
+    }
+
+    #[test]
+    fn reads_amounts_as_decimal_strings() {
+        let payload: ReceiptPayload = serde_json::from_str(
+            r#"{"order_id": 3, "items": [{"name": "Tea", "qty": 1, "price": "0.10"}], "subtotal": "0.10", "total": "0.30"}"#,
+        )
+        .unwrap();
+        assert_eq!(payload.items[0].price, amount("0.10"));
+        assert_eq!(payload.subtotal, Some(amount("0.10")));
+        assert_eq!(payload.total, amount("0.30"));
+    }
+
+    #[test]
+    fn wrap_breaks_between_words() {
+        assert_eq!(wrap("one two three", 7), vec!["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("", 4), vec![""]);
+    }
+}
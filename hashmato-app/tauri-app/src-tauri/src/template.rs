@@ -0,0 +1,188 @@
+use base64::Engine;
+use serde::Deserialize;
+
+use crate::amount::Amount;
+use crate::document::{Paper, Raster};
+
+/// How receipts look: store details, header and footer, logo, currency and number
+/// format. Fetched from the backend (`GET /receipt-templates/default`); every field is
+/// optional so older or partial templates still print.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ReceiptTemplate {
+    /// 58 or 80 (mm).
+    pub paper_width: Paper,
+    pub store_name: String,
+    /// May span several lines.
+    pub store_address: Option<String>,
+    pub tax_id: Option<String>,
+    pub header: Option<String>,
+    pub footer: Option<String>,
+    pub currency_symbol: String,
+    /// BCP 47 tag such as `en-US` or `de-DE`; decides the number format.
+    pub locale: String,
+    /// QR code content, with `{order_id}` and `{token}` filled in. No QR code when unset.
+    pub qr_code: Option<String>,
+    pub logo_width: Option<u16>,
+    pub logo_height: Option<u16>,
+    /// Base64 of the 1-bit raster (see `EscPos::raster`).
+    pub logo_data: Option<String>,
+    pub labels: Labels,
+}
+
+impl Default for ReceiptTemplate {
+    fn default() -> Self {
+        Self {
+            paper_width: Paper::Mm80,
+            store_name: String::new(),
+            store_address: None,
+            tax_id: None,
+            header: None,
+            footer: None,
+            currency_symbol: "$".into(),
+            locale: "en-US".into(),
+            qr_code: None,
+            logo_width: None,
+            logo_height: None,
+            logo_data: None,
+            labels: Labels::default(),
+        }
+    }
+}
+
+/// Fixed wording on the receipt, for stores printing in another language.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Labels {
+    pub order: String,
+    pub token: String,
+    pub tax_id: String,
+    pub subtotal: String,
+    pub tax: String,
+    pub total: String,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Self {
+            order: "Order".into(),
+            token: "Token".into(),
+            tax_id: "Tax ID".into(),
+            subtotal: "Subtotal".into(),
+            tax: "Tax".into(),
+            total: "TOTAL".into(),
+        }
+    }
+}
+
+impl ReceiptTemplate {
+    /// The logo, if there is one and its data matches its size.
+    pub fn logo(&self) -> Option<Raster> {
+        let (width, height) = (self.logo_width?, self.logo_height?);
+        let data = base64::engine::general_purpose::STANDARD.decode(self.logo_data.as_deref()?).ok()?;
+        (data.len() == width.div_ceil(8) as usize * height as usize).then_some(Raster { width, height, data })
+    }
+
+    /// `1234.5` as `1,234.50` for `en-US` or `1.234,50` for `de-DE`.
+    pub fn number(&self, amount: &Amount) -> String {
+        let format = NumberFormat::for_locale(&self.locale);
+        let (whole, cents) = amount.parts();
+        let whole = whole.to_string();
+
+        let mut grouped = String::new();
+        for (i, digit) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i) % 3 == 0 {
+                grouped.push(format.thousands);
+            }
+            grouped.push(digit);
+        }
+        let sign = if amount.is_negative() { "-" } else { "" };
+        format!("{}{}{}{:02}", sign, grouped, format.decimal, cents)
+    }
+
+    /// `number` with the currency symbol where the locale puts it.
+    pub fn money(&self, amount: &Amount) -> String {
+        let number = self.number(amount);
+        if self.currency_symbol.is_empty() {
+            number
+        } else if NumberFormat::for_locale(&self.locale).symbol_after {
+            format!("{} {}", number, self.currency_symbol)
+        } else {
+            // Codes like CHF get a space, signs like $ don't.
+            let gap = if self.currency_symbol.chars().all(char::is_alphabetic) { " " } else { "" };
+            match number.strip_prefix('-') {
+                Some(unsigned) => format!("-{}{}{}", self.currency_symbol, gap, unsigned),
+                None => format!("{}{}{}", self.currency_symbol, gap, number),
+            }
+        }
+    }
+}
+
+struct NumberFormat {
+    thousands: char,
+    decimal: char,
+    symbol_after: bool,
+}
+
+impl NumberFormat {
+    /// Separators by language, with a few regional exceptions. Unknown locales get the
+    /// English format. Printers can't show narrow no-break spaces, so French and friends
+    /// group with a plain space.
+    fn for_locale(locale: &str) -> Self {
+        let locale = locale.replace('_', "-");
+        let language = locale.split('-').next().unwrap_or("").to_ascii_lowercase();
+        let region = locale.split('-').nth(1).unwrap_or("").to_ascii_uppercase();
+
+        let (thousands, decimal, symbol_after) = match (language.as_str(), region.as_str()) {
+            ("de", "CH") | ("it", "CH") | ("fr", "CH") => ('\'', '.', false),
+            ("en", "IE") | ("en", "MT") => (',', '.', false),
+            ("de" | "nl" | "it" | "es" | "pt" | "id" | "da" | "tr" | "el" | "ro" | "hr" | "sl", _) => ('.', ',', true),
+            ("fr" | "sv" | "nb" | "no" | "fi" | "pl" | "cs" | "sk" | "hu" | "ru" | "uk" | "et" | "lt" | "lv", _) => (' ', ',', true),
+            _ => (',', '.', false),
+        };
+        Self { thousands, decimal, symbol_after }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template(locale: &str, symbol: &str) -> ReceiptTemplate {
+        ReceiptTemplate { locale: locale.into(), currency_symbol: symbol.into(), ..Default::default() }
+    }
+
+    #[test]
+    fn formats_numbers_by_locale() {
+        let money = |locale: &str, symbol: &str, amount: &str| template(locale, symbol).money(&amount.parse().unwrap());
+        assert_eq!(money("en-US", "$", "1234567.891"), "$1,234,567.89");
+        assert_eq!(money("de-DE", "€", "1234.5"), "1.234,50 €");
+        assert_eq!(money("fr-FR", "€", "-1234.5"), "-1 234,50 €");
+        assert_eq!(money("de-CH", "CHF", "1234.5"), "CHF 1'234.50");
+        assert_eq!(money("en-GB", "£", "-3.00"), "-£3.00");
+        assert_eq!(money("xx", "", "999.999"), "1,000.00");
+        assert_eq!(money("en-US", "$", "-0.001"), "$0.00");
+        assert_eq!(money("en-US", "$", "90071992547409.93"), "$90,071,992,547,409.93");
+    }
+
+    #[test]
+    fn paper_width_must_be_supported() {
+        let width = |json: &str| serde_json::from_str::<ReceiptTemplate>(json).map(|t| t.paper_width);
+        assert_eq!(width(r#"{"paper_width": 58}"#).unwrap(), Paper::Mm58);
+        assert_eq!(width("{}").unwrap(), Paper::Mm80);
+        assert!(width(r#"{"paper_width": 76}"#).unwrap_err().to_string().contains("Unsupported paper width 76mm"));
+    }
+
+    #[test]
+    fn logo_needs_matching_data() {
+        let mut t = ReceiptTemplate {
+            logo_width: Some(10),
+            logo_height: Some(2),
+            logo_data: Some("AAAAAA==".into()), // 4 bytes: 2 per row
+            ..Default::default()
+        };
+        assert_eq!(t.logo().map(|l| l.data.len()), Some(4));
+        t.logo_height = Some(3);
+        assert!(t.logo().is_none());
+    }
+}
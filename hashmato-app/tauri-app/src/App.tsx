@@ -3,6 +3,7 @@ import { Suspense, lazy } from "react";
 import Header from "./components/Header";
 import Sidebar from "./components/Sidebar";
 import Loader from "./components/Loader"; 
+import StaffGate from "./components/StaffGate";
 
 // Lazy imports
 const POSPage = lazy(() => import("./pages/POSPage"));
@@ -20,9 +21,9 @@ export default function App() {
         <main className="flex-1 p-6 overflow-auto bg-slate-200">
           <Suspense fallback={<Loader />}>
             <Routes>
-              <Route path="/" element={<POSPage />} />
+              <Route path="/" element={<StaffGate><POSPage /></StaffGate>} />
               <Route path="/kiosk" element={<KioskPage />} />
-              <Route path="/kds" element={<KDSPage />} />
+              <Route path="/kds" element={<StaffGate><KDSPage /></StaffGate>} />
               <Route path="/queue" element={<QueuePage />} />
             </Routes>
           </Suspense>
@@ -29,9 +29,27 @@ export default function CartSidebar({
     }
   };
 
+  // Cooking instructions go on the kitchen ticket.
+  const editNote = (item: CartItem) => {
+    const note = window.prompt(`Note for ${item.name}`, item.note ?? "");
+    if (note === null) return;
+    setCart(cart.map(c => c.id === item.id ? { ...c, note: note.trim() || undefined } : c));
+  };
+
   const removeItem = (itemId: number) => {
     setCart(cart.filter(c => c.id !== itemId));
   };
+
+  // Opening the drawer without a sale is audited and needs a reason.
+  const openNoSale = async () => {
+    const note = window.prompt("Reason for opening the drawer")?.trim();
+    if (!note) return;
+    try {
+      await openDrawer("no_sale", { note });
+    } catch (e: any) {
+      alert(`Could not open drawer: ${e?.message ?? e}`);
+    }
+  };
   return (
     <div className="w-full sm:w-[22rem] lg:w-96 bg-white border-t sm:border-t-0 sm:border-l border-slate-200 shadow-xl flex flex-col h-[60vh] sm:h-full">
       {/* Header */}
@@ -72,6 +90,12 @@ export default function CartSidebar({
                   <p className="text-xs sm:text-sm text-slate-500 mb-2">
                     ${item.price.toFixed(2)} each
                   </p>
+                  <button
+                    onClick={() => editNote(item)}
+                    className="text-xs text-blue-600 hover:underline mb-2 text-left"
+                  >
+                    {item.note ? `Note: ${item.note}` : "Add note"}
+                  </button>
 
                   <div className="flex items-center justify-between">
                     <div className="flex items-center gap-2">
@@ -143,7 +167,7 @@ export default function CartSidebar({
 
           <div className="flex flex-col sm:flex-row gap-3 mt-4">
             <button
-              onClick={() => openDrawer()}
+              onClick={openNoSale}
               className="flex-1 py-3 bg-blue-500 hover:bg-blue-600 text-white font-medium rounded-lg text-sm sm:text-base"
             >
               Open Drawer
@@ -1,6 +1,20 @@
-import { Bell, Settings, Store } from "lucide-react";
+import { useEffect, useState } from "react";
+import { Bell, LogOut, Settings, Store } from "lucide-react";
+import * as api from "../services/api";
+import { AuthStaff } from "../types";
 
 const Header = () => {
+  const [staff, setStaff] = useState<AuthStaff | null>(null);
+
+  useEffect(() => {
+    const refresh = (signedIn: boolean) => {
+      if (signedIn) api.currentStaff().then(setStaff).catch(() => setStaff(null));
+      else setStaff(null);
+    };
+    refresh(api.isSignedIn());
+    return api.onSessionChange(refresh);
+  }, []);
+
   return (
     <header className="h-14 sm:h-16 bg-gradient-to-r from-slate-900 to-slate-800 border-b border-slate-700 flex items-center px-4 sm:px-6 shadow-lg">
       {/* Logo + Brand */}
@@ -26,6 +40,18 @@ const Header = () => {
         <button className="p-2 text-slate-300 hover:text-white hover:bg-slate-700 rounded-lg transition-colors">
           <Settings className="w-5 h-5" />
         </button>
+        {staff && (
+          <>
+            <span className="hidden sm:inline text-sm text-slate-300">{staff.name}</span>
+            <button
+              onClick={() => api.logout()}
+              title="Sign out"
+              className="p-2 text-slate-300 hover:text-white hover:bg-slate-700 rounded-lg transition-colors"
+            >
+              <LogOut className="w-5 h-5" />
+            </button>
+          </>
+        )}
       </div>
     </header>
   );
@@ -116,7 +116,7 @@ export default function PeripheralModal() {
                         </span>
                       </div>
                       <span className="font-bold text-slate-800 text-sm sm:text-base">
-                        ${(it.price * it.qty).toFixed(2)}
+                        ${it.price}
                       </span>
                     </li>
                   ))}
@@ -130,7 +130,7 @@ export default function PeripheralModal() {
                     Total Amount
                   </span>
                   <span className="text-lg sm:text-2xl font-bold text-slate-800">
-                    ${modal.payload.total.toFixed(2)}
+                    ${modal.payload.total}
                   </span>
                 </div>
               </div>
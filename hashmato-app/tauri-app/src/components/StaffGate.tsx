@@ -0,0 +1,13 @@
+import { ReactNode, useEffect, useState } from "react";
+import * as api from "../services/api";
+import SignInPage from "../pages/SignInPage";
+
+// Staff-only screens render behind this; it swaps to the sign-in screen whenever there
+// is no session, including when the server rejects an expired one.
+export default function StaffGate({ children }: { children: ReactNode }) {
+  const [signedIn, setSignedIn] = useState(api.isSignedIn());
+
+  useEffect(() => api.onSessionChange(setSignedIn), []);
+
+  return signedIn ? <>{children}</> : <SignInPage />;
+}
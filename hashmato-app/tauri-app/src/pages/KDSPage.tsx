@@ -41,6 +41,14 @@ export default function KDSPage() {
     }
   }
 
+  async function reprintTickets(orderId: number) {
+    try {
+      await api.reprintKitchenTickets(orderId);
+    } catch (e: any) {
+      alert(`Could not reprint tickets: ${e?.response?.data ?? e?.message ?? e}`);
+    }
+  }
+
   function statusColor(status: string) {
     switch (status) {
       case "pending":
@@ -70,6 +78,12 @@ export default function KDSPage() {
                 <div className="text-xs sm:text-sm text-gray-500">
                   {o.order.source.toUpperCase()}
                 </div>
+                <button
+                  onClick={() => reprintTickets(o.order.id)}
+                  className="text-xs text-blue-600 hover:underline"
+                >
+                  Reprint tickets
+                </button>
               </div>
               <span
                 className={`px-2 sm:px-3 py-0.5 sm:py-1 text-[10px] sm:text-xs font-semibold rounded-full ${statusColor(
@@ -87,7 +101,10 @@ export default function KDSPage() {
                   key={it.id}
                   className="p-2 sm:p-3 border rounded-lg flex justify-between items-center"
                 >
-                  <div className="font-medium text-sm sm:text-base">{it.menu_name}</div>
+                  <div>
+                    <div className="font-medium text-sm sm:text-base">{it.menu_name}</div>
+                    {it.note && <div className="text-xs text-orange-600">{it.note}</div>}
+                  </div>
                   <div className="text-xs sm:text-sm text-gray-500">x{it.quantity}</div>
                 </div>
               ))}
@@ -9,6 +9,7 @@ export default function KioskPage() {
   const [cart, setCart] = useState<CartItem[]>([]);
   const [orderPlaced, setOrderPlaced] = useState<LocalOrder | null>(null);
   const [queueNumber, setQueueNumber] = useState<string | null>(null);
+  const [wantReceipt, setWantReceipt] = useState(true);
 
   useEffect(() => {
     load();
@@ -49,6 +50,7 @@ export default function KioskPage() {
     if (cart.length === 0) return;
 
     const localOrder: LocalOrder = {
+      client_order_id: crypto.randomUUID(),
       source: "kiosk",
       status: "pending" as const,
       items: cart.map((c) => ({ menu_item_id: c.id, quantity: c.quantity })),
@@ -59,7 +61,13 @@ export default function KioskPage() {
     const localId = await db.orders.add(localOrder);
 
     try {
-      const res = await api.createOrder({ source: "kiosk", items: localOrder.items });
+      const res = await api.createOrder({
+        source: "kiosk",
+        items: localOrder.items,
+        client_order_id: localOrder.client_order_id,
+        // Printed at the counter
+        print_receipt: wantReceipt,
+      });
       await db.orders.update(localId, { synced: 1 });
 
       // ✅ Use backend order ID as queue number
@@ -121,6 +129,14 @@ export default function KioskPage() {
                 <span>Total:</span>
                 <span>₹{getTotal()}</span>
               </div>
+              <label className="flex items-center gap-2 mt-3 text-sm text-slate-600">
+                <input
+                  type="checkbox"
+                  checked={wantReceipt}
+                  onChange={(e) => setWantReceipt(e.target.checked)}
+                />
+                Print a receipt at the counter
+              </label>
               <button
                 onClick={placeOrder}
                 className="w-full mt-3 bg-blue-600 text-white py-2.5 sm:py-3 rounded-lg text-base sm:text-lg font-medium hover:bg-blue-700 transition"
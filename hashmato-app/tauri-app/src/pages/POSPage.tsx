@@ -5,7 +5,7 @@ import * as api from "../services/api";
 import { db } from "../db/dexie";
 import { AppMenuItem, CartItem, LocalOrder, OrderDetailed } from "../types";
 import { MenuIcon, Search } from "lucide-react";
-import { openDrawer, printReceipt } from "../services/peripherals";
+import { openDrawer, printReceipt, startPrintWorker } from "../services/peripherals";
 import PeripheralModal from "../components/PeripheralModal";
 
 
@@ -19,6 +19,17 @@ export default function POSPage() {
     load();
   }, []);
 
+  // This till prints kiosk receipts and reprints queued for its printers.
+  useEffect(() => {
+    const stop = startPrintWorker().catch((e) => {
+      console.warn("print worker not started", e);
+      return () => {};
+    });
+    return () => {
+      stop.then((fn) => fn());
+    };
+  }, []);
+
   async function load() {
     const local = await db.menu.toArray();
     if (local.length > 0) setMenu(local);
@@ -56,11 +67,13 @@ export default function POSPage() {
   }
   async function onCheckout() {
     const localOrder: LocalOrder = {
+      client_order_id: crypto.randomUUID(),
       source: "pos",
       status: "pending",
       items: cart.map((c) => ({
         menu_item_id: c.id,
         quantity: c.quantity,
+        note: c.note,
       })),
       synced: 0,
       created_at: new Date().toISOString(),
@@ -70,13 +83,19 @@ export default function POSPage() {
 
     try {
 
-      const remote = await api.createOrder({ source: "pos", items: localOrder.items });
+      const remote = await api.createOrder({
+        source: "pos",
+        items: localOrder.items,
+        client_order_id: localOrder.client_order_id,
+      });
       localOrder.synced = 1;
       await db.orders.put({ ...localOrder, id });
       const orderDetailed = await api.fetchOrder(remote.order_id);
       setLastOrder(orderDetailed)
 
-      await openDrawer();
+      // Counter sales are settled in cash; the drawer only opens for a cash tender.
+      const payment = await api.recordPayment(remote.order_id, { tender: "cash" });
+      if (payment.open_drawer) await openDrawer("sale", { orderId: remote.order_id });
       await printReceipt(orderDetailed);
 
     } catch (e) {
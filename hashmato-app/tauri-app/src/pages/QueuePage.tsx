@@ -1,6 +1,6 @@
 import { useEffect, useState } from "react";
 import * as api from "../services/api";
-import { connectWS, onQueueUpdate } from "../services/ws";
+import { connectWS, onEvent, onQueueUpdate } from "../services/ws";
 import { QueueToken } from "../types";
 
 export default function QueuePage() {
@@ -10,20 +10,40 @@ export default function QueuePage() {
     load();
     connectWS();
     const off = onQueueUpdate((q) => setQueue(q));
-    return off;
+    const offCall = onEvent("queue", (e) => {
+      if (e.type === "queue.call") announce(e.payload.token);
+    });
+    return () => {
+      off();
+      offCall();
+    };
   }, []);
 
+  function announce(label: string) {
+    if (!("speechSynthesis" in window)) return;
+    const spoken = label.replace("-", " ").split("").join(" ");
+    window.speechSynthesis.speak(new SpeechSynthesisUtterance(`Order ${spoken}, please collect`));
+  }
+
   async function load() {
     const res = await api.listQueue();
     setQueue(res);
   }
 
+  function waitLabel(q: QueueToken) {
+    if (q.status !== "waiting" || q.estimated_wait_seconds == null) return null;
+    const minutes = Math.ceil(q.estimated_wait_seconds / 60);
+    return minutes <= 1 ? "Almost ready" : `~${minutes} min`;
+  }
+
   function statusStyles(status: string) {
     switch (status) {
       case "pending":
         return "bg-yellow-50 border-yellow-200 text-yellow-700";
       case "ready":
         return "bg-green-50 border-green-200 text-green-700";
+      case "called":
+        return "bg-green-100 border-green-400 text-green-800 animate-pulse";
       case "completed":
         return "bg-gray-50 border-gray-200 text-gray-500";
       default:
@@ -44,11 +64,14 @@ export default function QueuePage() {
             )}`}
           >
             <div className="text-4xl sm:text-5xl lg:text-6xl font-extrabold tracking-wider">
-              {q.token_number}
+              {q.token_label}
             </div>
             <div className="mt-2 text-xs sm:text-sm lg:text-base font-semibold uppercase">
               {q.status}
             </div>
+            {waitLabel(q) && (
+              <div className="mt-1 text-xs sm:text-sm opacity-75">{waitLabel(q)}</div>
+            )}
           </div>
         ))}
       </div>
@@ -0,0 +1,82 @@
+import { FormEvent, useState } from "react";
+import { KeyRound, LogIn } from "lucide-react";
+import * as api from "../services/api";
+
+// Shown in place of the staff screens until someone signs in. Staff normally use their
+// PIN; managers without one can switch to their password.
+export default function SignInPage() {
+  const [username, setUsername] = useState("");
+  const [secret, setSecret] = useState("");
+  const [usePassword, setUsePassword] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const [busy, setBusy] = useState(false);
+
+  async function submit(e: FormEvent) {
+    e.preventDefault();
+    if (!username.trim() || !secret) return;
+    setBusy(true);
+    setError(null);
+    try {
+      await api.login(username.trim(), usePassword ? { password: secret } : { pin: secret });
+    } catch (err: any) {
+      const status = err?.response?.status;
+      if (status === 401) setError(usePassword ? "Wrong username or password" : "Wrong username or PIN");
+      else if (typeof err?.response?.data === "string" && err.response.data) setError(err.response.data);
+      else setError(`Could not sign in: ${err?.message ?? err}`);
+      setSecret("");
+    } finally {
+      setBusy(false);
+    }
+  }
+
+  return (
+    <div className="flex items-center justify-center h-full p-4">
+      <form onSubmit={submit} className="w-full max-w-sm bg-white rounded-2xl shadow-md p-6 space-y-4">
+        <div className="flex items-center gap-2 text-lg font-bold text-slate-800">
+          <KeyRound className="w-5 h-5" />
+          Staff sign-in
+        </div>
+
+        <input
+          className="w-full border border-slate-300 rounded-lg px-3 py-2 focus:outline-none focus:ring-2 focus:ring-blue-500"
+          placeholder="Username"
+          autoComplete="username"
+          autoFocus
+          value={username}
+          onChange={(e) => setUsername(e.target.value)}
+        />
+        <input
+          className="w-full border border-slate-300 rounded-lg px-3 py-2 focus:outline-none focus:ring-2 focus:ring-blue-500"
+          type="password"
+          placeholder={usePassword ? "Password" : "PIN"}
+          inputMode={usePassword ? "text" : "numeric"}
+          autoComplete={usePassword ? "current-password" : "off"}
+          value={secret}
+          onChange={(e) => setSecret(usePassword ? e.target.value : e.target.value.replace(/\D/g, "").slice(0, 8))}
+        />
+
+        {error && <div className="text-sm text-red-600">{error}</div>}
+
+        <button
+          type="submit"
+          disabled={busy || !username.trim() || !secret}
+          className="w-full flex items-center justify-center gap-2 bg-blue-600 hover:bg-blue-700 disabled:opacity-50 text-white font-semibold rounded-lg py-2 transition-colors"
+        >
+          <LogIn className="w-4 h-4" />
+          {busy ? "Signing in..." : "Sign in"}
+        </button>
+        <button
+          type="button"
+          className="w-full text-sm text-slate-500 hover:text-slate-800"
+          onClick={() => {
+            setUsePassword(!usePassword);
+            setSecret("");
+            setError(null);
+          }}
+        >
+          {usePassword ? "Use PIN instead" : "Use password instead"}
+        </button>
+      </form>
+    </div>
+  );
+}
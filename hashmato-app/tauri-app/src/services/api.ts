@@ -1,4 +1,7 @@
 import axios from "axios";
+import {
+  AdjustmentRequest, DrawerOpening, DrawerReason, KitchenStation, ReceiptTemplate, ReceiptTemplatePayload, OrderItemStatus, PrintJob, PrintJobStatus, SalesReport, StationTicket, SyncMutation, ZReport,
+} from "../types";
 
 export const API_BASE = import.meta.env.VITE_API_BASE || "https://hashmato-app.onrender.com";
 
@@ -7,6 +10,71 @@ const client = axios.create({
   timeout: 10000,
 });
 
+const TOKEN_KEY = "hashmato.staffToken";
+
+type SessionListener = (signedIn: boolean) => void;
+const sessionListeners: SessionListener[] = [];
+
+// Staff session token, sent on every request once signed in.
+client.interceptors.request.use(config => {
+  const token = localStorage.getItem(TOKEN_KEY);
+  if (token) config.headers.Authorization = `Bearer ${token}`;
+  return config;
+});
+
+// An expired or revoked session signs the till out, so staff get the sign-in screen again.
+client.interceptors.response.use(undefined, error => {
+  if (error.response?.status === 401 && isSignedIn() && error.config?.url !== "/auth/login") {
+    setToken(null);
+  }
+  return Promise.reject(error);
+});
+
+function setToken(token: string | null) {
+  if (token) localStorage.setItem(TOKEN_KEY, token);
+  else localStorage.removeItem(TOKEN_KEY);
+  sessionListeners.forEach(l => l(!!token));
+}
+
+// Sent to /ws so the socket gets order, print and station events, not just the queue and menu.
+export function sessionToken() {
+  return localStorage.getItem(TOKEN_KEY);
+}
+
+export function isSignedIn() {
+  return !!localStorage.getItem(TOKEN_KEY);
+}
+
+export function onSessionChange(cb: SessionListener) {
+  sessionListeners.push(cb);
+  return () => {
+    const idx = sessionListeners.indexOf(cb);
+    if (idx >= 0) sessionListeners.splice(idx, 1);
+  };
+}
+
+export async function login(username: string, credentials: { pin?: string; password?: string }) {
+  const res = await client.post("/auth/login", { username, ...credentials });
+  setToken(res.data.token);
+  return res.data;
+}
+
+export function logout() {
+  setToken(null);
+}
+
+export async function currentStaff() {
+  return client.get("/auth/me").then(r => r.data);
+}
+
+export async function listRoles() {
+  return client.get("/roles").then(r => r.data);
+}
+
+export async function setStaffRoles(staffId: number, roles: string[]) {
+  return client.put(`/staff/${staffId}/roles`, { roles }).then(r => r.data);
+}
+
 export async function fetchMenu() {
   const res = await client.get("/menu");
   return res.data;
@@ -38,28 +106,164 @@ export async function listQueue() {
   return res.data;
 }
 
+// Announce a ready token on the queue screens; recall repeats it for a missed call.
+export async function callToken(label: string) {
+  return client.post(`/queue/${encodeURIComponent(label)}/call`).then(r => r.data);
+}
+
+export async function recallToken(label: string) {
+  return client.post(`/queue/${encodeURIComponent(label)}/recall`).then(r => r.data);
+}
+
 export async function updateOrderStatus(id: number, status: string) {
   return client.put(`/orders/${id}/status`, { status });
 }
 
-export async function syncMenu(since?: string) {
-  const params = since ? { since } : {};
-  return client.get("/sync/menu", { params }).then(r => r.data);
+export async function recordPayment(
+  orderId: number,
+  payment: { tender: "cash" | "card" | "other"; amount?: string; tendered?: string; reference?: string }
+) {
+  return client.post(`/orders/${orderId}/payments`, payment).then(r => r.data);
 }
 
-export async function syncOrders(since?: string) {
-  const params = since ? { since } : {};
-  return client.get("/sync/orders", { params }).then(r => r.data);
+export async function updateOrderItemStatus(itemId: number, status: OrderItemStatus) {
+  return client.put(`/order-items/${itemId}/status`, { status }).then(r => r.data);
 }
 
-export async function printReceipt(orderId: number) {
-  const res = await axios.post(`${API_BASE}/print/${orderId}`);
-  return res.data;
+export async function listStations(): Promise<KitchenStation[]> {
+  return client.get("/stations").then(r => r.data);
 }
 
-export async function openDrawer() {
-  const res = await axios.post(`${API_BASE}/drawer/open`);
-  return res.data;
+export async function listStationTickets(stationId: number): Promise<StationTicket[]> {
+  return client.get(`/stations/${stationId}/tickets`).then(r => r.data);
+}
+
+export async function listPayments(orderId: number) {
+  return client.get(`/orders/${orderId}/payments`).then(r => r.data);
+}
+
+export async function voidOrderItems(orderId: number, request: AdjustmentRequest) {
+  return client.post(`/orders/${orderId}/void`, request).then(r => r.data);
+}
+
+export async function refundOrder(orderId: number, request: AdjustmentRequest) {
+  return client.post(`/orders/${orderId}/refunds`, request).then(r => r.data);
+}
+
+export async function syncMenu(cursor = 0) {
+  return client.get("/menu/sync", { params: { cursor } }).then(r => r.data);
+}
+
+export async function syncOrders(cursor = 0) {
+  return client.get("/orders/sync", { params: { cursor } }).then(r => r.data);
+}
+
+export async function syncPush(mutations: SyncMutation[]) {
+  return client.post("/sync/push", { mutations }).then(r => r.data);
+}
+
+export async function xReport(): Promise<SalesReport> {
+  return client.get("/reports/x").then(r => r.data);
+}
+
+export async function closeDay(counted_cash?: string, float_left?: string): Promise<ZReport> {
+  return client.post("/reports/z", { counted_cash, float_left }).then(r => r.data);
+}
+
+export async function listZReports(): Promise<ZReport[]> {
+  return client.get("/reports/z").then(r => r.data);
+}
+
+export type SalesBreakdown = "items" | "hours" | "weekdays" | "heatmap" | "sources";
+
+export async function salesAnalytics(
+  breakdown: SalesBreakdown,
+  params: { from?: string; to?: string; sort?: "quantity" | "revenue" } = {},
+) {
+  return client.get(`/reports/${breakdown}`, { params }).then(r => r.data);
+}
+
+export async function salesAnalyticsCsv(
+  breakdown: SalesBreakdown,
+  params: { from?: string; to?: string; sort?: "quantity" | "revenue" } = {},
+): Promise<Blob> {
+  return client
+    .get(`/reports/${breakdown}`, { params: { ...params, format: "csv" }, responseType: "blob" })
+    .then(r => r.data);
+}
+
+// Queues a receipt; whichever till drives `printer` prints it.
+export async function printReceipt(orderId: number, printer?: string) {
+  const res = await client.post(`/print/${orderId}`, printer ? { printer } : undefined);
+  return res.data as PrintJob;
+}
+
+// Prints an order's kitchen tickets again, at every station with a printer or just one.
+export async function reprintKitchenTickets(orderId: number, stationId?: number) {
+  const res = await client.post(`/orders/${orderId}/kitchen-tickets`, stationId ? { station_id: stationId } : undefined);
+  return res.data as PrintJob[];
+}
+
+export async function listPrintJobs(params: { status?: PrintJobStatus; printer?: string; order_id?: number; limit?: number } = {}) {
+  const res = await client.get(`/print-jobs`, { params });
+  return res.data as PrintJob[];
+}
+
+// The next job for one of `printers`, or null when there is nothing to print.
+export async function claimPrintJob(printers: string[], clientName: string) {
+  const res = await client.post(`/print-jobs/claim`, { printers, client: clientName });
+  return res.status === 204 ? null : (res.data as PrintJob);
+}
+
+export async function reportPrintJob(id: number, clientName: string, result: { ok: true } | { ok: false; error: string }) {
+  const res = await client.post(`/print-jobs/${id}/result`, { client: clientName, ...result });
+  return res.data as PrintJob;
+}
+
+export async function retryPrintJob(id: number) {
+  const res = await client.post(`/print-jobs/${id}/retry`);
+  return res.data as PrintJob;
+}
+
+// Records a drawer opening for the cash audit; the till kicks the drawer once this succeeds.
+export async function logDrawerOpening(opening: { reason: DrawerReason; order_id?: number; note?: string }) {
+  const res = await client.post(`/drawer/open`, opening);
+  return res.data as DrawerOpening;
+}
+
+// Marks a recorded opening as one where the drawer never opened.
+export async function reportDrawerKickFailure(id: number, error: string) {
+  const res = await client.post(`/drawer/openings/${id}/failed`, { error });
+  return res.data as DrawerOpening;
+}
+
+export async function getDefaultReceiptTemplate() {
+  const res = await client.get(`/receipt-templates/default`);
+  return res.data as ReceiptTemplate;
+}
+
+export async function listReceiptTemplates() {
+  const res = await client.get(`/receipt-templates`);
+  return res.data as ReceiptTemplate[];
+}
+
+export async function createReceiptTemplate(template: ReceiptTemplatePayload) {
+  const res = await client.post(`/receipt-templates`, template);
+  return res.data as ReceiptTemplate;
+}
+
+export async function updateReceiptTemplate(id: number, template: ReceiptTemplatePayload) {
+  const res = await client.put(`/receipt-templates/${id}`, template);
+  return res.data as ReceiptTemplate;
+}
+
+export async function deleteReceiptTemplate(id: number) {
+  return client.delete(`/receipt-templates/${id}`);
+}
+
+export async function listDrawerOpenings(params: { z_report_id?: number; staff_id?: number } = {}) {
+  const res = await client.get(`/drawer/openings`, { params });
+  return res.data as DrawerOpening[];
 }
 export async function fetchOrder(orderId: number) {
   const res = await client.get(`/orders/${orderId}/detailed`);
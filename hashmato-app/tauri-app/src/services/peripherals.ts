@@ -1,32 +1,175 @@
-import { ReceiptPayload, OrderDetailed } from "../types";
+import * as api from "./api";
+import { connectWS, onEvent } from "./ws";
+import { AdjustmentRequest, DrawerReason, PrintJob, ReceiptJob, ReceiptPayload, ReceiptTemplate, OrderDetailed } from "../types";
 
 function showModal(type: "drawer" | "receipt", payload?: any) {
   const event = new CustomEvent("peripheral-action", { detail: { type, payload } });
   window.dispatchEvent(event);
 }
 
-export async function openDrawer(): Promise<void> {
-  if (window.__TAURI__) {
-    await window.__TAURI__.invoke("open_drawer");
-  } else {
+// The opening is recorded before the drawer is kicked, so there is never an opening
+// without an audit row; if the server refuses or can't be reached, the drawer stays shut.
+// A failed kick is reported back on the row, so the audit only counts real openings.
+// Throws if the printer the drawer hangs off is offline.
+export async function openDrawer(reason: DrawerReason, opts: { orderId?: number; note?: string } = {}): Promise<void> {
+  const opening = await api.logDrawerOpening({ reason, order_id: opts.orderId, note: opts.note });
+  if (!window.__TAURI__) {
     showModal("drawer");
+    return;
+  }
+  try {
+    await window.__TAURI__.invoke("open_drawer");
+  } catch (e) {
+    await api.reportDrawerKickFailure(opening.id, String(e)).catch((err) => console.warn("could not record the failed drawer kick", err));
+    throw e;
   }
 }
 
-export async function printReceipt(order: OrderDetailed): Promise<void> {
-  const payload: ReceiptPayload = {
+// Refunds through the order's payments; cash going back out opens the drawer.
+export async function refundOrder(orderId: number, request: AdjustmentRequest) {
+  const result = await api.refundOrder(orderId, request);
+  if (result.open_drawer) await openDrawer("refund", { orderId });
+  return result;
+}
+
+// Fetched once per session; without one the app prints with its built-in layout.
+let template: Promise<ReceiptTemplate | null> | null = null;
+
+function receiptTemplate() {
+  template ??= api.getDefaultReceiptTemplate().catch((e) => {
+    console.warn("no receipt template, using the built-in layout", e);
+    template = null;
+    return null;
+  });
+  return template;
+}
+
+function receiptPayload(order: OrderDetailed, locale?: string): ReceiptPayload {
+  return {
     order_id: order.order.id,
+    token_label: order.order.token_label,
+    placed_at: new Date(order.order.created_at).toLocaleString(locale, { dateStyle: "short", timeStyle: "short" }),
     items: order.items.map((it) => ({
       name: it.menu_name,
       qty: it.quantity,
-      price: it.menu_price,
+      price: it.line_total,
     })),
-    total: order.items.reduce((s, it) => s + it.menu_price * it.quantity, 0),
+    subtotal: order.order.subtotal,
+    tax_total: order.order.tax_total,
+    total: order.order.total,
   };
+}
+
+export async function printReceipt(order: OrderDetailed): Promise<void> {
+  const tmpl = await receiptTemplate();
+  const payload = receiptPayload(order, tmpl?.locale);
 
   if (window.__TAURI__) {
-    await window.__TAURI__.invoke("print_receipt", { payload });
+    await window.__TAURI__.invoke("print_receipt", { payload, template: tmpl });
   } else {
     showModal("receipt", payload);
   }
 }
+
+// The receipt as it will print, as plain text. Only available in the desktop app.
+export async function previewReceipt(order: OrderDetailed): Promise<string | null> {
+  if (!window.__TAURI__) return null;
+  const tmpl = await receiptTemplate();
+  return window.__TAURI__.invoke("preview_receipt", { payload: receiptPayload(order, tmpl?.locale), template: tmpl });
+}
+
+// Call after editing templates so the next receipt picks up the change.
+export function reloadReceiptTemplate() {
+  template = null;
+}
+
+function receiptJobPayload(job: ReceiptJob, locale?: string): ReceiptPayload {
+  return {
+    ...job,
+    placed_at: new Date(job.placed_at).toLocaleString(locale, { dateStyle: "short", timeStyle: "short" }),
+  };
+}
+
+async function runPrintJob(job: PrintJob) {
+  switch (job.kind) {
+    case "receipt": {
+      const tmpl = await receiptTemplate();
+      const payload = receiptJobPayload(job.payload, tmpl?.locale);
+      await window.__TAURI__!.invoke("print_receipt", { payload, template: tmpl, printer: job.printer });
+      break;
+    }
+    case "kitchen_ticket": {
+      const tmpl = await receiptTemplate();
+      const ticket = {
+        ...job.payload,
+        placed_at: new Date(job.payload.placed_at).toLocaleString(tmpl?.locale, { dateStyle: "short", timeStyle: "short" }),
+      };
+      await window.__TAURI__!.invoke("print_kitchen_ticket", { ticket, printer: job.printer, paperWidth: job.payload.paper_width });
+      break;
+    }
+    default:
+      throw new Error(`Unknown print job kind ${(job as PrintJob).kind}`);
+  }
+}
+
+// Names this till in job claims; kept across restarts so its claims are recognisably its own.
+function printClientName() {
+  let name = localStorage.getItem("printClient");
+  if (!name) {
+    name = `till-${crypto.randomUUID().slice(0, 8)}`;
+    localStorage.setItem("printClient", name);
+  }
+  return name;
+}
+
+const PRINT_POLL_MS = 15000;
+
+// Prints queued jobs (kiosk receipts, kitchen tickets, reprints) on the printers this
+// till drives. Jobs are claimed as soon as `print.queued` arrives, and on a timer in
+// case an event was missed. Desktop app only; returns a function that stops it.
+export async function startPrintWorker(): Promise<() => void> {
+  if (!window.__TAURI__) return () => {};
+  const printers: string[] = await window.__TAURI__.invoke("list_printers");
+  if (printers.length === 0) return () => {};
+  const clientName = printClientName();
+
+  let running = false;
+  let again = false;
+  async function drain() {
+    if (running) {
+      again = true;
+      return;
+    }
+    running = true;
+    try {
+      do {
+        again = false;
+        let job: PrintJob | null;
+        while ((job = await api.claimPrintJob(printers, clientName))) {
+          try {
+            await runPrintJob(job);
+            await api.reportPrintJob(job.id, clientName, { ok: true });
+          } catch (e) {
+            await api.reportPrintJob(job.id, clientName, { ok: false, error: String(e) });
+          }
+        }
+      } while (again);
+    } catch (e) {
+      console.warn("print jobs unavailable", e);
+    } finally {
+      running = false;
+    }
+  }
+
+  connectWS();
+  const unsubscribe = onEvent("print", (event) => {
+    if (event.type === "print.queued" && printers.includes(event.payload.printer)) drain();
+  });
+  const timer = setInterval(drain, PRINT_POLL_MS);
+  drain();
+
+  return () => {
+    unsubscribe();
+    clearInterval(timer);
+  };
+}
@@ -1,22 +1,35 @@
 import { db } from "../db/dexie";
 import * as api from "./api";
-import { AppMenuItem, LocalOrder } from "../types";
+import { AppMenuItem, LocalOrder, SyncMutation, SyncPage } from "../types";
+
+const MENU_CURSOR_KEY = "hashmato.menuCursor";
 
 export async function pullMenuDelta() {
-  const lastRecord = await db.menu.orderBy("updated_at").last();
-  const lastUpdated = lastRecord?.updated_at;
-  const res = await api.syncMenu(lastUpdated);
-
-  const items: AppMenuItem[] = res.menu ?? [];
-  for (const it of items) {
-    await db.menu.put({
-      id: it.id,
-      name: it.name,
-      price: Number(it.price),
-      available: it.available,
-      image_url: it.image_url,
-      updated_at: it.updated_at,
-    });
+  let cursor = Number(localStorage.getItem(MENU_CURSOR_KEY) ?? 0);
+
+  for (;;) {
+    const page: SyncPage = await api.syncMenu(cursor);
+
+    for (const change of page.changes) {
+      if (change.entity !== "menu_item") continue;
+      if (change.op === "delete") {
+        await db.menu.delete(change.id);
+        continue;
+      }
+      const it: AppMenuItem = change.data;
+      await db.menu.put({
+        id: it.id,
+        name: it.name,
+        price: Number(it.price),
+        available: it.available,
+        image_url: it.image_url,
+        updated_at: it.updated_at,
+      });
+    }
+
+    cursor = page.next_cursor;
+    localStorage.setItem(MENU_CURSOR_KEY, String(cursor));
+    if (!page.has_more) break;
   }
 }
 
@@ -25,15 +38,42 @@ export async function pushLocalOrders() {
     .where("synced")
     .equals(0)
     .toArray();
+  if (pending.length === 0) return;
 
+  // Older local orders predate client ids; give them one so later retries are safe.
   for (const o of pending) {
-    const payload = { source: o.source, items: o.items };
-    try {
-      await api.createOrder(payload);
-      o.synced = 1;
+    if (!o.client_order_id) {
+      o.client_order_id = crypto.randomUUID();
       await db.orders.put(o);
-    } catch (e) {
-      console.error("sync order failed", e);
+    }
+  }
+
+  const mutations: SyncMutation[] = pending.map((o) => ({
+    type: "order.create",
+    client_order_id: o.client_order_id,
+    source: o.source,
+    items: o.items,
+  }));
+
+  try {
+    const res = await api.syncPush(mutations);
+    for (const [i, r] of res.results.entries()) {
+      if (r.result === "applied" || r.result === "duplicate") {
+        await db.orders.update(pending[i].id!, { synced: 1 });
+      } else {
+        console.error("sync order rejected", r.error);
+      }
+    }
+  } catch (e: any) {
+    // Kiosks aren't signed in; they can still place orders one at a time.
+    if (e?.response?.status !== 401) throw e;
+    for (const o of pending) {
+      try {
+        await api.createOrder({ source: o.source, items: o.items, client_order_id: o.client_order_id });
+        await db.orders.update(o.id!, { synced: 1 });
+      } catch (err) {
+        console.error("sync order failed", err);
+      }
     }
   }
 }
@@ -1,15 +1,54 @@
+import { onSessionChange, sessionToken } from "./api";
+
 type QueueListener = (data: any) => void;
+type EventListener = (event: WsEvent) => void;
+
+export interface WsEvent {
+  v: number;
+  seq: number;
+  type: string;   // e.g. "queue.snapshot", "order.created"
+  topic: string;  // "queue" | "order" | "menu" | "print"
+  at: string;
+  payload: any;
+}
 
 let socket: WebSocket | null = null;
 const listeners: QueueListener[] = [];
+const eventListeners: { topic: string; cb: EventListener }[] = [];
+
+// Where we left off, so a reconnect only replays what was missed.
+let epoch: string | null = null;
+let lastSeq = 0;
 
 export function connectWS() {
   if (socket && socket.readyState === WebSocket.OPEN) return;
-  socket = new WebSocket((import.meta.env.VITE_API_WS || (import.meta.env.VITE_API_BASE || "http://localhost:8080"))!.replace(/^http/, "ws") + "/ws");
+  const base = (import.meta.env.VITE_API_WS || (import.meta.env.VITE_API_BASE || "http://localhost:8080"))!.replace(/^http/, "ws") + "/ws";
+  const params = new URLSearchParams();
+  const token = sessionToken();
+  if (token) params.set("token", token);
+  if (epoch) {
+    params.set("epoch", epoch);
+    params.set("last_seq", String(lastSeq));
+  }
+  const query = params.toString();
+  socket = new WebSocket(query ? `${base}?${query}` : base);
   socket.onmessage = (evt) => {
     try {
       const data = JSON.parse(evt.data);
-      listeners.forEach(l => l(data));
+      if (data?.type === "hello") {
+        if (epoch !== data.epoch) lastSeq = data.seq;
+        epoch = data.epoch;
+        return;
+      }
+      if (typeof data?.seq !== "number") return; // protocol replies (subscribed, pong, error)
+      const event = data as WsEvent;
+      if (event.seq > 0) lastSeq = Math.max(lastSeq, event.seq);
+      if (event.type === "queue.snapshot") {
+        listeners.forEach(l => l(event.payload.tokens));
+      } else if (event.type === "sync.snapshot" && event.payload.queue) {
+        listeners.forEach(l => l(event.payload.queue));
+      }
+      eventListeners.filter(l => l.topic === event.topic || event.topic === "sync").forEach(l => l.cb(event));
     } catch (e) { console.warn(e); }
   };
   socket.onopen = () => console.info("ws open");
@@ -19,6 +58,10 @@ export function connectWS() {
   };
 }
 
+// Orders, print jobs and station feeds are only sent to signed-in sockets; reconnect
+// (resuming where we left off) when the session changes so the topics follow it.
+onSessionChange(() => socket?.close());
+
 export function onQueueUpdate(cb: QueueListener) {
   listeners.push(cb);
   return () => {
@@ -26,3 +69,16 @@ export function onQueueUpdate(cb: QueueListener) {
     if (idx >= 0) listeners.splice(idx, 1);
   };
 }
+
+export function onEvent(topic: "queue" | "order" | "menu" | "print", cb: EventListener) {
+  const entry = { topic, cb };
+  eventListeners.push(entry);
+  return () => {
+    const idx = eventListeners.indexOf(entry);
+    if (idx >= 0) eventListeners.splice(idx, 1);
+  };
+}
+
+export function onMenuUpdate(cb: EventListener) {
+  return onEvent("menu", cb);
+}
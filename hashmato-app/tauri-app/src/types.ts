@@ -5,30 +5,101 @@ export interface AppMenuItem {
   price: number;
   available: boolean;
   image_url?: string;
+  category_id?: number | null;
+  station_id?: number | null;
+  prep_seconds?: number;
   created_at?: string; // keep optional for Dexie/local
   updated_at?: string;
 }
 
+export interface MenuCategory {
+  id: number;
+  name: string;
+  parent_id: number | null;
+  display_order: number;
+  active: boolean;
+  created_at?: string;
+  updated_at?: string;
+}
+
+export interface MenuCategoryNode extends MenuCategory {
+  items: AppMenuItem[];
+  children: MenuCategoryNode[];
+}
+
+export interface MenuTree {
+  categories: MenuCategoryNode[];
+  uncategorized: AppMenuItem[];
+}
+
 // ---------- Orders ----------
 export interface OrderItem {
   id?: number;           // DB id
   order_id?: number;     // reference to order
   menu_item_id: number;
   quantity: number;
+  modifiers?: number[];  // chosen modifier option ids
+  note?: string | null;  // cooking instructions, printed on kitchen tickets
+}
+
+export interface OrderItemModifier {
+  id: number;
+  order_item_id: number;
+  modifier_option_id: number | null;
+  group_name: string;
+  option_name: string;
+  price_delta: string;
 }
 
 export interface Order {
   id: number;
   source: "pos" | "kiosk" | "online";
-  status: "pending" | "preparing" | "ready" | "completed";
+  status: "pending" | "preparing" | "ready" | "completed" | "cancelled" | "refunded";
+  subtotal: string;
+  tax_total: string;
+  total: string;
+  currency: string;
   created_at: string;
   updated_at: string;
+  amount_paid: string;
+  payment_status: "unpaid" | "partially_paid" | "paid";
+  token_label?: string | null;
+}
+
+export interface Payment {
+  id: number;
+  order_id: number;
+  tender: "cash" | "card" | "other";
+  amount: string;
+  tendered: string;
+  change_given: string;
+  reference: string | null;
+  staff_id: number | null;
+  created_at: string;
+  refund_of: number | null;
+  adjustment_id: number | null;
+}
+
+export interface AdjustmentRequest {
+  reason_code: string;
+  note?: string;
+  items?: { order_item_id: number; quantity: number }[];
+  tenders?: { payment_id: number; amount: string }[];
+  approval?: { username: string; pin: string };
 }
 
 export interface OrderItemDetailed extends OrderItem {
   menu_name: string;
   menu_price: number;
+  unit_price: string;
+  line_total: string;
+  tax_rate: string;
+  tax_inclusive: boolean;
+  tax_amount: string;
   menu_image?: string | null;
+  station_id: number | null;
+  status: OrderItemStatus;
+  modifiers: OrderItemModifier[];
 }
 
 export interface OrderDetailed {
@@ -41,15 +112,32 @@ export interface QueueToken {
   id: number;
   order_id: number;
   token_number: number;
-  status: "waiting" | "ready";
+  token_label: string;    // e.g. "K-012"
+  business_date: string;
+  status: "waiting" | "ready" | "called" | "collected" | "no_show" | "cancelled";
   created_at: string;
+  ready_at?: string | null;
+  call_count: number;
+  last_called_at?: string | null;
+  collected_at?: string | null;
+  no_show_at?: string | null;
+  archived_at?: string | null;
+  estimated_ready_at?: string | null;
+  estimated_wait_seconds?: number | null;
+}
+
+// ---------- Staff ----------
+export interface AuthStaff {
+  id: number;
+  name: string;
 }
 
 // ---------- Local Offline Order ----------
 export interface LocalOrder {
   id?: number;
+  client_order_id: string; // sent with every attempt so retries don't create duplicates
   source: "pos" | "kiosk";
-  status: "pending" | "preparing" | "ready" | "completed";
+  status: "pending" | "preparing" | "ready" | "completed" | "cancelled" | "refunded";
   created_at: string;
   updated_at?: string;
   items: OrderItem[];
@@ -59,17 +147,204 @@ export interface LocalOrder {
 // ---------- Cart ----------
 export interface CartItem extends AppMenuItem {
   quantity: number;
+  note?: string;
 }
 
 // ---------- Receipt ----------
 export interface ReceiptItem {
   name: string;
   qty: number;
-  price: number;
+  price: string;          // line total as a decimal string, e.g. "12.50"
 }
 
 export interface ReceiptPayload {
   order_id: number;
+  token_label?: string | null;
+  placed_at?: string;     // already formatted for the template's locale
   items: ReceiptItem[];
-  total: number;
+  subtotal?: string;
+  tax_total?: string;
+  total: string;
+}
+
+export type ReceiptLabel = "order" | "token" | "tax_id" | "subtotal" | "tax" | "total";
+
+export interface ReceiptTemplate {
+  id: number;
+  name: string;
+  is_default: boolean;
+  paper_width: 58 | 80;
+  store_name: string;
+  store_address: string | null;
+  tax_id: string | null;
+  header: string | null;
+  footer: string | null;
+  currency_symbol: string;
+  locale: string;          // e.g. "en-US", "de-DE"
+  qr_code: string | null;  // "{order_id}" and "{token}" are filled in
+  // 1-bit raster, base64: rows of ceil(width / 8) bytes, most significant bit leftmost
+  logo_width: number | null;
+  logo_height: number | null;
+  logo_data: string | null;
+  labels: Partial<Record<ReceiptLabel, string>>;
+  created_at: string;
+  updated_at: string;
+}
+
+export type ReceiptTemplatePayload = Omit<ReceiptTemplate, "id" | "created_at" | "updated_at">;
+
+// ---------- Print jobs ----------
+export type PrintJobStatus = "queued" | "claimed" | "printed" | "failed";
+
+// Payload of a "receipt" job; amounts are strings and placed_at is an ISO timestamp.
+export interface ReceiptJob {
+  order_id: number;
+  token_label: string | null;
+  placed_at: string;
+  items: { name: string; qty: number; price: string }[];
+  subtotal: string;
+  tax_total: string;
+  total: string;
+}
+
+// Payload of a "kitchen_ticket" job.
+export interface KitchenTicketJob extends StationTicket {
+  reprint: boolean;
+  paper_width: 58 | 80;   // the station printer's roll, not the receipt printer's
+}
+
+interface PrintJobBase {
+  id: number;
+  printer: string;        // e.g. "receipt", "kitchen"
+  order_id: number | null;
+  station_id: number | null;
+  status: PrintJobStatus;
+  attempts: number;
+  max_attempts: number;
+  available_at: string;
+  claimed_by: string | null;
+  claimed_at: string | null;
+  last_error: string | null;
+  requested_by: number | null;
+  printed_at: string | null;
+  created_at: string;
+  updated_at: string;
+}
+
+export type PrintJob =
+  | (PrintJobBase & { kind: "receipt"; payload: ReceiptJob })
+  | (PrintJobBase & { kind: "kitchen_ticket"; payload: KitchenTicketJob });
+
+// ---------- Sync ----------
+export interface SyncChange<T = any> {
+  change_id: number;
+  entity: "menu_item" | "menu_category" | "modifier_group" | "modifier_option" | "tax_rate" | "order";
+  id: number;
+  op: "upsert" | "delete";
+  data: T | null;
+}
+
+export interface SyncPage {
+  changes: SyncChange[];
+  next_cursor: number;
+  has_more: boolean;
+}
+
+export type SyncMutation =
+  | { type: "order.create"; client_order_id: string; source: string; items: OrderItem[] }
+  | { type: "order.status"; order_id: number; status: string }
+  | { type: "menu_item.availability"; id: number; available: boolean; base_change_id?: number };
+
+// ---------- Reports ----------
+export interface SalesReport {
+  period_start: string | null;
+  period_end: string;
+  currency: string;
+  order_count: number;
+  gross_sales: string;
+  discounts: string;
+  voids: string;
+  void_count: number;
+  refunds: string;
+  refund_count: number;
+  net_sales: string;
+  tax: string;
+  total: string;
+  tenders: { tender: string; payment_count: number; taken: string; refunded: string; net: string }[];
+  sources: { source: string; order_count: number; gross_sales: string; net_sales: string }[];
+  cash: {
+    opening_float: string;
+    cash_taken: string;
+    cash_refunded: string;
+    expected_in_drawer: string;
+    counted: string | null;
+    variance: string | null;
+    drawer_openings?: number;
+    no_sale_openings?: number;
+  };
+}
+
+export interface ZReport {
+  id: number;
+  business_date: string;
+  period_start: string | null;
+  period_end: string;
+  opening_float: string;
+  counted_cash: string | null;
+  float_left: string;
+  closed_by: number | null;
+  report: SalesReport;
+  created_at: string;
+}
+
+export type DrawerReason = "sale" | "refund" | "no_sale" | "float" | "count";
+
+export interface DrawerOpening {
+  id: number;
+  staff_id: number;
+  staff_name: string;
+  reason: DrawerReason;
+  order_id: number | null;
+  note: string | null;
+  opened_at: string;
+  z_report_id: number | null;
+  error: string | null;   // why the drawer didn't open after all
+}
+
+// ---------- Kitchen ----------
+export type OrderItemStatus = "queued" | "cooking" | "done";
+
+export interface KitchenStation {
+  id: number;
+  code: string;
+  name: string;
+  display_order: number;
+  active: boolean;
+  capacity: number;
+  printer: string | null;  // ticket printer, by the name tills use for it
+  auto_print: boolean;     // print a ticket for every new order with lines here
+  paper_width: 58 | 80;    // roll width of the ticket printer, in mm
+}
+
+/** One order's lines for one station, as sent on the `station:<code>` WebSocket topic. */
+export interface StationTicket {
+  station_id: number;
+  station_code: string;
+  station_name: string;
+  order_id: number;
+  token_label: string | null;
+  source: string;
+  order_status: string;
+  placed_at: string;
+  items: {
+    id: number;
+    menu_item_id: number;
+    name: string;
+    quantity: number;
+    modifiers: string[];
+    note: string | null;
+    status: OrderItemStatus;
+    started_at: string | null;
+    done_at: string | null;
+  }[];
 }